@@ -0,0 +1,692 @@
+//! The tokenising loop of `main()` from before the lexer was split out into
+//! a library, kept so the benchmark can show how far the current lexer has
+//! come. The loop is copied as it was, except that the token types are
+//! reduced to what it needs and malformed input is counted instead of
+//! printed.
+
+use std::collections::HashMap;
+
+use regex::Regex;
+
+#[allow(dead_code)]
+enum TokenType {
+    Identifier(String),
+    Comment(String),
+    Operator(&'static str),
+    Literal(String),
+    Keyword(&'static str),
+    Number(String),
+    Delimiter(&'static str),
+    Character(char),
+}
+
+#[allow(dead_code)]
+struct Token {
+    line: usize,
+    block: usize,
+    token_type: TokenType,
+}
+
+/// Tokenises `file` the way the old `main()` did, returning the number of
+/// tokens and the number of errors it reported.
+#[allow(clippy::all)]
+pub fn lex(file: &str) -> (usize, usize) {
+    let mut errors = 0;
+    let keywords: HashMap<&str, &str> = HashMap::from([
+        ("auto", "Auto"),
+        ("break", "Break"),
+        ("case", "Case"),
+        ("char", "Char"),
+        ("const", "Const"),
+        ("continue", "Continue"),
+        ("default", "Default"),
+        ("do", "Do"),
+        ("double", "Double"),
+        ("else", "Else"),
+        ("enum", "Enum"),
+        ("extern", "Extern"),
+        ("float", "Float"),
+        ("for", "For"),
+        ("goto", "Goto"),
+        ("if", "If"),
+        ("inline", "Inline"),
+        ("int", "Int"),
+        ("long", "Long"),
+        ("register", "Register"),
+        ("restrict", "Restrict"),
+        ("return", "Return"),
+        ("short", "Short"),
+        ("signed", "Signed"),
+        ("sizeof", "Sizeof"),
+        ("static", "Static"),
+        ("struct", "Struct"),
+        ("switch", "Switch"),
+        ("typedef", "Typedef"),
+        ("union", "Union"),
+        ("unsigned", "Unsigned"),
+        ("void", "Void"),
+        ("volatile", "Volatile"),
+        ("while", "Wihle"),
+        ("_Bool", "_Bool"),
+        ("_Complex", "_Complex"),
+        ("_Imaginary", "_Imaginary"),
+    ]);
+
+    let operators: HashMap<&str, &str> = HashMap::from([
+        // Arithmetic Operators
+        ("+", "Addition"),
+        ("-", "Subtraction"),
+        ("*", "Multiplication"),
+        ("/", "Division"),
+        ("%", "Remainder"),
+        ("++", "Increment"),
+        ("--", "Decrement"),
+        // Relational Operators
+        ("==", "Equal"),
+        ("!=", "NotEqual"),
+        (">", "Bigger"),
+        (">=", "BiggerOrEqual"),
+        ("<", "Smaller"),
+        ("<=", "SmallerOrEqual"),
+        // Logical and Bitwise Operators
+        ("&&", "LogicalAnd"),
+        ("||", "LogicalOr"),
+        ("&", "BitwiseAnd"),
+        ("!", "LogicalNegation"),
+        ("|", "BitwiseOr"),
+        ("^", "ExclusiveOr"),
+        ("~", "BitwiseNegation"),
+        ("<<", "LeftShift"),
+        (">>", "RightShift"),
+        // Assignment Operators
+        ("=", "Assignment"),
+        ("+=", "AdditionAndAssignment"),
+        ("-=", "SubtractionAndAssignment"),
+        ("*=", "MultiplicationAndAssignment"),
+        ("/=", "DivisionAndAssignment"),
+        ("%=", "RemainderAndAssignment"),
+        (">>=", "RightShiftAndAssignment"),
+        ("<<=", "LeftShiftAndAssignment"),
+        ("&=", "BitwiseAndAssignment"),
+        ("|=", "BitwiseOrAssignment"),
+        ("^=", "BitwiseExclusiveOrAndAssignment"),
+    ]);
+
+    let delimiters: HashMap<&str, &str> = HashMap::from([
+        (",", "Comma"),
+        (";", "SemiColon"),
+        ("(", "OpeningParenthesis"),
+        (")", "ClosingParenthesis"),
+        ("[", "OpeningBracket"),
+        ("]", "ClosingBracket"),
+        ("{", "OpeningCurlyBracket"),
+        ("}", "ClosingCurlyBracket"),
+    ]);
+
+    let mut starting_index = 0;
+    let mut ending_index = 0;
+    let mut starting_line = 0;
+    let mut starting_block = 0;
+
+    let mut tokens: Vec<Token> = vec![];
+
+    let mut is_inside_block_comments = false;
+    let mut is_inside_linear_comments = false;
+    let mut is_inside_literal = false;
+    let mut line = 1;
+    let mut block = 1;
+    let characters: Vec<char> = file.chars().collect();
+    let mut index = 0;
+    let identifer_regex = Regex::new(r"^([a-zA-Z_])(0-9a-zA-Z_)*").unwrap();
+    let number_regex = Regex::new(r"^(-|\+)?\d+(.\d+)?*").unwrap();
+    while index + 1 != characters.len() {
+        let character = characters.get(index).unwrap();
+        if is_inside_linear_comments {
+            if character == &'\n' {
+                is_inside_linear_comments = false;
+                tokens.push(Token {
+                    line,
+                    block: starting_block,
+                    token_type: TokenType::Comment(
+                        characters
+                            .get(starting_index..(starting_index + ending_index))
+                            .unwrap()
+                            .iter()
+                            .collect(),
+                    ),
+                });
+                line += 1;
+                block = 1;
+                starting_block = 0;
+                starting_index = 0;
+                ending_index = 0;
+            } else {
+                ending_index += 1;
+                block += 1;
+            }
+            index += 1;
+            continue;
+        } else if is_inside_block_comments {
+            if character == &'*' && characters.get(index + 1).unwrap() == &'/' {
+                is_inside_block_comments = false;
+                tokens.push(Token {
+                    line: starting_line,
+                    block: starting_block,
+                    token_type: TokenType::Comment(
+                        characters
+                            .get(starting_index..(starting_index + ending_index))
+                            .unwrap()
+                            .iter()
+                            .collect(),
+                    ),
+                });
+                starting_index = 0;
+                starting_block = 0;
+                starting_line = 0;
+                block += 2;
+                ending_index = 0;
+                index += 2;
+            } else {
+                if *character == '\n' {
+                    line += 1;
+                }
+                block += 1;
+                ending_index += 1;
+                index += 1;
+            }
+            continue;
+        } else if is_inside_literal {
+            if character == &'"' {
+                is_inside_literal = false;
+                tokens.push(Token {
+                    line: starting_line,
+                    block: starting_block,
+                    token_type: TokenType::Literal(
+                        characters
+                            .get(starting_index..(starting_index + ending_index))
+                            .unwrap()
+                            .iter()
+                            .collect(),
+                    ),
+                });
+                starting_block = 0;
+                starting_line = 0;
+                starting_index = 0;
+                block += 1;
+                ending_index = 0;
+            } else {
+                if *character == '\n' {
+                    line += 1;
+                }
+                block += 1;
+                ending_index += 1;
+            }
+            index += 1;
+            continue;
+        }
+        if character.is_whitespace() {
+            if *character == '\n' {
+                line += 1;
+                block = 0;
+            } else {
+                block += 1;
+            }
+            index += 1;
+            continue;
+        } else if character == &'/' && characters.get(index + 1).unwrap() == &'/' {
+            index += 2;
+            block += 2;
+            is_inside_linear_comments = true;
+            starting_index = index;
+            starting_block = block;
+            continue;
+        } else if character == &'/' && characters.get(index + 1).unwrap() == &'*' {
+            index += 2;
+            block += 2;
+            is_inside_block_comments = true;
+            starting_index = index;
+            starting_block = block;
+            starting_line = line;
+            continue;
+        } else if character == &'"' {
+            index += 1;
+            block += 1;
+            is_inside_literal = true;
+            starting_index = index;
+            starting_line = line;
+            starting_block = block;
+            continue;
+        }
+
+        let delimiter = match *character {
+            ',' => Some("Comma"),
+            ';' => Some("SemiColon"),
+            '(' => Some("OpeningParenthesis"),
+            ')' => Some("ClosingParenthesis"),
+            '[' => Some("OpeningBracket"),
+            ']' => Some("ClosingBracket"),
+            '{' => Some("OpeningCurlyBracket"),
+            '}' => Some("ClosingCurlyBracket"),
+            _ => None,
+        };
+
+        if let Some(delimiter) = delimiter {
+            tokens.push(Token {
+                line,
+                block,
+                token_type: TokenType::Delimiter(delimiter),
+            });
+            index += 1;
+            block += 1;
+            continue;
+        }
+
+        if *character == '+' {
+            if characters[index + 1] == '+' {
+                tokens.push(Token {
+                    line,
+                    block,
+                    token_type: TokenType::Operator("Increment"),
+                });
+                index += 2;
+                block += 2;
+                continue;
+            } else if characters[index + 1] == '=' {
+                tokens.push(Token {
+                    line,
+                    block,
+                    token_type: TokenType::Operator("AdditionAndAssignment"),
+                });
+                index += 2;
+                block += 2;
+                continue;
+            } else {
+                tokens.push(Token {
+                    line,
+                    block,
+                    token_type: TokenType::Operator("Addition"),
+                });
+                index += 1;
+                block += 1;
+                continue;
+            }
+        } else if *character == '-' {
+            if characters[index + 1] == '-' {
+                tokens.push(Token {
+                    line,
+                    block,
+                    token_type: TokenType::Operator("Decrement"),
+                });
+                index += 2;
+                block += 2;
+                continue;
+            } else if characters[index + 1] == '=' {
+                tokens.push(Token {
+                    line,
+                    block,
+                    token_type: TokenType::Operator("SubtractionAndAssignment"),
+                });
+                index += 2;
+                block += 2;
+                continue;
+            } else {
+                tokens.push(Token {
+                    line,
+                    block,
+                    token_type: TokenType::Operator("Subtraction"),
+                });
+                index += 1;
+                block += 1;
+                continue;
+            }
+        } else if *character == '*' {
+            if characters[index + 1] == '=' {
+                tokens.push(Token {
+                    line,
+                    block,
+                    token_type: TokenType::Operator("MultiplicationAndAssignment"),
+                });
+                index += 2;
+                block += 2;
+                continue;
+            } else {
+                tokens.push(Token {
+                    line,
+                    block,
+                    token_type: TokenType::Operator("Multiplication"),
+                });
+                index += 1;
+                block += 1;
+                continue;
+            }
+        } else if *character == '/' {
+            if characters[index + 1] == '=' {
+                tokens.push(Token {
+                    line,
+                    block,
+                    token_type: TokenType::Operator("DivisionAndAssignment"),
+                });
+                index += 2;
+                block += 2;
+                continue;
+            } else {
+                tokens.push(Token {
+                    line,
+                    block,
+                    token_type: TokenType::Operator("Division"),
+                });
+                index += 1;
+                block += 1;
+                continue;
+            }
+        } else if *character == '%' {
+            if characters[index + 1] == '=' {
+                tokens.push(Token {
+                    line,
+                    block,
+                    token_type: TokenType::Operator("RemainderAndAssignment"),
+                });
+                index += 2;
+                block += 2;
+                continue;
+            } else {
+                tokens.push(Token {
+                    line,
+                    block,
+                    token_type: TokenType::Operator("Remainder"),
+                });
+                index += 1;
+                block += 1;
+                continue;
+            }
+        } else if *character == '=' {
+            if characters[index + 1] == '=' {
+                tokens.push(Token {
+                    line,
+                    block,
+                    token_type: TokenType::Operator("Equal"),
+                });
+                index += 2;
+                block += 2;
+                continue;
+            } else {
+                tokens.push(Token {
+                    line,
+                    block,
+                    token_type: TokenType::Operator("Assignment"),
+                });
+                index += 1;
+                block += 1;
+                continue;
+            }
+        } else if *character == '!' {
+            if characters[index + 1] == '=' {
+                tokens.push(Token {
+                    line,
+                    block,
+                    token_type: TokenType::Operator("NotEqual"),
+                });
+                index += 2;
+                block += 2;
+                continue;
+            } else {
+                tokens.push(Token {
+                    line,
+                    block,
+                    token_type: TokenType::Operator("LogicalNegation"),
+                });
+                index += 1;
+                block += 1;
+                continue;
+            }
+        } else if *character == '>' {
+            if characters[index + 1] == '=' {
+                tokens.push(Token {
+                    line,
+                    block,
+                    token_type: TokenType::Operator("BiggerOrEqual"),
+                });
+                index += 2;
+                block += 2;
+                continue;
+            } else if characters[index + 1] == '>' {
+                if characters[index + 2] == '=' {
+                    tokens.push(Token {
+                        line,
+                        block,
+                        token_type: TokenType::Operator("RightShiftAndAssignment"),
+                    });
+                    index += 3;
+                    block += 3;
+                    continue;
+                } else {
+                    tokens.push(Token {
+                        line,
+                        block,
+                        token_type: TokenType::Operator("RightShift"),
+                    });
+                    index += 2;
+                    block += 2;
+                    continue;
+                }
+            } else {
+                tokens.push(Token {
+                    line,
+                    block,
+                    token_type: TokenType::Operator("Bigger"),
+                });
+                index += 1;
+                block += 1;
+                continue;
+            }
+        } else if *character == '<' {
+            if characters[index + 1] == '=' {
+                tokens.push(Token {
+                    line,
+                    block,
+                    token_type: TokenType::Operator("SmallerOrEqual"),
+                });
+                index += 2;
+                block += 2;
+                continue;
+            } else if characters[index + 1] == '>' {
+                if characters[index + 2] == '=' {
+                    tokens.push(Token {
+                        line,
+                        block,
+                        token_type: TokenType::Operator("LeftShiftAndAssignment"),
+                    });
+                    index += 3;
+                    block += 3;
+                    continue;
+                } else {
+                    tokens.push(Token {
+                        line,
+                        block,
+                        token_type: TokenType::Operator("LeftShift"),
+                    });
+                    index += 2;
+                    block += 2;
+                    continue;
+                }
+            } else {
+                tokens.push(Token {
+                    line,
+                    block,
+                    token_type: TokenType::Operator("Smaller"),
+                });
+                index += 1;
+                block += 1;
+                continue;
+            }
+        } else if *character == '&' {
+            if characters[index + 1] == '=' {
+                tokens.push(Token {
+                    line,
+                    block,
+                    token_type: TokenType::Operator("BitwiseAndAssignment"),
+                });
+                index += 2;
+                block += 2;
+                continue;
+            } else if characters[index + 1] == '&' {
+                tokens.push(Token {
+                    line,
+                    block,
+                    token_type: TokenType::Operator("LogicalAnd"),
+                });
+                index += 2;
+                block += 2;
+                continue;
+            } else {
+                tokens.push(Token {
+                    line,
+                    block,
+                    token_type: TokenType::Operator("BitwiseAnd"),
+                });
+                index += 1;
+                block += 1;
+                continue;
+            }
+        } else if *character == '|' {
+            if characters[index + 1] == '=' {
+                tokens.push(Token {
+                    line,
+                    block,
+                    token_type: TokenType::Operator("BitwiseOrAssignment"),
+                });
+                index += 2;
+                block += 2;
+                continue;
+            } else if characters[index + 1] == '|' {
+                tokens.push(Token {
+                    line,
+                    block,
+                    token_type: TokenType::Operator("LogicalOr"),
+                });
+                index += 2;
+                block += 2;
+                continue;
+            } else {
+                tokens.push(Token {
+                    line,
+                    block,
+                    token_type: TokenType::Operator("BitwiseOr"),
+                });
+                index += 1;
+                block += 1;
+                continue;
+            }
+        } else if *character == '^' {
+            if characters[index + 1] == '=' {
+                tokens.push(Token {
+                    line,
+                    block,
+                    token_type: TokenType::Operator("BitwiseExclusiveOrAndAssignment"),
+                });
+                index += 2;
+                block += 2;
+                continue;
+            } else {
+                tokens.push(Token {
+                    line,
+                    block,
+                    token_type: TokenType::Operator("ExclusiveOr"),
+                });
+                index += 1;
+                block += 1;
+                continue;
+            }
+        } else if *character == '~' {
+            tokens.push(Token {
+                line,
+                block,
+                token_type: TokenType::Operator("BitwiseNegation"),
+            });
+            index += 1;
+            block += 1;
+            continue;
+        }
+
+        if *character == '\'' {
+            if characters.get(index + 2).unwrap() == &'\'' {
+                tokens.push(Token {
+                    line,
+                    block,
+                    token_type: TokenType::Character(characters.get(index + 1).unwrap().to_owned()),
+                });
+            } else {
+                errors += 1;
+            }
+            block += 1;
+            index += 1;
+            continue;
+        }
+
+        let mut count = 0;
+        let mut word: String = String::new();
+
+        for character2 in characters[index..].iter() {
+            if *character2 == '\n'
+                || *character2 == ' '
+                || delimiters.get(String::from(*character2).as_str()).is_some()
+                || operators.get(String::from(*character2).as_str()).is_some()
+                || operators
+                    .get(
+                        characters[(index + count)..(index + count + 1)]
+                            .iter()
+                            .collect::<String>()
+                            .as_str(),
+                    )
+                    .is_some()
+                || operators
+                    .get(
+                        characters[(index + count)..(index + count + 2)]
+                            .iter()
+                            .collect::<String>()
+                            .as_str(),
+                    )
+                    .is_some()
+            {
+                break;
+            }
+            word.push(*character2);
+            count += 1;
+        }
+
+        if let Some(keyword) = keywords.get(word.as_str()) {
+            tokens.push(Token {
+                line,
+                block,
+                token_type: TokenType::Keyword(*keyword),
+            });
+            index += count;
+            block += count;
+            continue;
+        } else if identifer_regex.is_match(&word) {
+            tokens.push(Token {
+                line,
+                block,
+                token_type: TokenType::Identifier(word),
+            });
+            index += count;
+            block += count;
+            continue;
+        } else if number_regex.is_match(&word) {
+            tokens.push(Token {
+                line,
+                block,
+                token_type: TokenType::Number(word),
+            });
+            index += count;
+            block += count;
+            continue;
+        } else {
+            errors += 1;
+        }
+
+        index += 1;
+    }
+
+    (tokens.len(), errors)
+}
@@ -0,0 +1,107 @@
+/*
+ * A configuration header in the style of large C projects: include guards,
+ * feature tests, long macros continued over several lines and platform
+ * conditionals.
+ */
+#ifndef BENCH_CONFIG_H
+#define BENCH_CONFIG_H
+
+#include <stdio.h>
+
+#if defined(__GNUC__) && !defined(__clang__)
+#define COMPILER_GCC 1
+#define LIKELY(x) __builtin_expect(!!(x), 1)
+#define UNLIKELY(x) __builtin_expect(!!(x), 0)
+#elif defined(_MSC_VER)
+#define COMPILER_MSVC 1
+#define LIKELY(x) (x)
+#define UNLIKELY(x) (x)
+#else
+#define LIKELY(x) (x)
+#define UNLIKELY(x) (x)
+#endif
+
+#if __STDC_VERSION__ >= 201112L
+#define STATIC_ASSERT(condition, message) _Static_assert(condition, message)
+#else
+#define STATIC_ASSERT(condition, message) \
+    typedef char static_assertion_##__LINE__[(condition) ? 1 : -1]
+#endif
+
+#define ARRAY_LENGTH(array) (sizeof(array) / sizeof((array)[0]))
+#define MIN(a, b) ((a) < (b) ? (a) : (b))
+#define MAX(a, b) ((a) > (b) ? (a) : (b))
+#define CLAMP(value, low, high) MIN(MAX((value), (low)), (high))
+#define STRINGIFY_(x) #x
+#define STRINGIFY(x) STRINGIFY_(x)
+#define CONCAT_(a, b) a##b
+#define CONCAT(a, b) CONCAT_(a, b)
+
+#define LOG_LEVEL_ERROR 0
+#define LOG_LEVEL_WARNING 1
+#define LOG_LEVEL_INFO 2
+#define LOG_LEVEL_DEBUG 3
+
+#ifndef LOG_LEVEL
+#define LOG_LEVEL LOG_LEVEL_INFO
+#endif
+
+#define LOG_AT(level, tag, ...)                                                  \
+    do {                                                                         \
+        if ((level) <= LOG_LEVEL) {                                              \
+            fprintf(stderr, "[%s] %s:%d: ", tag, __FILE__, __LINE__);            \
+            fprintf(stderr, __VA_ARGS__);                                        \
+            fputc('\n', stderr);                                                 \
+        }                                                                        \
+    } while (0)
+
+#define LOG_ERROR(...) LOG_AT(LOG_LEVEL_ERROR, "error", __VA_ARGS__)
+#define LOG_WARNING(...) LOG_AT(LOG_LEVEL_WARNING, "warning", __VA_ARGS__)
+#define LOG_INFO(...) LOG_AT(LOG_LEVEL_INFO, "info", __VA_ARGS__)
+#define LOG_DEBUG(...) LOG_AT(LOG_LEVEL_DEBUG, "debug", __VA_ARGS__)
+
+#define CHECK(condition)                                                         \
+    do {                                                                         \
+        if (UNLIKELY(!(condition))) {                                            \
+            LOG_ERROR("check failed: %s", #condition);                           \
+            abort();                                                             \
+        }                                                                        \
+    } while (0)
+
+#if defined(_WIN32) || defined(_WIN64)
+#define PATH_SEPARATOR '\\'
+#define PLATFORM_NAME "windows"
+#elif defined(__APPLE__)
+#define PATH_SEPARATOR '/'
+#define PLATFORM_NAME "macos"
+#else
+#define PATH_SEPARATOR '/'
+#define PLATFORM_NAME "unix"
+#endif
+
+#define VERSION_MAJOR 3
+#define VERSION_MINOR 14
+#define VERSION_PATCH 159
+#define VERSION_STRING \
+    STRINGIFY(VERSION_MAJOR) "." STRINGIFY(VERSION_MINOR) "." STRINGIFY(VERSION_PATCH)
+
+enum feature_flags {
+    FEATURE_NONE = 0,
+    FEATURE_COMPRESSION = 1 << 0,
+    FEATURE_ENCRYPTION = 1 << 1,
+    FEATURE_CHECKSUMS = 1 << 2,
+    FEATURE_ALL = FEATURE_COMPRESSION | FEATURE_ENCRYPTION | FEATURE_CHECKSUMS,
+};
+
+struct options {
+    unsigned int flags;
+    const char *input_path;
+    const char *output_path;
+    int verbosity;
+    _Bool dry_run;
+};
+
+extern int parse_options(int argc, char **argv, struct options *options);
+extern void print_usage(FILE *out, const char *program);
+
+#endif /* BENCH_CONFIG_H */
@@ -0,0 +1,183 @@
+/*
+ * An open-addressing hash map from strings to integers, written the way
+ * ordinary application code is: structs, loops, calls and the odd comment.
+ */
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+#include <string.h>
+
+#include "config.h"
+
+#define HASHMAP_MIN_CAPACITY 16u
+#define HASHMAP_MAX_LOAD 0.75
+#define HASHMAP_TOMBSTONE ((const char *)-1)
+
+typedef struct hashmap_entry {
+    const char *key;
+    size_t key_length;
+    uint64_t hash;
+    long value;
+} hashmap_entry;
+
+typedef struct hashmap {
+    hashmap_entry *entries;
+    size_t capacity;
+    size_t count;
+    size_t tombstones;
+} hashmap;
+
+/* FNV-1a, which is short and good enough for identifiers. */
+static uint64_t hash_bytes(const char *bytes, size_t length)
+{
+    uint64_t hash = 0xcbf29ce484222325ULL;
+    for (size_t i = 0; i < length; ++i) {
+        hash ^= (unsigned char)bytes[i];
+        hash *= 0x100000001b3ULL;
+    }
+    return hash;
+}
+
+static int entry_matches(const hashmap_entry *entry, const char *key, size_t length,
+                         uint64_t hash)
+{
+    return entry->key != NULL && entry->key != HASHMAP_TOMBSTONE && entry->hash == hash &&
+           entry->key_length == length && memcmp(entry->key, key, length) == 0;
+}
+
+int hashmap_init(hashmap *map, size_t capacity)
+{
+    if (capacity < HASHMAP_MIN_CAPACITY) {
+        capacity = HASHMAP_MIN_CAPACITY;
+    }
+    // Round up to a power of two so that masking replaces the modulo.
+    size_t rounded = 1;
+    while (rounded < capacity) {
+        rounded <<= 1;
+    }
+    map->entries = calloc(rounded, sizeof *map->entries);
+    if (!map->entries) {
+        return -1;
+    }
+    map->capacity = rounded;
+    map->count = 0;
+    map->tombstones = 0;
+    return 0;
+}
+
+void hashmap_free(hashmap *map)
+{
+    free(map->entries);
+    map->entries = NULL;
+    map->capacity = map->count = map->tombstones = 0;
+}
+
+static hashmap_entry *find_slot(hashmap *map, const char *key, size_t length, uint64_t hash)
+{
+    size_t mask = map->capacity - 1;
+    size_t index = (size_t)hash & mask;
+    hashmap_entry *tombstone = NULL;
+    for (;;) {
+        hashmap_entry *entry = &map->entries[index];
+        if (entry->key == NULL) {
+            return tombstone ? tombstone : entry;
+        } else if (entry->key == HASHMAP_TOMBSTONE) {
+            if (!tombstone) {
+                tombstone = entry;
+            }
+        } else if (entry_matches(entry, key, length, hash)) {
+            return entry;
+        }
+        index = (index + 1) & mask;
+    }
+}
+
+static int grow(hashmap *map)
+{
+    hashmap bigger;
+    if (hashmap_init(&bigger, map->capacity * 2) != 0) {
+        return -1;
+    }
+    for (size_t i = 0; i < map->capacity; i++) {
+        hashmap_entry *old = &map->entries[i];
+        if (old->key == NULL || old->key == HASHMAP_TOMBSTONE) {
+            continue;
+        }
+        hashmap_entry *slot = find_slot(&bigger, old->key, old->key_length, old->hash);
+        *slot = *old;
+        bigger.count++;
+    }
+    free(map->entries);
+    *map = bigger;
+    return 0;
+}
+
+int hashmap_put(hashmap *map, const char *key, long value)
+{
+    if ((double)(map->count + map->tombstones + 1) > map->capacity * HASHMAP_MAX_LOAD) {
+        if (grow(map) != 0) {
+            LOG_ERROR("out of memory growing a map of %zu entries", map->count);
+            return -1;
+        }
+    }
+    size_t length = strlen(key);
+    uint64_t hash = hash_bytes(key, length);
+    hashmap_entry *entry = find_slot(map, key, length, hash);
+    if (entry->key == NULL || entry->key == HASHMAP_TOMBSTONE) {
+        if (entry->key == HASHMAP_TOMBSTONE) {
+            map->tombstones--;
+        }
+        entry->key = key;
+        entry->key_length = length;
+        entry->hash = hash;
+        map->count++;
+    }
+    entry->value = value;
+    return 0;
+}
+
+int hashmap_get(const hashmap *map, const char *key, long *value)
+{
+    size_t length = strlen(key);
+    uint64_t hash = hash_bytes(key, length);
+    hashmap_entry *entry = find_slot((hashmap *)map, key, length, hash);
+    if (entry->key == NULL || entry->key == HASHMAP_TOMBSTONE) {
+        return 0;
+    }
+    *value = entry->value;
+    return 1;
+}
+
+int hashmap_remove(hashmap *map, const char *key)
+{
+    size_t length = strlen(key);
+    uint64_t hash = hash_bytes(key, length);
+    hashmap_entry *entry = find_slot(map, key, length, hash);
+    if (entry->key == NULL || entry->key == HASHMAP_TOMBSTONE) {
+        return 0;
+    }
+    entry->key = HASHMAP_TOMBSTONE;
+    map->count--;
+    map->tombstones++;
+    return 1;
+}
+
+void hashmap_dump(const hashmap *map, FILE *out)
+{
+    fprintf(out, "hashmap of %zu/%zu entries:\n", map->count, map->capacity);
+    for (size_t i = 0; i < map->capacity; i++) {
+        const hashmap_entry *entry = &map->entries[i];
+        switch (entry->key == NULL ? 0 : entry->key == HASHMAP_TOMBSTONE ? 1 : 2) {
+        case 0:
+            break;
+        case 1:
+            fputs("  <removed>\n", out);
+            break;
+        default:
+            fprintf(out, "  \"%.*s\" => %ld\t(hash %#llx)\n", (int)entry->key_length,
+                    entry->key, entry->value, (unsigned long long)entry->hash);
+            break;
+        }
+    }
+    fputc('\n', out);
+}
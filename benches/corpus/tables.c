@@ -0,0 +1,94 @@
+/* Lookup tables and numeric code, which are heavy on constants. */
+#include <math.h>
+#include <stdint.h>
+
+#include "config.h"
+
+static const uint32_t crc32_table[16] = {
+    0x00000000u, 0x1db71064u, 0x3b6e20c8u, 0x26d930acu, 0x76dc4190u, 0x6b6b51f4u,
+    0x4db26158u, 0x5005713cu, 0xedb88320u, 0xf00f9344u, 0xd6d6a3e8u, 0xcb61b38cu,
+    0x9b64c2b0u, 0x86d3d2d4u, 0xa00ae278u, 0xbdbdf21cu,
+};
+
+static const double coefficients[] = {
+    1.0,          -0.5,         0.041666667,  -0.0013888889, 2.4801587e-5,
+    -2.7557319e-7, 2.0876757e-9, -1.1470746e-11, 4.7794773e-14, -1.5619207e-16,
+};
+
+static const float weights[8] = {
+    0.125f, 0.25f, .5f, 1.f, 2.0F, 4e0f, 8E+0f, 0x1p4f,
+};
+
+static const long long limits[] = {
+    9223372036854775807LL, -9223372036854775807LL - 1, 2147483647L, 65535, 0777, 0b1010,
+};
+
+static const char *const month_names[12] = {
+    "January", "February", "March",     "April",   "May",      "June",
+    "July",    "August",   "September", "October", "November", "December",
+};
+
+static const char escapes[] = {'\n', '\t', '\\', '\'', '"', '\0', '\x7f', '\177', 'a', 'Z'};
+
+uint32_t crc32_update(uint32_t crc, const unsigned char *data, size_t length)
+{
+    crc = ~crc;
+    while (length--) {
+        crc ^= *data++;
+        crc = (crc >> 4) ^ crc32_table[crc & 0x0f];
+        crc = (crc >> 4) ^ crc32_table[crc & 0x0f];
+    }
+    return ~crc;
+}
+
+double cosine(double x)
+{
+    double x2 = x * x, term = 1.0, sum = 0.0;
+    for (unsigned i = 0; i < ARRAY_LENGTH(coefficients); ++i) {
+        sum += coefficients[i] * term;
+        term *= x2;
+    }
+    return sum;
+}
+
+int days_in_month(int month, int year)
+{
+    static const unsigned char days[] = {31, 28, 31, 30, 31, 30, 31, 31, 30, 31, 30, 31};
+    int leap = (year % 4 == 0 && year % 100 != 0) || year % 400 == 0;
+    return days[month - 1] + (month == 2 && leap);
+}
+
+void mix(uint64_t state[4])
+{
+    uint64_t t = state[1] << 17;
+    state[2] ^= state[0];
+    state[3] ^= state[1];
+    state[1] ^= state[2];
+    state[0] ^= state[3];
+    state[2] ^= t;
+    state[3] = (state[3] << 45) | (state[3] >> 19);
+}
+
+float weighted_average(const float *values, unsigned count)
+{
+    float total = 0.0f, weight_total = 0.0f;
+    for (unsigned i = 0; i < count && i < 8; i++) {
+        total += values[i] * weights[i];
+        weight_total += weights[i];
+    }
+    return weight_total != 0.0f ? total / weight_total : NAN;
+}
+
+const char *month_name(int month)
+{
+    return month >= 1 && month <= 12 ? month_names[month - 1] : "?";
+}
+
+int count_escapes(void)
+{
+    int count = 0;
+    for (unsigned i = 0; i < sizeof escapes; i++) {
+        count += escapes[i] < ' ' || escapes[i] == '\\';
+    }
+    return count;
+}
@@ -0,0 +1,100 @@
+//! Measures how fast the lexer gets through the corpus in `benches/corpus`,
+//! each file repeated to a few megabytes so that timing noise does not
+//! matter. Files given on the command line are measured too:
+//!
+//! ```text
+//! cargo bench --bench lexer -- path/to/file.c ...
+//! ```
+//!
+//! Each input is lexed twice: by the current lexer, and by the tokenising
+//! loop the original `main()` ran, copied into `baseline.rs`. The last
+//! column is how many times faster the current lexer is. Token counts differ
+//! between the two, since the old loop knew far fewer kinds of token.
+
+use std::{
+    env, fs,
+    hint::black_box,
+    time::{Duration, Instant},
+};
+
+use cust::{tokens::TokenType, Lexer};
+
+mod baseline;
+
+const CORPUS: &[(&str, &str)] = &[
+    ("hashmap.c", include_str!("corpus/hashmap.c")),
+    ("config.h", include_str!("corpus/config.h")),
+    ("tables.c", include_str!("corpus/tables.c")),
+];
+
+/// How much source each measurement lexes.
+const TARGET_SIZE: usize = 4 << 20;
+const RUNS: usize = 5;
+
+/// Lexes `source` and counts the tokens, including those inside directives.
+fn lex(source: &str) -> usize {
+    Lexer::new(source)
+        .filter_map(Result::ok)
+        .map(|token| match &token.token_type {
+            TokenType::Directive(_, tokens) => 1 + tokens.len(),
+            _ => 1,
+        })
+        .sum()
+}
+
+/// The fastest of several runs of `lex`, and the number of tokens found.
+fn measure(source: &str, lex: impl Fn(&str) -> usize) -> (Duration, usize) {
+    let mut fastest = Duration::MAX;
+    let mut tokens = 0;
+    for _ in 0..RUNS {
+        let start = Instant::now();
+        tokens = black_box(lex(black_box(source)));
+        fastest = fastest.min(start.elapsed());
+    }
+    (fastest, tokens)
+}
+
+fn report(name: &str, source: &str) {
+    let mebibytes = source.len() as f64 / (1 << 20) as f64;
+    let (time, tokens) = measure(source, lex);
+    let (baseline_time, baseline_tokens) = measure(source, |source| baseline::lex(source).0);
+    let row = |lexer: &str, time: Duration, tokens: usize| {
+        let seconds = time.as_secs_f64();
+        format!(
+            "{:<12} {:<8} {:>8.2} MiB {:>9.1} ms {:>8.1} MiB/s {:>7.2} M tokens/s",
+            name,
+            lexer,
+            mebibytes,
+            seconds * 1000.0,
+            mebibytes / seconds,
+            tokens as f64 / 1e6 / seconds,
+        )
+    };
+    println!("{}", row("baseline", baseline_time, baseline_tokens));
+    println!(
+        "{} {:>6.1}x",
+        row("current", time, tokens),
+        baseline_time.as_secs_f64() / time.as_secs_f64()
+    );
+}
+
+fn main() {
+    let mut all = String::new();
+    for (name, text) in CORPUS {
+        let source = text.repeat(TARGET_SIZE.div_ceil(text.len()));
+        report(name, &source);
+        all.push_str(text);
+    }
+    report("all", &all.repeat(TARGET_SIZE.div_ceil(all.len())));
+
+    // `cargo bench` passes `--bench` to every benchmark.
+    for path in env::args()
+        .skip(1)
+        .filter(|argument| !argument.starts_with("--"))
+    {
+        match fs::read_to_string(&path) {
+            Ok(source) => report(&path, &source),
+            Err(error) => eprintln!("{}: {}", path, error),
+        }
+    }
+}
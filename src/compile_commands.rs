@@ -0,0 +1,371 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+
+use crate::{
+    error::CompileCommandsError,
+    standard::{Extension, Standard},
+    Preprocessor,
+};
+
+/// An entry of `compile_commands.json` as CMake and other tools write it.
+/// Either `arguments` or `command` gives the compiler's command line.
+#[derive(Deserialize)]
+struct Entry {
+    directory: PathBuf,
+    file: PathBuf,
+    arguments: Option<Vec<String>>,
+    command: Option<String>,
+}
+
+/// How to scan one file: the options of its compiler command that matter to
+/// the lexer and preprocessor.
+#[derive(Clone, Default)]
+pub struct CompileCommand {
+    pub file: PathBuf,
+    /// The `-iquote` directories, which only `#include "..."` searches.
+    pub quote_include_paths: Vec<PathBuf>,
+    pub include_paths: Vec<PathBuf>,
+    pub system_include_paths: Vec<PathBuf>,
+    /// The `-D` and `-U` options in order, with `None` for `-U`.
+    pub definitions: Vec<(String, Option<String>)>,
+    /// The standard `-std` selects, if it is given.
+    pub standard: Option<Standard>,
+    pub extensions: Vec<Extension>,
+}
+
+impl CompileCommand {
+    pub fn new(file: impl Into<PathBuf>) -> Self {
+        CompileCommand {
+            file: file.into(),
+            ..CompileCommand::default()
+        }
+    }
+
+    /// Picks out the options cust understands from a compiler's command
+    /// line, run in `directory`, and ignores the rest. Both GCC-style
+    /// options and MSVC's `cl` ones are understood. Like GCC and Clang, a
+    /// GCC-style command has the GNU extensions unless `-std` names an ISO
+    /// standard.
+    pub fn from_arguments(directory: &Path, file: &Path, arguments: &[String]) -> Self {
+        let mut command = CompileCommand::new(directory.join(file));
+        let msvc = arguments.first().is_some_and(|compiler| {
+            // The database may come from Windows, whatever system reads it.
+            let name = compiler.rsplit(['/', '\\']).next().unwrap_or_default();
+            let name = Path::new(name).file_stem().unwrap_or_default();
+            name.eq_ignore_ascii_case("cl") || name.eq_ignore_ascii_case("clang-cl")
+        });
+        if msvc {
+            command.extensions.push(Extension::Msvc);
+        }
+        let mut gnu = !msvc;
+
+        let mut arguments = arguments.iter().skip(1);
+        while let Some(argument) = arguments.next() {
+            let argument = match argument.strip_prefix('/') {
+                // `/std:` is the only option here with a colon before its
+                // value; the operands of the others may hold colons.
+                Some(option) if msvc => match option.strip_prefix("std:") {
+                    Some(name) => format!("-std={}", name),
+                    None => format!("-{}", option),
+                },
+                _ => argument.clone(),
+            };
+            // The option and its operand, which may be the next argument.
+            let (option, operand) = match ["-isystem", "-idirafter", "-iquote", "-I", "-D", "-U"]
+                .iter()
+                .find(|option| argument.starts_with(**option))
+            {
+                Some(option) if argument.len() == option.len() => match arguments.next() {
+                    Some(operand) => (*option, operand.clone()),
+                    None => break,
+                },
+                Some(option) => (*option, argument[option.len()..].to_string()),
+                None => ("", argument),
+            };
+            match option {
+                "-I" => command.include_paths.push(directory.join(operand)),
+                "-iquote" => command.quote_include_paths.push(directory.join(operand)),
+                "-isystem" | "-idirafter" => {
+                    command.system_include_paths.push(directory.join(operand))
+                }
+                "-D" | "-U" => command.definitions.push(definition(option, &operand)),
+                _ => {
+                    let name = match operand.strip_prefix("-std=") {
+                        Some(name) => name,
+                        None if operand == "-ansi" => "c89",
+                        None => continue,
+                    };
+                    if let Some(standard) = Standard::from_name(name) {
+                        command.standard = Some(standard);
+                        gnu = !msvc && name.starts_with("gnu");
+                    }
+                }
+            }
+        }
+        if gnu {
+            command.extensions.push(Extension::Gnu);
+        }
+        command
+    }
+
+    /// Adds the options of `other` after these, as if they came later on
+    /// the command line. Its standard replaces this one if it has one.
+    pub fn extend(&mut self, other: &CompileCommand) {
+        self.quote_include_paths
+            .extend(other.quote_include_paths.iter().cloned());
+        self.include_paths
+            .extend(other.include_paths.iter().cloned());
+        self.system_include_paths
+            .extend(other.system_include_paths.iter().cloned());
+        self.definitions.extend(other.definitions.iter().cloned());
+        self.standard = other.standard.or(self.standard);
+        for extension in &other.extensions {
+            if !self.extensions.contains(extension) {
+                self.extensions.push(*extension);
+            }
+        }
+    }
+
+    /// Sets up `preprocessor` to read the file as the compiler would.
+    pub fn configure(&self, preprocessor: Preprocessor) -> Preprocessor {
+        let mut preprocessor = preprocessor.with_standard(self.standard.unwrap_or_default());
+        for extension in &self.extensions {
+            preprocessor = preprocessor.with_extension(*extension);
+        }
+        for path in &self.quote_include_paths {
+            preprocessor = preprocessor.with_quote_include_path(path);
+        }
+        for path in &self.include_paths {
+            preprocessor = preprocessor.with_include_path(path);
+        }
+        for path in &self.system_include_paths {
+            preprocessor = preprocessor.with_system_include_path(path);
+        }
+        for (name, value) in &self.definitions {
+            preprocessor = match value {
+                Some(value) => preprocessor.with_definition(name, value),
+                None => preprocessor.with_undefinition(name),
+            };
+        }
+        preprocessor
+    }
+}
+
+/// Splits the operand of `-D` or `-U` into a name and, for `-D`, a value,
+/// which is `1` if none is given.
+pub fn definition(option: &str, operand: &str) -> (String, Option<String>) {
+    if option == "-U" {
+        return (operand.to_string(), None);
+    }
+    match operand.split_once('=') {
+        Some((name, value)) => (name.to_string(), Some(value.to_string())),
+        None => (operand.to_string(), Some("1".to_string())),
+    }
+}
+
+/// Reads a compilation database, in the order of its entries.
+pub fn read_compile_commands(path: &Path) -> Result<Vec<CompileCommand>, CompileCommandsError> {
+    let text = fs::read_to_string(path)?;
+    let entries: Vec<Entry> = serde_json::from_str(&text)?;
+    entries
+        .into_iter()
+        .map(|entry| {
+            let arguments = match (entry.arguments, entry.command) {
+                (Some(arguments), _) => arguments,
+                (None, Some(command)) => split_command(&command),
+                (None, None) => {
+                    return Err(CompileCommandsError::MissingCommand { file: entry.file });
+                }
+            };
+            Ok(CompileCommand::from_arguments(
+                &entry.directory,
+                &entry.file,
+                &arguments,
+            ))
+        })
+        .collect()
+}
+
+/// Splits a command into arguments as a POSIX shell would, which is how
+/// the `command` field is meant to be read.
+fn split_command(command: &str) -> Vec<String> {
+    let mut arguments = vec![];
+    let mut argument: Option<String> = None;
+    let mut characters = command.chars();
+    while let Some(character) = characters.next() {
+        match character {
+            _ if character.is_whitespace() => arguments.extend(argument.take()),
+            '\\' => {
+                let argument = argument.get_or_insert_with(String::new);
+                argument.extend(characters.next());
+            }
+            '\'' => {
+                let argument = argument.get_or_insert_with(String::new);
+                argument.extend(
+                    characters
+                        .by_ref()
+                        .take_while(|&character| character != '\''),
+                );
+            }
+            '"' => {
+                let argument = argument.get_or_insert_with(String::new);
+                while let Some(character) = characters.next() {
+                    match character {
+                        '"' => break,
+                        '\\' => match characters.next() {
+                            Some(escaped @ ('"' | '\\' | '$' | '`')) => argument.push(escaped),
+                            Some(other) => {
+                                argument.push('\\');
+                                argument.push(other);
+                            }
+                            None => argument.push('\\'),
+                        },
+                        _ => argument.push(character),
+                    }
+                }
+            }
+            _ => argument.get_or_insert_with(String::new).push(character),
+        }
+    }
+    arguments.extend(argument);
+    arguments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command(arguments: &[&str]) -> CompileCommand {
+        let arguments: Vec<String> = arguments.iter().map(ToString::to_string).collect();
+        CompileCommand::from_arguments(Path::new("/build"), Path::new("main.c"), &arguments)
+    }
+
+    #[test]
+    fn commands_split_like_a_posix_shell() {
+        for (command, expected) in [
+            ("cc  -c\tmain.c ", &["cc", "-c", "main.c"][..]),
+            (r#"cc "-DNAME=a b" main.c"#, &["cc", "-DNAME=a b", "main.c"]),
+            (r#"cc '-DS="x y"'"#, &["cc", r#"-DS="x y""#]),
+            (r#"cc -DS=\"x\ y\""#, &["cc", r#"-DS="x y""#]),
+            // Inside double quotes only some characters can be escaped.
+            (r#"cc "a\"b\\c\d\$""#, &["cc", r#"a"b\c\d$"#]),
+            (r#"cc '\n'"#, &["cc", r"\n"]),
+            (r#"cc "" '' x"#, &["cc", "", "", "x"]),
+            (r#"cc -I"my dir"/include"#, &["cc", "-Imy dir/include"]),
+        ] {
+            assert_eq!(split_command(command), expected, "{}", command);
+        }
+    }
+
+    #[test]
+    fn gcc_style_options_are_understood() {
+        let command = command(&[
+            "/usr/bin/gcc",
+            "-I",
+            "include",
+            "-Isrc",
+            "-isystem",
+            "/opt/include",
+            "-idirafter/late",
+            "-iquote",
+            "quoted",
+            "-DA",
+            "-D",
+            "B=2",
+            "-DC=x=y",
+            "-UA",
+            "-O2",
+            "-std=c11",
+            "-c",
+            "main.c",
+        ]);
+        assert_eq!(command.file, Path::new("/build/main.c"));
+        assert_eq!(
+            command.include_paths,
+            [Path::new("/build/include"), Path::new("/build/src")]
+        );
+        assert_eq!(
+            command.system_include_paths,
+            [Path::new("/opt/include"), Path::new("/late")]
+        );
+        assert_eq!(command.quote_include_paths, [Path::new("/build/quoted")]);
+        assert_eq!(
+            command.definitions,
+            [
+                ("A".to_string(), Some("1".to_string())),
+                ("B".to_string(), Some("2".to_string())),
+                ("C".to_string(), Some("x=y".to_string())),
+                ("A".to_string(), None),
+            ]
+        );
+        assert_eq!(command.standard, Some(Standard::C11));
+        assert!(command.extensions.is_empty());
+    }
+
+    #[test]
+    fn gcc_style_drivers_default_to_gnu_extensions() {
+        for (arguments, standard, extensions) in [
+            (&["cc", "-c", "main.c"][..], None, &[Extension::Gnu][..]),
+            (
+                &["clang", "-std=gnu11"],
+                Some(Standard::C11),
+                &[Extension::Gnu],
+            ),
+            (&["gcc", "-std=c99"], Some(Standard::C99), &[]),
+            (&["gcc", "-ansi"], Some(Standard::C89), &[]),
+            (
+                &["gcc", "-std=c99", "-std=gnu2x"],
+                Some(Standard::C23),
+                &[Extension::Gnu],
+            ),
+            (&["gcc", "-std=c++17"], None, &[Extension::Gnu]),
+            (&["cl.exe", "/c", "main.c"], None, &[Extension::Msvc]),
+        ] {
+            let command = command(arguments);
+            assert_eq!(command.standard, standard, "{:?}", arguments);
+            assert_eq!(command.extensions, extensions, "{:?}", arguments);
+        }
+    }
+
+    #[test]
+    fn msvc_options_are_understood() {
+        let command = command(&[
+            r"C:\VS\bin\cl.exe",
+            "/Iinclude",
+            "/I",
+            "src",
+            r"/IC:\inc",
+            "/DWIN32",
+            "/DURL=a:b",
+            "/D",
+            "N=1",
+            "/UDEBUG",
+            "/std:c17",
+            "/c",
+            "main.c",
+        ]);
+        assert_eq!(
+            command.include_paths,
+            [
+                Path::new("/build/include"),
+                Path::new("/build/src"),
+                &Path::new("/build").join(r"C:\inc"),
+            ]
+        );
+        assert_eq!(
+            command.definitions,
+            [
+                ("WIN32".to_string(), Some("1".to_string())),
+                ("URL".to_string(), Some("a:b".to_string())),
+                ("N".to_string(), Some("1".to_string())),
+                ("DEBUG".to_string(), None),
+            ]
+        );
+        assert_eq!(command.standard, Some(Standard::C17));
+        assert_eq!(command.extensions, [Extension::Msvc]);
+    }
+}
@@ -0,0 +1,449 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Write,
+    path::{Path, PathBuf},
+};
+
+use serde_json::{json, Value};
+
+use crate::{preprocessor::Include, tokens::HeaderName};
+
+/// A file in the graph.
+struct File {
+    path: PathBuf,
+    /// Whether the file was given to walk, rather than included.
+    source: bool,
+    system: bool,
+    /// The files the file includes, in order and without repeats.
+    includes: Vec<usize>,
+    /// The headers the file includes that were not found.
+    missing: Vec<HeaderName<'static>>,
+}
+
+/// The include graph of a set of source files, as found by running the
+/// [`Preprocessor`](crate::Preprocessor) over each of them.
+///
+/// Files are identified by their canonical path, so one header reached
+/// along different paths is one node, named as it was first found.
+#[derive(Default)]
+pub struct DependencyGraph {
+    files: Vec<File>,
+    indices: HashMap<PathBuf, usize>,
+}
+
+impl DependencyGraph {
+    pub fn new() -> Self {
+        DependencyGraph::default()
+    }
+
+    /// Adds `source` and the includes found while preprocessing it.
+    pub fn add_source(&mut self, source: &Path, includes: &[Include]) {
+        let index = self.file(source, false);
+        self.files[index].source = true;
+        for include in includes {
+            let includer = self.file(&include.includer, false);
+            match &include.path {
+                Some(path) => {
+                    let included = self.file(path, include.system);
+                    if !self.files[includer].includes.contains(&included) {
+                        self.files[includer].includes.push(included);
+                    }
+                }
+                None => {
+                    let missing = &mut self.files[includer].missing;
+                    if !missing.contains(&include.header) {
+                        missing.push(include.header.clone());
+                    }
+                }
+            }
+        }
+    }
+
+    fn file(&mut self, path: &Path, system: bool) -> usize {
+        let key = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        if let Some(&index) = self.indices.get(&key) {
+            return index;
+        }
+        self.files.push(File {
+            path: path.to_path_buf(),
+            source: false,
+            system,
+            includes: vec![],
+            missing: vec![],
+        });
+        self.indices.insert(key, self.files.len() - 1);
+        self.files.len() - 1
+    }
+
+    /// The headers that were not found, with the files including them.
+    pub fn missing(&self) -> impl Iterator<Item = (&Path, &HeaderName<'static>)> {
+        self.files.iter().flat_map(|file| {
+            file.missing
+                .iter()
+                .map(|header| (file.path.as_path(), header))
+        })
+    }
+
+    /// The include cycles, each starting and ending with the same file.
+    /// Include guards and `#pragma once` stop a cycle from recursing
+    /// forever, but it still means the headers depend on their order.
+    pub fn cycles(&self) -> Vec<Vec<&Path>> {
+        self.cycle_edges()
+            .into_iter()
+            .map(|cycle| {
+                cycle
+                    .into_iter()
+                    .map(|index| self.files[index].path.as_path())
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Finds a cycle for every edge that leads back to a file still being
+    /// walked in a depth-first search.
+    fn cycle_edges(&self) -> Vec<Vec<usize>> {
+        let mut cycles = vec![];
+        let mut visited = vec![false; self.files.len()];
+        let mut stack: Vec<(usize, usize)> = vec![];
+        for root in 0..self.files.len() {
+            if visited[root] {
+                continue;
+            }
+            visited[root] = true;
+            stack.push((root, 0));
+            while let Some((file, next)) = stack.last_mut() {
+                let Some(&included) = self.files[*file].includes.get(*next) else {
+                    stack.pop();
+                    continue;
+                };
+                *next += 1;
+                if let Some(position) = stack.iter().position(|&(open, _)| open == included) {
+                    let mut cycle: Vec<usize> =
+                        stack[position..].iter().map(|&(open, _)| open).collect();
+                    cycle.push(included);
+                    cycles.push(cycle);
+                } else if !visited[included] {
+                    visited[included] = true;
+                    stack.push((included, 0));
+                }
+            }
+        }
+        cycles
+    }
+
+    /// Every file `index` includes, directly or not, in the order a
+    /// depth-first walk finds them.
+    fn reachable(&self, index: usize) -> Vec<usize> {
+        let mut seen = HashSet::from([index]);
+        let mut order = vec![];
+        let mut stack: Vec<(usize, usize)> = vec![(index, 0)];
+        while let Some((file, next)) = stack.last_mut() {
+            let Some(&included) = self.files[*file].includes.get(*next) else {
+                stack.pop();
+                continue;
+            };
+            *next += 1;
+            if seen.insert(included) {
+                order.push(included);
+                stack.push((included, 0));
+            }
+        }
+        order
+    }
+
+    /// The graph in Graphviz's DOT language. Sources are boxes, system
+    /// headers are grey, missing headers are dashed and the edges of cycles
+    /// are red.
+    pub fn to_dot(&self) -> String {
+        let cycle_edges: HashSet<(usize, usize)> = self
+            .cycle_edges()
+            .iter()
+            .flat_map(|cycle| cycle.windows(2).map(|pair| (pair[0], pair[1])))
+            .collect();
+        let mut dot = String::from("digraph includes {\n");
+        for file in &self.files {
+            let mut attributes = vec![];
+            if file.source {
+                attributes.push("shape=box");
+            }
+            if file.system {
+                attributes.push("color=gray");
+            }
+            let _ = write!(dot, "    {}", dot_string(&file.path.display().to_string()));
+            if !attributes.is_empty() {
+                let _ = write!(dot, " [{}]", attributes.join(", "));
+            }
+            dot.push_str(";\n");
+        }
+        for (index, file) in self.files.iter().enumerate() {
+            let from = dot_string(&file.path.display().to_string());
+            for &included in &file.includes {
+                let to = dot_string(&self.files[included].path.display().to_string());
+                let _ = write!(dot, "    {} -> {}", from, to);
+                if cycle_edges.contains(&(index, included)) {
+                    dot.push_str(" [color=red]");
+                }
+                dot.push_str(";\n");
+            }
+            for header in &file.missing {
+                let to = dot_string(&header.to_string());
+                let _ = writeln!(dot, "    {} [style=dashed];", to);
+                let _ = writeln!(dot, "    {} -> {} [style=dashed];", from, to);
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// The graph as JSON: the files with what they include, the missing
+    /// headers and the cycles.
+    pub fn to_json(&self) -> String {
+        let path = |index: usize| self.files[index].path.display().to_string();
+        let files: Vec<Value> = self
+            .files
+            .iter()
+            .enumerate()
+            .map(|(index, file)| {
+                json!({
+                    "path": path(index),
+                    "source": file.source,
+                    "system": file.system,
+                    "includes": file.includes.iter().map(|&index| path(index)).collect::<Vec<_>>(),
+                    "missing": file.missing.iter().map(ToString::to_string).collect::<Vec<_>>(),
+                })
+            })
+            .collect();
+        let cycles: Vec<Vec<String>> = self
+            .cycle_edges()
+            .into_iter()
+            .map(|cycle| cycle.into_iter().map(path).collect())
+            .collect();
+        let mut json = serde_json::to_string_pretty(&json!({"files": files, "cycles": cycles}))
+            .expect("strings and booleans always serialise");
+        json.push('\n');
+        json
+    }
+
+    /// The graph as `make` rules, like those `cc -MD -MP` writes: one rule
+    /// per source making its object file depend on every file it includes,
+    /// then an empty rule per header so that deleting one does not break
+    /// the build.
+    pub fn to_make(&self) -> String {
+        let mut make = String::new();
+        let mut headers = vec![];
+        for (index, file) in self.files.iter().enumerate() {
+            if !file.source {
+                continue;
+            }
+            let object = file.path.with_extension("o");
+            let object = object.file_name().unwrap_or(object.as_os_str());
+            let _ = write!(
+                make,
+                "{}: {}",
+                make_path(&Path::new(object).display().to_string()),
+                make_path(&file.path.display().to_string())
+            );
+            for included in self.reachable(index) {
+                let _ = write!(
+                    make,
+                    " \\\n  {}",
+                    make_path(&self.files[included].path.display().to_string())
+                );
+                if !self.files[included].source && !headers.contains(&included) {
+                    headers.push(included);
+                }
+            }
+            make.push('\n');
+        }
+        for header in headers {
+            let path = make_path(&self.files[header].path.display().to_string());
+            let _ = write!(make, "\n{}:\n", path);
+        }
+        make
+    }
+}
+
+fn dot_string(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Escapes the characters `make` would read as separators or variables.
+fn make_path(path: &str) -> String {
+    path.replace(' ', "\\ ")
+        .replace('#', "\\#")
+        .replace('$', "$$")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, process};
+
+    use super::*;
+    use crate::{standard::Standard, Preprocessor};
+
+    /// A directory of its own holding `files`, removed when dropped.
+    struct Tree(PathBuf);
+
+    impl Tree {
+        fn new(name: &str, files: &[(&str, &str)]) -> Self {
+            let root = env::temp_dir().join(format!("cust-deps-{}-{}", process::id(), name));
+            for (file, text) in files {
+                let path = root.join(file);
+                fs::create_dir_all(path.parent().unwrap())
+                    .expect("the temporary directory is writable");
+                fs::write(path, text).expect("the temporary directory is writable");
+            }
+            Tree(root)
+        }
+
+        fn path(&self, file: &str) -> PathBuf {
+            self.0.join(file)
+        }
+
+        /// The graph of `source`, preprocessed with `configure`.
+        fn graph(
+            &self,
+            source: &str,
+            configure: impl FnOnce(Preprocessor) -> Preprocessor,
+        ) -> DependencyGraph {
+            let path = self.path(source);
+            let mut preprocessor = configure(Preprocessor::new(&path).with_standard(Standard::C23));
+            preprocessor.by_ref().for_each(drop);
+            let mut graph = DependencyGraph::new();
+            graph.add_source(&path, preprocessor.includes());
+            graph
+        }
+    }
+
+    impl Drop for Tree {
+        fn drop(&mut self) {
+            fs::remove_dir_all(&self.0).ok();
+        }
+    }
+
+    fn json(graph: &DependencyGraph) -> Value {
+        serde_json::from_str(&graph.to_json()).expect("the graph is valid JSON")
+    }
+
+    #[test]
+    fn cycles_are_found_and_marked() {
+        let tree = Tree::new(
+            "cycle",
+            &[
+                ("main.c", "#include \"a.h\"\n"),
+                ("a.h", "#pragma once\n#include \"b.h\"\n"),
+                ("b.h", "#pragma once\n#include \"a.h\"\n"),
+            ],
+        );
+        let graph = tree.graph("main.c", |preprocessor| preprocessor);
+        let (a, b) = (tree.path("a.h"), tree.path("b.h"));
+        assert_eq!(
+            graph.cycles(),
+            [vec![a.as_path(), b.as_path(), a.as_path()]]
+        );
+        let red = format!("\"{}\" -> \"{}\" [color=red];", b.display(), a.display());
+        assert!(graph.to_dot().contains(&red), "{}", graph.to_dot());
+        let cycle = a.display().to_string();
+        assert_eq!(json(&graph)["cycles"][0][0], cycle);
+        assert_eq!(json(&graph)["cycles"][0][2], cycle);
+    }
+
+    #[test]
+    fn missing_headers_are_reported_in_every_format() {
+        let tree = Tree::new(
+            "missing",
+            &[("main.c", "#include \"gone.h\"\n#include <we\"ird.h>\n")],
+        );
+        let graph = tree.graph("main.c", |preprocessor| preprocessor);
+        let main = tree.path("main.c");
+        let missing: Vec<_> = graph
+            .missing()
+            .map(|(includer, header)| (includer.to_path_buf(), header.to_string()))
+            .collect();
+        assert_eq!(
+            missing,
+            [
+                (main.clone(), "\"gone.h\"".to_string()),
+                (main.clone(), "<we\"ird.h>".to_string()),
+            ]
+        );
+        assert!(graph
+            .to_dot()
+            .contains("\"\\\"gone.h\\\"\" [style=dashed];"));
+        let json = json(&graph);
+        assert_eq!(json["files"][0]["missing"][1], "<we\"ird.h>");
+        assert_eq!(json["cycles"], Value::Array(vec![]));
+    }
+
+    #[test]
+    fn quoted_and_angled_includes_search_in_order() {
+        let tree = Tree::new(
+            "order",
+            &[
+                (
+                    "src/main.c",
+                    "#include \"h.h\"\n#include <h.h>\n#include <s.h>\n",
+                ),
+                ("src/h.h", ""),
+                ("include/h.h", ""),
+                ("system/h.h", ""),
+                ("system/s.h", ""),
+            ],
+        );
+        let graph = tree.graph("src/main.c", |preprocessor| {
+            preprocessor
+                .with_include_path(tree.path("include"))
+                .with_system_include_path(tree.path("system"))
+        });
+        let json = json(&graph);
+        let includes: Vec<PathBuf> = json["files"][0]["includes"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|path| PathBuf::from(path.as_str().unwrap()))
+            .collect();
+        // `"h.h"` is found next to the source, `<h.h>` along `-I` before
+        // `-isystem`, and only `<s.h>` is a system header.
+        assert_eq!(
+            includes,
+            [
+                tree.path("src/h.h"),
+                tree.path("include/h.h"),
+                tree.path("system/s.h")
+            ]
+        );
+        let system: Vec<_> = json["files"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|file| file["system"].as_bool().unwrap())
+            .collect();
+        assert_eq!(system, [false, false, false, true]);
+    }
+
+    #[test]
+    fn make_rules_list_every_reachable_header() {
+        let tree = Tree::new(
+            "make",
+            &[
+                ("main.c", "#include \"a.h\"\n"),
+                ("a.h", "#include \"my header.h\"\n"),
+                ("my header.h", ""),
+            ],
+        );
+        let graph = tree.graph("main.c", |preprocessor| preprocessor);
+        let path = |file: &str| make_path(&tree.path(file).display().to_string());
+        assert_eq!(
+            graph.to_make(),
+            format!(
+                "main.o: {} \\\n  {} \\\n  {}\n\n{}:\n\n{}:\n",
+                path("main.c"),
+                path("a.h"),
+                path("my header.h"),
+                path("a.h"),
+                path("my header.h"),
+            )
+        );
+        assert!(path("my header.h").ends_with("my\\ header.h"));
+    }
+}
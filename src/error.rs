@@ -0,0 +1,615 @@
+use std::{
+    fmt::Display,
+    io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    literal::{Encoding, LiteralError},
+    number::NumberError,
+    standard::Standard,
+    tokens::{Directive, HeaderName, Location},
+};
+
+#[derive(Debug)]
+pub enum LexError {
+    UnknownCharacter {
+        character: char,
+        location: Location,
+    },
+    UnterminatedString {
+        location: Location,
+    },
+    /// A `\u` or `\U` escape in an identifier that is cut short or names a
+    /// character that may not be written that way.
+    InvalidUniversalCharacterName {
+        name: String,
+        location: Location,
+    },
+    /// A character that C23 does not allow in identifiers, or not at their
+    /// start if `first` is set.
+    DisallowedIdentifierCharacter {
+        character: char,
+        first: bool,
+        location: Location,
+    },
+    MalformedStringLiteral {
+        reason: LiteralError,
+        location: Location,
+    },
+    IncompatibleStringPrefixes {
+        first: Encoding,
+        second: Encoding,
+        location: Location,
+    },
+    UnterminatedBlockComment {
+        location: Location,
+    },
+    MalformedCharacterLiteral {
+        reason: LiteralError,
+        location: Location,
+    },
+    InvalidNumber {
+        number: String,
+        reason: NumberError,
+        location: Location,
+    },
+    /// A warning rather than an error: the token after it relies on a
+    /// feature of a newer standard than the selected one.
+    NewerFeature {
+        feature: &'static str,
+        introduced: Standard,
+        selected: Standard,
+        location: Location,
+    },
+    Io(io::Error),
+    InvalidEncoding {
+        location: Location,
+    },
+}
+
+impl LexError {
+    /// Where the error was found, if it relates to a place in the source.
+    pub fn location(&self) -> Option<Location> {
+        match self {
+            LexError::UnknownCharacter { location, .. }
+            | LexError::UnterminatedString { location }
+            | LexError::InvalidUniversalCharacterName { location, .. }
+            | LexError::DisallowedIdentifierCharacter { location, .. }
+            | LexError::MalformedStringLiteral { location, .. }
+            | LexError::IncompatibleStringPrefixes { location, .. }
+            | LexError::UnterminatedBlockComment { location }
+            | LexError::MalformedCharacterLiteral { location, .. }
+            | LexError::InvalidNumber { location, .. }
+            | LexError::NewerFeature { location, .. }
+            | LexError::InvalidEncoding { location } => Some(*location),
+            LexError::Io(_) => None,
+        }
+    }
+
+    /// The location, for moving it when text was lexed apart from what
+    /// comes before it.
+    pub(crate) fn location_mut(&mut self) -> Option<&mut Location> {
+        match self {
+            LexError::UnknownCharacter { location, .. }
+            | LexError::UnterminatedString { location }
+            | LexError::InvalidUniversalCharacterName { location, .. }
+            | LexError::DisallowedIdentifierCharacter { location, .. }
+            | LexError::MalformedStringLiteral { location, .. }
+            | LexError::IncompatibleStringPrefixes { location, .. }
+            | LexError::UnterminatedBlockComment { location }
+            | LexError::MalformedCharacterLiteral { location, .. }
+            | LexError::InvalidNumber { location, .. }
+            | LexError::NewerFeature { location, .. }
+            | LexError::InvalidEncoding { location } => Some(location),
+            LexError::Io(_) => None,
+        }
+    }
+
+    /// Whether this only warns about the input, which still lexed fine.
+    pub fn is_warning(&self) -> bool {
+        matches!(self, LexError::NewerFeature { .. })
+    }
+}
+
+impl Display for LexError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LexError::UnknownCharacter {
+                character,
+                location,
+            } => write!(f, "Unknown character {:?} {}", character, location),
+            LexError::UnterminatedString { location } => {
+                write!(f, "Unterminated string literal starting {}", location)
+            }
+            LexError::InvalidUniversalCharacterName { name, location } => write!(
+                f,
+                "{} is not a valid universal character name {}",
+                name, location
+            ),
+            LexError::DisallowedIdentifierCharacter {
+                character,
+                first,
+                location,
+            } => write!(
+                f,
+                "{:?} (U+{:04X}) cannot {} an identifier {}",
+                character,
+                *character as u32,
+                if *first { "start" } else { "appear in" },
+                location
+            ),
+            LexError::MalformedStringLiteral { reason, location } => {
+                write!(f, "Malformed string literal ({}) {}", reason, location)
+            }
+            LexError::IncompatibleStringPrefixes {
+                first,
+                second,
+                location,
+            } => write!(
+                f,
+                "Cannot concatenate {}\"...\" and {}\"...\" string literals {}",
+                first, second, location
+            ),
+            LexError::UnterminatedBlockComment { location } => {
+                write!(f, "Unterminated block comment starting {}", location)
+            }
+            LexError::MalformedCharacterLiteral { reason, location } => {
+                write!(f, "Malformed character literal ({}) {}", reason, location)
+            }
+            LexError::InvalidNumber {
+                number,
+                reason,
+                location,
+            } => write!(f, "Invalid number \"{}\" ({}) {}", number, reason, location),
+            LexError::NewerFeature {
+                feature,
+                introduced,
+                selected,
+                location,
+            } => write!(
+                f,
+                "{} is a {} feature but the selected standard is {} {}",
+                feature, introduced, selected, location
+            ),
+            LexError::Io(error) => write!(f, "Could not read the file: {}", error),
+            LexError::InvalidEncoding { location } => {
+                write!(f, "The file is not valid UTF-8 {}", location)
+            }
+        }
+    }
+}
+
+impl std::error::Error for LexError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LexError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for LexError {
+    fn from(error: io::Error) -> Self {
+        LexError::Io(error)
+    }
+}
+
+/// A problem with a preprocessor constant expression.
+#[derive(Debug)]
+pub enum ExpressionError {
+    MissingExpression,
+    UnexpectedToken {
+        token: String,
+        location: Location,
+    },
+    InvalidNumber {
+        number: String,
+        reason: NumberError,
+        location: Location,
+    },
+    FloatingConstant {
+        number: String,
+        location: Location,
+    },
+    DivisionByZero {
+        location: Location,
+    },
+    Overflow {
+        location: Location,
+    },
+    InvalidShift {
+        location: Location,
+    },
+    InvalidDefined {
+        location: Location,
+    },
+    InvalidHasInclude {
+        location: Location,
+    },
+}
+
+impl ExpressionError {
+    pub fn location(&self) -> Option<Location> {
+        match self {
+            ExpressionError::MissingExpression => None,
+            ExpressionError::UnexpectedToken { location, .. }
+            | ExpressionError::InvalidNumber { location, .. }
+            | ExpressionError::FloatingConstant { location, .. }
+            | ExpressionError::DivisionByZero { location }
+            | ExpressionError::Overflow { location }
+            | ExpressionError::InvalidShift { location }
+            | ExpressionError::InvalidDefined { location }
+            | ExpressionError::InvalidHasInclude { location } => Some(*location),
+        }
+    }
+}
+
+impl Display for ExpressionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExpressionError::MissingExpression => write!(f, "Missing expression"),
+            ExpressionError::UnexpectedToken { token, location } => {
+                write!(f, "Unexpected \"{}\" in expression {}", token, location)
+            }
+            ExpressionError::InvalidNumber {
+                number,
+                reason,
+                location,
+            } => write!(f, "Invalid number \"{}\" ({}) {}", number, reason, location),
+            ExpressionError::FloatingConstant { number, location } => write!(
+                f,
+                "Floating constant \"{}\" in integer expression {}",
+                number, location
+            ),
+            ExpressionError::DivisionByZero { location } => {
+                write!(f, "Division by zero {}", location)
+            }
+            ExpressionError::Overflow { location } => {
+                write!(f, "Integer overflow {}", location)
+            }
+            ExpressionError::InvalidShift { location } => {
+                write!(f, "Shift count out of range {}", location)
+            }
+            ExpressionError::InvalidDefined { location } => {
+                write!(f, "defined expects a macro name {}", location)
+            }
+            ExpressionError::InvalidHasInclude { location } => {
+                write!(f, "__has_include expects <file> or \"file\" {}", location)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ExpressionError {}
+
+/// A problem found while preprocessing. Every variant records the file it
+/// was found in, since it may be a header rather than the file asked for.
+#[derive(Debug)]
+pub enum PreprocessError {
+    Lex {
+        file: PathBuf,
+        error: LexError,
+    },
+    IncludeNotFound {
+        header: HeaderName<'static>,
+        file: PathBuf,
+        location: Location,
+    },
+    InvalidInclude {
+        directive: Directive,
+        file: PathBuf,
+        location: Location,
+    },
+    IncludeTooDeep {
+        file: PathBuf,
+        location: Location,
+    },
+    UnsupportedEmbedParameters {
+        file: PathBuf,
+        location: Location,
+    },
+    MissingMacroName {
+        directive: Directive,
+        file: PathBuf,
+        location: Location,
+    },
+    InvalidMacroParameters {
+        name: String,
+        file: PathBuf,
+        location: Location,
+    },
+    /// A warning: the macro was defined before with a different body.
+    MacroRedefined {
+        name: String,
+        file: PathBuf,
+        location: Location,
+    },
+    InvalidStringification {
+        name: String,
+        file: PathBuf,
+        location: Location,
+    },
+    PasteAtEdge {
+        name: String,
+        file: PathBuf,
+        location: Location,
+    },
+    InvalidPaste {
+        left: String,
+        right: String,
+        file: PathBuf,
+        location: Location,
+    },
+    UnterminatedArguments {
+        name: String,
+        file: PathBuf,
+        location: Location,
+    },
+    WrongArgumentCount {
+        name: String,
+        expected: usize,
+        found: usize,
+        file: PathBuf,
+        location: Location,
+    },
+    UnbalancedConditional {
+        directive: Directive,
+        file: PathBuf,
+        location: Location,
+    },
+    UnterminatedConditional {
+        file: PathBuf,
+        location: Location,
+    },
+    Expression {
+        error: Box<ExpressionError>,
+        file: PathBuf,
+        location: Location,
+    },
+    InvalidLine {
+        file: PathBuf,
+        location: Location,
+    },
+    UnknownDirective {
+        name: String,
+        file: PathBuf,
+        location: Location,
+    },
+    ErrorDirective {
+        message: String,
+        file: PathBuf,
+        location: Location,
+    },
+    /// A warning asked for with `#warning`.
+    WarningDirective {
+        message: String,
+        file: PathBuf,
+        location: Location,
+    },
+}
+
+impl PreprocessError {
+    /// The file the problem was found in.
+    pub fn file(&self) -> &Path {
+        match self {
+            PreprocessError::Lex { file, .. }
+            | PreprocessError::IncludeNotFound { file, .. }
+            | PreprocessError::InvalidInclude { file, .. }
+            | PreprocessError::IncludeTooDeep { file, .. }
+            | PreprocessError::UnsupportedEmbedParameters { file, .. }
+            | PreprocessError::MissingMacroName { file, .. }
+            | PreprocessError::InvalidMacroParameters { file, .. }
+            | PreprocessError::MacroRedefined { file, .. }
+            | PreprocessError::InvalidStringification { file, .. }
+            | PreprocessError::PasteAtEdge { file, .. }
+            | PreprocessError::InvalidPaste { file, .. }
+            | PreprocessError::UnterminatedArguments { file, .. }
+            | PreprocessError::WrongArgumentCount { file, .. }
+            | PreprocessError::UnbalancedConditional { file, .. }
+            | PreprocessError::UnterminatedConditional { file, .. }
+            | PreprocessError::Expression { file, .. }
+            | PreprocessError::InvalidLine { file, .. }
+            | PreprocessError::UnknownDirective { file, .. }
+            | PreprocessError::ErrorDirective { file, .. }
+            | PreprocessError::WarningDirective { file, .. } => file,
+        }
+    }
+
+    /// Where in [`PreprocessError::file`] the problem was found.
+    pub fn location(&self) -> Option<Location> {
+        match self {
+            PreprocessError::Lex { error, .. } => error.location(),
+            PreprocessError::Expression {
+                error, location, ..
+            } => error.location().or(Some(*location)),
+            PreprocessError::IncludeNotFound { location, .. }
+            | PreprocessError::InvalidInclude { location, .. }
+            | PreprocessError::IncludeTooDeep { location, .. }
+            | PreprocessError::UnsupportedEmbedParameters { location, .. }
+            | PreprocessError::MissingMacroName { location, .. }
+            | PreprocessError::InvalidMacroParameters { location, .. }
+            | PreprocessError::MacroRedefined { location, .. }
+            | PreprocessError::InvalidStringification { location, .. }
+            | PreprocessError::PasteAtEdge { location, .. }
+            | PreprocessError::InvalidPaste { location, .. }
+            | PreprocessError::UnterminatedArguments { location, .. }
+            | PreprocessError::WrongArgumentCount { location, .. }
+            | PreprocessError::UnbalancedConditional { location, .. }
+            | PreprocessError::UnterminatedConditional { location, .. }
+            | PreprocessError::InvalidLine { location, .. }
+            | PreprocessError::UnknownDirective { location, .. }
+            | PreprocessError::ErrorDirective { location, .. }
+            | PreprocessError::WarningDirective { location, .. } => Some(*location),
+        }
+    }
+
+    /// Whether this only warns about the input.
+    pub fn is_warning(&self) -> bool {
+        match self {
+            PreprocessError::Lex { error, .. } => error.is_warning(),
+            PreprocessError::MacroRedefined { .. } | PreprocessError::WarningDirective { .. } => {
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Display for PreprocessError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PreprocessError::Lex { error, .. } => write!(f, "{}", error),
+            PreprocessError::IncludeNotFound {
+                header, location, ..
+            } => write!(f, "Cannot find {} {}", header, location),
+            PreprocessError::InvalidInclude {
+                directive,
+                location,
+                ..
+            } => write!(f, "#{} expects <file> or \"file\" {}", directive, location),
+            PreprocessError::IncludeTooDeep { location, .. } => {
+                write!(f, "Includes nest too deeply {}", location)
+            }
+            PreprocessError::UnsupportedEmbedParameters { location, .. } => {
+                write!(f, "#embed parameters are not supported {}", location)
+            }
+            PreprocessError::MissingMacroName {
+                directive,
+                location,
+                ..
+            } => write!(f, "#{} expects a macro name {}", directive, location),
+            PreprocessError::InvalidMacroParameters { name, location, .. } => {
+                write!(
+                    f,
+                    "Invalid parameter list for macro \"{}\" {}",
+                    name, location
+                )
+            }
+            PreprocessError::MacroRedefined { name, location, .. } => {
+                write!(f, "Macro \"{}\" redefined {}", name, location)
+            }
+            PreprocessError::InvalidStringification { name, location, .. } => write!(
+                f,
+                "# is not followed by a parameter in macro \"{}\" {}",
+                name, location
+            ),
+            PreprocessError::PasteAtEdge { name, location, .. } => write!(
+                f,
+                "## cannot start or end the body of macro \"{}\" {}",
+                name, location
+            ),
+            PreprocessError::InvalidPaste {
+                left,
+                right,
+                location,
+                ..
+            } => write!(
+                f,
+                "Pasting \"{}\" and \"{}\" does not give a valid token {}",
+                left, right, location
+            ),
+            PreprocessError::UnterminatedArguments { name, location, .. } => write!(
+                f,
+                "Unterminated argument list for macro \"{}\" {}",
+                name, location
+            ),
+            PreprocessError::WrongArgumentCount {
+                name,
+                expected,
+                found,
+                location,
+                ..
+            } => write!(
+                f,
+                "Macro \"{}\" takes {} arguments but {} were given {}",
+                name, expected, found, location
+            ),
+            PreprocessError::UnbalancedConditional {
+                directive,
+                location,
+                ..
+            } => write!(f, "Unexpected #{} {}", directive, location),
+            PreprocessError::UnterminatedConditional { location, .. } => {
+                write!(f, "Conditional starting {} is never closed", location)
+            }
+            PreprocessError::Expression {
+                error, location, ..
+            } => match error.location() {
+                Some(_) => write!(f, "{}", error),
+                None => write!(f, "{} {}", error, location),
+            },
+            PreprocessError::InvalidLine { location, .. } => {
+                write!(f, "#line expects a line number and file name {}", location)
+            }
+            PreprocessError::UnknownDirective { name, location, .. } => {
+                write!(f, "Unknown directive \"#{}\" {}", name, location)
+            }
+            PreprocessError::ErrorDirective {
+                message, location, ..
+            } => write!(f, "#error {} {}", message, location),
+            PreprocessError::WarningDirective {
+                message, location, ..
+            } => write!(f, "#warning {} {}", message, location),
+        }
+    }
+}
+
+impl std::error::Error for PreprocessError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PreprocessError::Lex { error, .. } => Some(error),
+            PreprocessError::Expression { error, .. } => Some(error.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+/// A problem reading `compile_commands.json`.
+#[derive(Debug)]
+pub enum CompileCommandsError {
+    Io(io::Error),
+    Json(serde_json::Error),
+    /// The entry for `file` has neither `arguments` nor `command`.
+    MissingCommand {
+        file: PathBuf,
+    },
+}
+
+impl Display for CompileCommandsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CompileCommandsError::Io(error) => write!(f, "Could not read the file: {}", error),
+            CompileCommandsError::Json(error) => {
+                write!(f, "Not a compilation database: {}", error)
+            }
+            CompileCommandsError::MissingCommand { file } => write!(
+                f,
+                "The entry for {} has neither \"arguments\" nor \"command\"",
+                file.display()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for CompileCommandsError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CompileCommandsError::Io(error) => Some(error),
+            CompileCommandsError::Json(error) => Some(error),
+            CompileCommandsError::MissingCommand { .. } => None,
+        }
+    }
+}
+
+impl From<io::Error> for CompileCommandsError {
+    fn from(error: io::Error) -> Self {
+        CompileCommandsError::Io(error)
+    }
+}
+
+impl From<serde_json::Error> for CompileCommandsError {
+    fn from(error: serde_json::Error) -> Self {
+        CompileCommandsError::Json(error)
+    }
+}
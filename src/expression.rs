@@ -0,0 +1,627 @@
+use std::{borrow::Cow, fmt::Display};
+
+use crate::{
+    error::ExpressionError,
+    number::{self, DataModel, NumberKind},
+    standard::Extension,
+    symbol::Symbol,
+    tokens::{Delimiter, HeaderName, Keyword, Location, Operator, Token, TokenType},
+};
+
+/// The value of a preprocessor expression, in which every integer has the
+/// type `intmax_t` or `uintmax_t`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Value {
+    Signed(i64),
+    Unsigned(u64),
+}
+
+impl Value {
+    pub fn is_true(&self) -> bool {
+        self.bits() != 0
+    }
+
+    fn from_bool(value: bool) -> Value {
+        Value::Signed(value.into())
+    }
+
+    fn is_unsigned(&self) -> bool {
+        matches!(self, Value::Unsigned(_))
+    }
+
+    /// The value reinterpreted as `uintmax_t`, as the usual arithmetic
+    /// conversions do when the other operand is unsigned.
+    fn bits(&self) -> u64 {
+        match *self {
+            Value::Signed(value) => value as u64,
+            Value::Unsigned(value) => value,
+        }
+    }
+
+    /// Zero of the type the usual arithmetic conversions give `self` and
+    /// `other`, for results whose value does not matter.
+    fn zero_like(&self, other: Value) -> Value {
+        if self.is_unsigned() || other.is_unsigned() {
+            Value::Unsigned(0)
+        } else {
+            Value::Signed(0)
+        }
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Signed(value) => write!(f, "{} (intmax_t)", value),
+            Value::Unsigned(value) => write!(f, "{} (uintmax_t)", value),
+        }
+    }
+}
+
+/// Evaluates the integer constant expression of `#if` or `#elif` once
+/// macros are expanded. Identifiers left over count as zero.
+///
+/// Errors such as division by zero are only reported in operands that are
+/// evaluated, so `0 && 1 / 0` is fine, as in C. With GNU extensions, `a ?: b`
+/// may also be written with the `?` and `:` apart.
+///
+/// Names are told apart by their symbols, so the tokens must come from a
+/// lexer given an [`Interner`](crate::symbol::Interner).
+pub fn evaluate(
+    tokens: &[Token],
+    extensions: &[Extension],
+    is_defined: &dyn Fn(Symbol) -> bool,
+    has_include: &dyn Fn(&HeaderName) -> bool,
+) -> Result<Value, ExpressionError> {
+    let mut evaluator = Evaluator {
+        tokens,
+        index: 0,
+        elvis: extensions.contains(&Extension::Gnu),
+        is_defined,
+        has_include,
+        unevaluated: 0,
+    };
+    let value = evaluator.conditional()?;
+    match evaluator.peek() {
+        None => Ok(value),
+        Some(token) => Err(unexpected(token)),
+    }
+}
+
+struct Evaluator<'a> {
+    tokens: &'a [Token<'a>],
+    index: usize,
+    /// Whether `?` followed by `:` is GNU's `?:`.
+    elvis: bool,
+    is_defined: &'a dyn Fn(Symbol) -> bool,
+    has_include: &'a dyn Fn(&HeaderName) -> bool,
+    /// How many operands around the current one are skipped by `&&`, `||`
+    /// or `?:`.
+    unevaluated: usize,
+}
+
+impl<'a> Evaluator<'a> {
+    fn peek(&self) -> Option<&'a Token<'a>> {
+        self.tokens.get(self.index)
+    }
+
+    fn next_token(&mut self) -> Result<&'a Token<'a>, ExpressionError> {
+        let tokens = self.tokens;
+        let token = tokens
+            .get(self.index)
+            .ok_or(ExpressionError::MissingExpression)?;
+        self.index += 1;
+        Ok(token)
+    }
+
+    fn eat_delimiter(&mut self, expected: fn(&Delimiter) -> bool) -> bool {
+        let found = self.peek().is_some_and(|token| match &token.token_type {
+            TokenType::Delimiter(delimiter) => expected(delimiter),
+            _ => false,
+        });
+        if found {
+            self.index += 1;
+        }
+        found
+    }
+
+    fn expect_delimiter(
+        &mut self,
+        expected: fn(&Delimiter) -> bool,
+    ) -> Result<(), ExpressionError> {
+        if self.eat_delimiter(expected) {
+            return Ok(());
+        }
+        match self.peek() {
+            Some(token) => Err(unexpected(token)),
+            None => Err(ExpressionError::MissingExpression),
+        }
+    }
+
+    /// Reports `error`, unless it happened in an operand that is skipped;
+    /// then the result is just `zero`.
+    fn fail(&self, error: ExpressionError, zero: Value) -> Result<Value, ExpressionError> {
+        if self.unevaluated > 0 {
+            Ok(zero)
+        } else {
+            Err(error)
+        }
+    }
+
+    /// Evaluates an operand, treating it as skipped when `skipped` is set.
+    fn operand(
+        &mut self,
+        skipped: bool,
+        parse: impl FnOnce(&mut Self) -> Result<Value, ExpressionError>,
+    ) -> Result<Value, ExpressionError> {
+        self.unevaluated += usize::from(skipped);
+        let value = parse(self);
+        self.unevaluated -= usize::from(skipped);
+        value
+    }
+
+    fn conditional(&mut self) -> Result<Value, ExpressionError> {
+        let condition = self.binary(1)?;
+        let operator = match self.peek().map(|token| &token.token_type) {
+            Some(TokenType::Operator(operator @ (Operator::Conditional | Operator::Elvis))) => {
+                *operator
+            }
+            _ => return Ok(condition),
+        };
+        self.index += 1;
+        let operator = if self.elvis
+            && operator == Operator::Conditional
+            && self.eat_delimiter(|delimiter| matches!(delimiter, Delimiter::Colon))
+        {
+            Operator::Elvis
+        } else {
+            operator
+        };
+        let first = if matches!(operator, Operator::Conditional) {
+            let first = self.operand(!condition.is_true(), Self::conditional)?;
+            self.expect_delimiter(|delimiter| matches!(delimiter, Delimiter::Colon))?;
+            first
+        } else {
+            condition
+        };
+        let second = self.operand(condition.is_true(), Self::conditional)?;
+        let chosen = if condition.is_true() { first } else { second };
+        Ok(if first.is_unsigned() || second.is_unsigned() {
+            Value::Unsigned(chosen.bits())
+        } else {
+            chosen
+        })
+    }
+
+    /// Parses binary operators binding at least as tightly as `precedence`.
+    fn binary(&mut self, precedence: u8) -> Result<Value, ExpressionError> {
+        let mut left = self.unary()?;
+        loop {
+            let Some(token) = self.peek() else {
+                return Ok(left);
+            };
+            let location = token.span.start;
+            let operator = match &token.token_type {
+                TokenType::Operator(operator) => *operator,
+                _ => return Ok(left),
+            };
+            let Some(binding) = binding(operator).filter(|binding| *binding >= precedence) else {
+                return Ok(left);
+            };
+            self.index += 1;
+            left = match operator {
+                Operator::LogicalAnd => {
+                    let right = self.operand(!left.is_true(), |this| this.binary(binding + 1))?;
+                    Value::from_bool(left.is_true() && right.is_true())
+                }
+                Operator::LogicalOr => {
+                    let right = self.operand(left.is_true(), |this| this.binary(binding + 1))?;
+                    Value::from_bool(left.is_true() || right.is_true())
+                }
+                _ => {
+                    let right = self.binary(binding + 1)?;
+                    self.apply(operator, left, right, location)?
+                }
+            };
+        }
+    }
+
+    fn apply(
+        &self,
+        operator: Operator,
+        left: Value,
+        right: Value,
+        location: Location,
+    ) -> Result<Value, ExpressionError> {
+        let zero = left.zero_like(right);
+        if matches!(operator, Operator::LeftShift | Operator::RightShift) {
+            return self.shift(operator, left, right, location);
+        }
+        let value = match (left, right) {
+            (Value::Signed(left), Value::Signed(right)) => {
+                let value = match operator {
+                    Operator::Multiplication => left.checked_mul(right),
+                    Operator::Division | Operator::Remainder if right == 0 => {
+                        return self.fail(ExpressionError::DivisionByZero { location }, zero);
+                    }
+                    Operator::Division => left.checked_div(right),
+                    Operator::Remainder => left.checked_rem(right),
+                    Operator::Addition => left.checked_add(right),
+                    Operator::Subtraction => left.checked_sub(right),
+                    Operator::BitwiseAnd => Some(left & right),
+                    Operator::ExclusiveOr => Some(left ^ right),
+                    Operator::BitwiseOr => Some(left | right),
+                    _ => return Ok(Value::from_bool(compare(operator, left, right))),
+                };
+                match value {
+                    Some(value) => Value::Signed(value),
+                    None => return self.fail(ExpressionError::Overflow { location }, zero),
+                }
+            }
+            _ => {
+                let (left, right) = (left.bits(), right.bits());
+                Value::Unsigned(match operator {
+                    Operator::Multiplication => left.wrapping_mul(right),
+                    Operator::Division | Operator::Remainder if right == 0 => {
+                        return self.fail(ExpressionError::DivisionByZero { location }, zero);
+                    }
+                    Operator::Division => left / right,
+                    Operator::Remainder => left % right,
+                    Operator::Addition => left.wrapping_add(right),
+                    Operator::Subtraction => left.wrapping_sub(right),
+                    Operator::BitwiseAnd => left & right,
+                    Operator::ExclusiveOr => left ^ right,
+                    Operator::BitwiseOr => left | right,
+                    _ => return Ok(Value::from_bool(compare(operator, left, right))),
+                })
+            }
+        };
+        Ok(value)
+    }
+
+    /// Shifts keep the type of their left operand.
+    fn shift(
+        &self,
+        operator: Operator,
+        left: Value,
+        right: Value,
+        location: Location,
+    ) -> Result<Value, ExpressionError> {
+        let zero = left.zero_like(left);
+        let count = match right {
+            Value::Signed(count) => u32::try_from(count).ok(),
+            Value::Unsigned(count) => u32::try_from(count).ok(),
+        };
+        let Some(count) = count.filter(|count| *count < 64) else {
+            return self.fail(ExpressionError::InvalidShift { location }, zero);
+        };
+        let left_shift = matches!(operator, Operator::LeftShift);
+        match left {
+            Value::Unsigned(value) if left_shift => Ok(Value::Unsigned(value << count)),
+            Value::Unsigned(value) => Ok(Value::Unsigned(value >> count)),
+            Value::Signed(value) if left_shift => {
+                let shifted = i64::try_from(i128::from(value) << count)
+                    .ok()
+                    .filter(|_| value >= 0);
+                match shifted {
+                    Some(shifted) => Ok(Value::Signed(shifted)),
+                    None => self.fail(ExpressionError::Overflow { location }, zero),
+                }
+            }
+            Value::Signed(value) => Ok(Value::Signed(value >> count)),
+        }
+    }
+
+    fn unary(&mut self) -> Result<Value, ExpressionError> {
+        let token = self.next_token()?;
+        let location = token.span.start;
+        let operator = match &token.token_type {
+            TokenType::Operator(operator) => *operator,
+            _ => {
+                self.index -= 1;
+                return self.primary();
+            }
+        };
+        match operator {
+            Operator::Addition => self.unary(),
+            Operator::Subtraction => match self.unary()? {
+                Value::Signed(value) => match value.checked_neg() {
+                    Some(negated) => Ok(Value::Signed(negated)),
+                    None => self.fail(ExpressionError::Overflow { location }, Value::Signed(0)),
+                },
+                Value::Unsigned(value) => Ok(Value::Unsigned(value.wrapping_neg())),
+            },
+            Operator::BitwiseNegation => match self.unary()? {
+                Value::Signed(value) => Ok(Value::Signed(!value)),
+                Value::Unsigned(value) => Ok(Value::Unsigned(!value)),
+            },
+            Operator::LogicalNegation => Ok(Value::from_bool(!self.unary()?.is_true())),
+            _ => {
+                self.index -= 1;
+                self.primary()
+            }
+        }
+    }
+
+    fn primary(&mut self) -> Result<Value, ExpressionError> {
+        let token = self.next_token()?;
+        let location = token.span.start;
+        match &token.token_type {
+            TokenType::Number(number) => constant(number, location),
+            TokenType::Character(character) => Ok(match character.value.value {
+                number::Value::Unsigned(value) => Value::Unsigned(value as u64),
+                number::Value::Signed(value) => Value::Signed(value as i64),
+                number::Value::Floating(_) => Value::Signed(0),
+            }),
+            TokenType::Identifier(_) if token.symbol == Some(Symbol::DEFINED) => {
+                self.defined(location)
+            }
+            TokenType::Identifier(_) if token.symbol == Some(Symbol::HAS_INCLUDE) => {
+                self.has_include(location)
+            }
+            TokenType::Keyword(Keyword::True) => Ok(Value::Signed(1)),
+            TokenType::Identifier(_) | TokenType::Keyword(_) => Ok(Value::Signed(0)),
+            TokenType::Delimiter(Delimiter::OpeningParenthesis) => {
+                let value = self.conditional()?;
+                self.expect_delimiter(|delimiter| {
+                    matches!(delimiter, Delimiter::ClosingParenthesis)
+                })?;
+                Ok(value)
+            }
+            _ => Err(unexpected(token)),
+        }
+    }
+
+    /// Evaluates `defined NAME` or `defined ( NAME )`.
+    fn defined(&mut self, location: Location) -> Result<Value, ExpressionError> {
+        let parenthesised =
+            self.eat_delimiter(|delimiter| matches!(delimiter, Delimiter::OpeningParenthesis));
+        let name = match self.peek() {
+            Some(Token {
+                token_type: TokenType::Identifier(_) | TokenType::Keyword(_),
+                symbol: Some(symbol),
+                ..
+            }) => *symbol,
+            _ => return Err(ExpressionError::InvalidDefined { location }),
+        };
+        self.index += 1;
+        if parenthesised
+            && !self.eat_delimiter(|delimiter| matches!(delimiter, Delimiter::ClosingParenthesis))
+        {
+            return Err(ExpressionError::InvalidDefined { location });
+        }
+        Ok(Value::from_bool((self.is_defined)(name)))
+    }
+
+    /// Evaluates `__has_include ( <name> )` or `__has_include ( "name" )`.
+    fn has_include(&mut self, location: Location) -> Result<Value, ExpressionError> {
+        let error = ExpressionError::InvalidHasInclude { location };
+        if !self.eat_delimiter(|delimiter| matches!(delimiter, Delimiter::OpeningParenthesis)) {
+            return Err(error);
+        }
+        let header = match self.peek().map(|token| &token.token_type) {
+            Some(TokenType::HeaderName(header)) => {
+                self.index += 1;
+                header.clone()
+            }
+            Some(TokenType::Literal(literal)) => {
+                self.index += 1;
+                HeaderName {
+                    name: Cow::Owned(literal.pieces().collect()),
+                    angled: false,
+                }
+            }
+            // The name came out of a macro, so it was lexed as separate
+            // tokens.
+            Some(TokenType::Operator(Operator::Smaller)) => {
+                let rest = &self.tokens[self.index + 1..];
+                let length = rest
+                    .iter()
+                    .position(|token| {
+                        matches!(token.token_type, TokenType::Operator(Operator::Bigger))
+                    })
+                    .ok_or(ExpressionError::InvalidHasInclude { location })?;
+                let name = Cow::Owned(
+                    rest[..length]
+                        .iter()
+                        .map(|token| token.token_type.content())
+                        .collect(),
+                );
+                self.index += length + 2;
+                HeaderName { name, angled: true }
+            }
+            _ => return Err(error),
+        };
+        if !self.eat_delimiter(|delimiter| matches!(delimiter, Delimiter::ClosingParenthesis)) {
+            return Err(error);
+        }
+        Ok(Value::from_bool((self.has_include)(&header)))
+    }
+}
+
+/// How tightly a binary operator binds, from `||` up to `*`.
+fn binding(operator: Operator) -> Option<u8> {
+    match operator {
+        Operator::LogicalOr => Some(1),
+        Operator::LogicalAnd => Some(2),
+        Operator::BitwiseOr => Some(3),
+        Operator::ExclusiveOr => Some(4),
+        Operator::BitwiseAnd => Some(5),
+        Operator::Equal | Operator::NotEqual => Some(6),
+        Operator::Smaller
+        | Operator::Bigger
+        | Operator::SmallerOrEqual
+        | Operator::BiggerOrEqual => Some(7),
+        Operator::LeftShift | Operator::RightShift => Some(8),
+        Operator::Addition | Operator::Subtraction => Some(9),
+        Operator::Multiplication | Operator::Division | Operator::Remainder => Some(10),
+        _ => None,
+    }
+}
+
+fn compare<T: Ord>(operator: Operator, left: T, right: T) -> bool {
+    match operator {
+        Operator::Equal => left == right,
+        Operator::NotEqual => left != right,
+        Operator::Smaller => left < right,
+        Operator::Bigger => left > right,
+        Operator::SmallerOrEqual => left <= right,
+        _ => left >= right,
+    }
+}
+
+/// The value of an integer constant, with every signed type widened to
+/// `intmax_t` and every unsigned one to `uintmax_t`.
+fn constant(number: &number::Number, location: Location) -> Result<Value, ExpressionError> {
+    if matches!(number.kind, NumberKind::Floating(_)) {
+        return Err(ExpressionError::FloatingConstant {
+            number: number.spelling.to_string(),
+            location,
+        });
+    }
+    let invalid = |reason| ExpressionError::InvalidNumber {
+        number: number.spelling.to_string(),
+        reason,
+        location,
+    };
+    let constant = number.evaluate(DataModel::default()).map_err(invalid)?;
+    let value = match constant.value {
+        number::Value::Signed(value) => i64::try_from(value).ok().map(Value::Signed),
+        number::Value::Unsigned(value) => u64::try_from(value).ok().map(Value::Unsigned),
+        number::Value::Floating(_) => None,
+    };
+    let value = value.ok_or_else(|| invalid(number::NumberError::TooLarge))?;
+    Ok(if constant.c_type.is_unsigned() {
+        Value::Unsigned(value.bits())
+    } else {
+        value
+    })
+}
+
+fn unexpected(token: &Token) -> ExpressionError {
+    ExpressionError::UnexpectedToken {
+        token: token.token_type.content(),
+        location: token.span.start,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{standard::Standard, symbol::Interner, Lexer};
+
+    fn evaluate_with(text: &str, extensions: &[Extension]) -> Result<Value, ExpressionError> {
+        let mut interner = Interner::new();
+        let defined = interner.intern("DEFINED");
+        let mut lexer = Lexer::new(text)
+            .with_standard(Standard::C23)
+            .with_interner(&mut interner);
+        for extension in extensions {
+            lexer = lexer.with_extension(*extension);
+        }
+        let tokens: Vec<Token> = lexer.filter_map(Result::ok).collect();
+        evaluate(&tokens, extensions, &|name| name == defined, &|_| false)
+    }
+
+    fn value(text: &str) -> Value {
+        evaluate_with(text, &[]).unwrap_or_else(|error| panic!("{}: {}", text, error))
+    }
+
+    #[test]
+    fn usual_arithmetic_conversions() {
+        for (text, expected) in [
+            ("-1 < 0u", Value::Signed(0)),
+            ("-1 > 0u", Value::Signed(1)),
+            ("-1 == 18446744073709551615u", Value::Signed(1)),
+            ("0u - 1", Value::Unsigned(u64::MAX)),
+            ("-1 / 2u", Value::Unsigned(u64::MAX / 2)),
+            ("'a' + 0u", Value::Unsigned(97)),
+            ("-7 / 2", Value::Signed(-3)),
+            ("-7 % 2", Value::Signed(-1)),
+            ("1 ? -1 : 0u", Value::Unsigned(u64::MAX)),
+            ("0 ? 0u : -1", Value::Unsigned(u64::MAX)),
+            ("-9223372036854775807 - 1", Value::Signed(i64::MIN)),
+        ] {
+            assert_eq!(value(text), expected, "{}", text);
+        }
+    }
+
+    #[test]
+    fn constants_take_intmax_or_uintmax_types() {
+        for (text, expected) in [
+            ("1", Value::Signed(1)),
+            ("1u", Value::Unsigned(1)),
+            ("0x7fffffffffffffff", Value::Signed(i64::MAX)),
+            ("0x8000000000000000", Value::Unsigned(1 << 63)),
+            ("'a'", Value::Signed(97)),
+            ("~0u", Value::Unsigned(u64::MAX)),
+            ("!0u", Value::Signed(1)),
+            ("true", Value::Signed(1)),
+            ("unknown + 3", Value::Signed(3)),
+            ("defined DEFINED + defined(UNDEFINED)", Value::Signed(1)),
+        ] {
+            assert_eq!(value(text), expected, "{}", text);
+        }
+    }
+
+    #[test]
+    fn errors_in_skipped_operands_are_ignored() {
+        for text in [
+            "0 && 1 / 0",
+            "1 || 1 % 0",
+            "0 ? 1 / 0 : 2",
+            "1 ? 2 : 1 / 0",
+            "0 && (0x7fffffffffffffff + 1)",
+            "0 && (1 << 64)",
+        ] {
+            assert!(evaluate_with(text, &[]).is_ok(), "{}", text);
+        }
+        assert!(matches!(
+            evaluate_with("1 && 1 / 0", &[]),
+            Err(ExpressionError::DivisionByZero { .. })
+        ));
+        assert!(matches!(
+            evaluate_with("0 || 1 % 0", &[]),
+            Err(ExpressionError::DivisionByZero { .. })
+        ));
+    }
+
+    #[test]
+    fn signed_overflow_is_an_error() {
+        for text in [
+            "0x7fffffffffffffff + 1",
+            "(-9223372036854775807 - 1) / -1",
+            "1 << 63",
+        ] {
+            assert!(
+                matches!(
+                    evaluate_with(text, &[]),
+                    Err(ExpressionError::Overflow { .. })
+                ),
+                "{}",
+                text
+            );
+        }
+        assert_eq!(value("0x7fffffffffffffffu + 1"), Value::Unsigned(1 << 63));
+    }
+
+    #[test]
+    fn gnu_elvis_operator() {
+        let gnu = [Extension::Gnu];
+        for (text, expected) in [
+            ("3 ?: 5", Value::Signed(3)),
+            ("0 ?: 5", Value::Signed(5)),
+            ("0 ? : 5", Value::Signed(5)),
+            ("2 ? : 1 / 0", Value::Signed(2)),
+            ("0 ? : 1u", Value::Unsigned(1)),
+        ] {
+            let value =
+                evaluate_with(text, &gnu).unwrap_or_else(|error| panic!("{}: {}", text, error));
+            assert_eq!(value, expected, "{}", text);
+        }
+        assert!(matches!(
+            evaluate_with("0 ? : 5", &[]),
+            Err(ExpressionError::UnexpectedToken { .. })
+        ));
+    }
+}
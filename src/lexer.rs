@@ -0,0 +1,1799 @@
+use std::{borrow::Cow, collections::VecDeque, fs, path::Path, sync::OnceLock};
+
+use unicode_xid::UnicodeXID;
+
+mod stream;
+
+pub use stream::StreamingLexer;
+
+use crate::{
+    error::LexError,
+    literal::{universal_character_name, CharacterConstant, Encoding, LiteralError, StringLiteral},
+    number::Number,
+    perfect_hash::PerfectHash,
+    standard::{Extension, Standard},
+    symbol::Intern,
+    tokens::{
+        Delimiter, Directive, HeaderName, Keyword, Location, Punctuator, Span, Token, TokenType,
+        PUNCTUATORS,
+    },
+};
+
+pub fn keywords() -> &'static PerfectHash<Keyword> {
+    static KEYWORDS: OnceLock<PerfectHash<Keyword>> = OnceLock::new();
+    KEYWORDS.get_or_init(|| {
+        PerfectHash::new([
+            ("auto", Keyword::Auto),
+            ("break", Keyword::Break),
+            ("case", Keyword::Case),
+            ("char", Keyword::Char),
+            ("const", Keyword::Const),
+            ("continue", Keyword::Continue),
+            ("default", Keyword::Default),
+            ("do", Keyword::Do),
+            ("double", Keyword::Double),
+            ("else", Keyword::Else),
+            ("enum", Keyword::Enum),
+            ("extern", Keyword::Extern),
+            ("float", Keyword::Float),
+            ("for", Keyword::For),
+            ("goto", Keyword::Goto),
+            ("if", Keyword::If),
+            ("inline", Keyword::Inline),
+            ("int", Keyword::Int),
+            ("long", Keyword::Long),
+            ("register", Keyword::Register),
+            ("restrict", Keyword::Restrict),
+            ("return", Keyword::Return),
+            ("short", Keyword::Short),
+            ("signed", Keyword::Signed),
+            ("sizeof", Keyword::Sizeof),
+            ("static", Keyword::Static),
+            ("struct", Keyword::Struct),
+            ("switch", Keyword::Switch),
+            ("typedef", Keyword::Typedef),
+            ("union", Keyword::Union),
+            ("unsigned", Keyword::Unsigned),
+            ("void", Keyword::Void),
+            ("volatile", Keyword::Volatile),
+            ("while", Keyword::While),
+            ("_Bool", Keyword::_Bool),
+            ("_Complex", Keyword::_Complex),
+            ("_Imaginary", Keyword::_Imaginary),
+            ("_Alignas", Keyword::_Alignas),
+            ("_Alignof", Keyword::_Alignof),
+            ("_Atomic", Keyword::_Atomic),
+            ("_Generic", Keyword::_Generic),
+            ("_Noreturn", Keyword::_Noreturn),
+            ("_Static_assert", Keyword::_StaticAssert),
+            ("_Thread_local", Keyword::_ThreadLocal),
+            ("alignas", Keyword::Alignas),
+            ("alignof", Keyword::Alignof),
+            ("bool", Keyword::Bool),
+            ("constexpr", Keyword::Constexpr),
+            ("false", Keyword::False),
+            ("nullptr", Keyword::Nullptr),
+            ("static_assert", Keyword::StaticAssert),
+            ("thread_local", Keyword::ThreadLocal),
+            ("true", Keyword::True),
+            ("typeof", Keyword::Typeof),
+            ("typeof_unqual", Keyword::TypeofUnqual),
+            ("_BitInt", Keyword::_BitInt),
+            ("_Decimal32", Keyword::_Decimal32),
+            ("_Decimal64", Keyword::_Decimal64),
+            ("_Decimal128", Keyword::_Decimal128),
+        ])
+    })
+}
+
+/// Keywords added by the GNU dialects, including the standard ones they
+/// accept before the standard that introduced them. Of the `__builtin_*`
+/// names only the ones taking a type or a member name are listed, as the
+/// rest are called like ordinary functions.
+pub fn gnu_keywords() -> &'static PerfectHash<Keyword> {
+    static GNU_KEYWORDS: OnceLock<PerfectHash<Keyword>> = OnceLock::new();
+    GNU_KEYWORDS.get_or_init(|| {
+        PerfectHash::new([
+            ("asm", Keyword::Asm),
+            ("typeof", Keyword::Typeof),
+            ("inline", Keyword::Inline),
+            ("restrict", Keyword::Restrict),
+            ("__asm", Keyword::__Asm),
+            ("__asm__", Keyword::__Asm__),
+            ("__attribute", Keyword::__Attribute),
+            ("__attribute__", Keyword::__Attribute__),
+            ("__typeof", Keyword::__Typeof),
+            ("__typeof__", Keyword::__Typeof__),
+            ("__extension__", Keyword::__Extension__),
+            ("__inline", Keyword::__Inline),
+            ("__inline__", Keyword::__Inline__),
+            ("__restrict", Keyword::__Restrict),
+            ("__restrict__", Keyword::__Restrict__),
+            ("__const", Keyword::__Const),
+            ("__const__", Keyword::__Const__),
+            ("__volatile", Keyword::__Volatile),
+            ("__volatile__", Keyword::__Volatile__),
+            ("__signed", Keyword::__Signed),
+            ("__signed__", Keyword::__Signed__),
+            ("__alignof", Keyword::__Alignof),
+            ("__alignof__", Keyword::__Alignof__),
+            ("__label__", Keyword::__Label__),
+            ("__auto_type", Keyword::__AutoType),
+            ("__int128", Keyword::__Int128),
+            ("__real__", Keyword::__Real__),
+            ("__imag__", Keyword::__Imag__),
+            ("__complex__", Keyword::__Complex__),
+            ("__thread", Keyword::__Thread),
+            ("__builtin_va_arg", Keyword::__BuiltinVaArg),
+            ("__builtin_va_list", Keyword::__BuiltinVaList),
+            ("__builtin_offsetof", Keyword::__BuiltinOffsetof),
+            (
+                "__builtin_types_compatible_p",
+                Keyword::__BuiltinTypesCompatibleP,
+            ),
+            ("__builtin_choose_expr", Keyword::__BuiltinChooseExpr),
+        ])
+    })
+}
+
+/// Keywords added by Microsoft's compiler.
+pub fn msvc_keywords() -> &'static PerfectHash<Keyword> {
+    static MSVC_KEYWORDS: OnceLock<PerfectHash<Keyword>> = OnceLock::new();
+    MSVC_KEYWORDS.get_or_init(|| {
+        PerfectHash::new([
+            ("__asm", Keyword::__Asm),
+            ("__inline", Keyword::__Inline),
+            ("__restrict", Keyword::__Restrict),
+            ("__alignof", Keyword::__Alignof),
+            ("__declspec", Keyword::__Declspec),
+            ("__forceinline", Keyword::__Forceinline),
+            ("__int8", Keyword::__Int8),
+            ("__int16", Keyword::__Int16),
+            ("__int32", Keyword::__Int32),
+            ("__int64", Keyword::__Int64),
+            ("__cdecl", Keyword::__Cdecl),
+            ("__stdcall", Keyword::__Stdcall),
+            ("__fastcall", Keyword::__Fastcall),
+            ("__vectorcall", Keyword::__Vectorcall),
+            ("__thiscall", Keyword::__Thiscall),
+            ("__clrcall", Keyword::__Clrcall),
+            ("__ptr32", Keyword::__Ptr32),
+            ("__ptr64", Keyword::__Ptr64),
+            ("__unaligned", Keyword::__Unaligned),
+            ("__w64", Keyword::__W64),
+            ("__based", Keyword::__Based),
+            ("__try", Keyword::__Try),
+            ("__except", Keyword::__Except),
+            ("__finally", Keyword::__Finally),
+            ("__leave", Keyword::__Leave),
+            ("__pragma", Keyword::__Pragma),
+            ("__assume", Keyword::__Assume),
+            ("__noop", Keyword::__Noop),
+        ])
+    })
+}
+
+/// The feature an encoding prefix of a literal quoted with `quote` is and
+/// the standard that introduced it, or `None` for `L`, which C has always
+/// had.
+fn prefix_feature(prefix: &str, quote: char) -> Option<(&'static str, Standard)> {
+    match (prefix, quote) {
+        ("u8", '"') => Some(("u8 string literals", Standard::C11)),
+        ("u" | "U", '"') => Some(("u and U string literals", Standard::C11)),
+        ("u8", '\'') => Some(("u8 character constants", Standard::C23)),
+        ("u" | "U", '\'') => Some(("u and U character constants", Standard::C11)),
+        _ => None,
+    }
+}
+
+pub fn directives() -> &'static PerfectHash<Directive> {
+    static DIRECTIVES: OnceLock<PerfectHash<Directive>> = OnceLock::new();
+    DIRECTIVES.get_or_init(|| {
+        PerfectHash::new([
+            ("include", Directive::Include),
+            ("define", Directive::Define),
+            ("undef", Directive::Undef),
+            ("if", Directive::If),
+            ("ifdef", Directive::Ifdef),
+            ("ifndef", Directive::Ifndef),
+            ("elif", Directive::Elif),
+            ("elifdef", Directive::Elifdef),
+            ("elifndef", Directive::Elifndef),
+            ("else", Directive::Else),
+            ("endif", Directive::Endif),
+            ("line", Directive::Line),
+            ("error", Directive::Error),
+            ("warning", Directive::Warning),
+            ("pragma", Directive::Pragma),
+            ("embed", Directive::Embed),
+        ])
+    })
+}
+
+/// A state of the DFA over the spellings of [`PUNCTUATORS`] that
+/// [`punctuators`] builds. Every spelling is ASCII.
+struct PunctuatorState {
+    /// The state after each byte, or zero if there is none.
+    next: [u8; 128],
+    /// The index in `PUNCTUATORS` of the spelling that ends here.
+    punctuator: Option<u8>,
+}
+
+/// The states of the DFA recognising [`PUNCTUATORS`], starting with the
+/// initial one. It is a trie, so each state is reached by one spelling.
+fn punctuators() -> &'static [PunctuatorState] {
+    static PUNCTUATOR_STATES: OnceLock<Vec<PunctuatorState>> = OnceLock::new();
+    PUNCTUATOR_STATES.get_or_init(|| {
+        let empty = || PunctuatorState {
+            next: [0; 128],
+            punctuator: None,
+        };
+        let mut states = vec![empty()];
+        for (index, (spelling, _, _)) in PUNCTUATORS.iter().enumerate() {
+            let mut state = 0;
+            for byte in spelling.bytes() {
+                let next = usize::from(states[state].next[usize::from(byte)]);
+                state = if next == 0 {
+                    states.push(empty());
+                    let next = states.len() - 1;
+                    assert!(next <= u8::MAX as usize, "too many punctuator states");
+                    states[state].next[usize::from(byte)] = next as u8;
+                    next
+                } else {
+                    next
+                };
+            }
+            assert!(index <= u8::MAX as usize, "too many punctuators");
+            states[state].punctuator = Some(index as u8);
+        }
+        states
+    })
+}
+
+/// A bit of [`CLASSES`]: space, tab, newline, vertical tab, form feed or
+/// carriage return.
+const WHITESPACE: u8 = 1 << 0;
+/// A bit of [`CLASSES`]: an ASCII letter, digit or `_`.
+const WORD: u8 = 1 << 1;
+/// A bit of [`CLASSES`]: `\` and `?`, which may start a line splice or
+/// trigraph, and the bytes outside ASCII, which need decoding. Text without
+/// these reads the same a byte at a time as after phases 1 and 2.
+const SPECIAL: u8 = 1 << 2;
+
+/// What each byte can be to the lexer.
+static CLASSES: [u8; 256] = {
+    let mut classes = [0; 256];
+    let mut byte = 0;
+    while byte < 256 {
+        classes[byte] = match byte as u8 {
+            b' ' | b'\t' | b'\n' | b'\x0b' | b'\x0c' | b'\r' => WHITESPACE,
+            b'0'..=b'9' | b'a'..=b'z' | b'A'..=b'Z' | b'_' => WORD,
+            b'\\' | b'?' | 0x80..=0xff => SPECIAL,
+            _ => 0,
+        };
+        byte += 1;
+    }
+    classes
+};
+
+fn class(byte: u8) -> u8 {
+    CLASSES[usize::from(byte)]
+}
+
+/// Whether `character` may start an identifier. C23 allows `_` and the
+/// characters Unicode gives the XID_Start property.
+pub fn is_identifier_start(character: char) -> bool {
+    character == '_' || character.is_xid_start()
+}
+
+/// Whether `character` may follow the start of an identifier, which C23
+/// takes from Unicode's XID_Continue property.
+pub fn is_identifier_continue(character: char) -> bool {
+    character.is_xid_continue()
+}
+
+/// Decodes the universal character names in an identifier, so that `caf\u00E9`
+/// and `café` compare equal. Identifiers the lexer yields are already
+/// normalised this way.
+pub fn normalize_identifier(spelling: &str) -> Cow<'_, str> {
+    if !spelling.contains('\\') {
+        return Cow::Borrowed(spelling);
+    }
+    let mut normalized = String::with_capacity(spelling.len());
+    let mut characters = spelling.chars().peekable();
+    while let Some(character) = characters.next() {
+        match characters.peek() {
+            Some(&escape @ ('u' | 'U')) if character == '\\' => {
+                characters.next();
+                match universal_character_name(escape, &mut characters) {
+                    Ok(character) => normalized.push(character),
+                    Err(_) => return Cow::Borrowed(spelling),
+                }
+            }
+            _ => normalized.push(character),
+        }
+    }
+    Cow::Owned(normalized)
+}
+
+/// The character a trigraph such as `??=` stands for, given its last
+/// character.
+fn trigraph(last: u8) -> Option<char> {
+    let replacement = match last {
+        b'=' => '#',
+        b'(' => '[',
+        b'/' => '\\',
+        b')' => ']',
+        b'\'' => '^',
+        b'<' => '{',
+        b'!' => '|',
+        b'>' => '}',
+        b'-' => '~',
+        _ => return None,
+    };
+    Some(replacement)
+}
+
+/// Whether trigraphs are replaced: they were removed in C23, and GCC
+/// ignores them in its GNU modes too.
+pub fn replaces_trigraphs(standard: Standard, extensions: &[Extension]) -> bool {
+    standard < Standard::C23 && !extensions.contains(&Extension::Gnu)
+}
+
+/// Carries out translation phases 1 and 2 on `text`: replaces trigraphs if
+/// `trigraphs` is set and joins lines ending in a backslash with the next.
+pub fn splice_lines(text: &str, trigraphs: bool) -> Cow<'_, str> {
+    let has_trigraphs = trigraphs && text.contains("??");
+    if !text.contains('\\') && !has_trigraphs {
+        return Cow::Borrowed(text);
+    }
+    let bytes = text.as_bytes();
+    let mut spliced = String::with_capacity(text.len());
+    let mut index = 0;
+    while index < text.len() {
+        index += splice_length(bytes, index, trigraphs);
+        if let Some((character, length)) = logical_character(text, index, trigraphs) {
+            spliced.push(character);
+            index += length;
+        }
+    }
+    Cow::Owned(spliced)
+}
+
+/// How many bytes at `index` are backslash-newlines, which phase 2
+/// removes.
+fn splice_length(bytes: &[u8], index: usize, trigraphs: bool) -> usize {
+    let mut length = 0;
+    loop {
+        let rest = bytes.get(index + length..).unwrap_or_default();
+        let backslash = match rest {
+            [b'\\', ..] => 1,
+            [b'?', b'?', b'/', ..] if trigraphs => 3,
+            _ => return length,
+        };
+        match &rest[backslash..] {
+            [b'\n', ..] => length += backslash + 1,
+            [b'\r', b'\n', ..] => length += backslash + 2,
+            _ => return length,
+        }
+    }
+}
+
+/// The character at byte `index` after phase 1, and how many bytes it is
+/// written with.
+fn logical_character(text: &str, index: usize, trigraphs: bool) -> Option<(char, usize)> {
+    let bytes = text.as_bytes();
+    match bytes.get(index..index + 3) {
+        Some([b'?', b'?', last]) if trigraphs => match trigraph(*last) {
+            Some(replacement) => Some((replacement, 3)),
+            None => Some(('?', 1)),
+        },
+        _ => match *bytes.get(index)? {
+            byte if byte.is_ascii() => Some((char::from(byte), 1)),
+            _ => {
+                let character = text.get(index..)?.chars().next()?;
+                Some((character, character.len_utf8()))
+            }
+        },
+    }
+}
+
+/// Reads a source file, reporting unreadable files and invalid UTF-8 as
+/// [`LexError`]s instead of panicking.
+pub fn read_source(path: &Path) -> Result<String, LexError> {
+    let bytes = fs::read(path)?;
+    String::from_utf8(bytes).map_err(|error| {
+        let offset = error.utf8_error().valid_up_to();
+        // The bytes before `offset` are known to be UTF-8, so this never
+        // falls back.
+        let valid = std::str::from_utf8(&error.as_bytes()[..offset]).unwrap_or_default();
+        let line_start = valid.rfind('\n').map_or(0, |index| index + 1);
+        LexError::InvalidEncoding {
+            location: Location {
+                offset,
+                line: valid.matches('\n').count() + 1,
+                column: valid[line_start..].chars().count() + 1,
+                utf16_column: None,
+            },
+        }
+    })
+}
+
+/// Whether `tokens` end with `__has_include (`, after which a header name
+/// is expected.
+fn follows_has_include(tokens: &[Token]) -> bool {
+    matches!(
+        tokens,
+        [.., Token {
+            token_type: TokenType::Identifier(name),
+            ..
+        }, Token {
+            token_type: TokenType::Delimiter(Delimiter::OpeningParenthesis),
+            ..
+        }] if name == "__has_include"
+    )
+}
+
+/// Splits C source code into tokens.
+///
+/// The lexer yields one `Result` per token, so callers can decide whether to
+/// stop at the first error or keep going. It never panics: after an error it
+/// yields a [`TokenType::Error`] token covering the text it skipped and
+/// carries on with the rest of the input.
+///
+/// Trigraphs and backslash-newlines are dealt with as the characters are
+/// read, so tokens may span them while their locations still point into the
+/// source as written.
+///
+/// The source is read as bytes. Runs of text without line splices,
+/// trigraphs or characters outside ASCII, which is nearly all of it, are
+/// classified through a table a byte at a time, and only the rest is
+/// decoded.
+///
+/// Tokens borrow their text from the source, so lexing costs no allocation
+/// per token except where a line splice or universal character name means
+/// the text has to be rewritten.
+pub struct Lexer<'src> {
+    /// The source as written. The location's offset is the position in it.
+    source: &'src str,
+    location: Location,
+    tab_width: usize,
+    standard: Standard,
+    warn_newer_features: bool,
+    extensions: Vec<Extension>,
+    at_line_start: bool,
+    /// Whether trigraphs are replaced, which the standard and extensions
+    /// decide.
+    trigraphs: bool,
+    interner: Option<Box<dyn Intern + 'src>>,
+    pending: VecDeque<Result<Token<'src>, LexError>>,
+}
+
+impl<'src> Lexer<'src> {
+    pub fn new(source: &'src str) -> Self {
+        Lexer {
+            source,
+            location: Location::START,
+            tab_width: 1,
+            standard: Standard::default(),
+            warn_newer_features: false,
+            extensions: vec![],
+            at_line_start: true,
+            trigraphs: replaces_trigraphs(Standard::default(), &[]),
+            interner: None,
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// Selects the C standard, which decides which words are keywords.
+    pub fn with_standard(mut self, standard: Standard) -> Self {
+        self.standard = standard;
+        self.trigraphs = replaces_trigraphs(self.standard, &self.extensions);
+        self
+    }
+
+    /// Reports a [`LexError::NewerFeature`] warning before each token that
+    /// relies on a newer standard than the selected one.
+    pub fn warning_on_newer_features(mut self) -> Self {
+        self.warn_newer_features = true;
+        self
+    }
+
+    /// Enables a compiler's extensions: its extra keywords, and for GNU the
+    /// `?:` operator. Both allow `$` in identifiers.
+    pub fn with_extension(mut self, extension: Extension) -> Self {
+        if !self.extensions.contains(&extension) {
+            self.extensions.push(extension);
+        }
+        self.trigraphs = replaces_trigraphs(self.standard, &self.extensions);
+        self
+    }
+
+    /// Makes a tab advance the column to the next multiple of `tab_width`
+    /// (plus one) instead of counting as a single column.
+    pub fn with_tab_width(mut self, tab_width: usize) -> Self {
+        self.tab_width = tab_width.max(1);
+        self
+    }
+
+    /// Also tracks columns in UTF-16 code units, as most editors expect.
+    pub fn with_utf16_columns(mut self) -> Self {
+        self.location.utf16_column = Some(1);
+        self
+    }
+
+    /// Interns the name of every identifier and keyword into `interner`,
+    /// such as `&mut Interner` or `&SharedInterner`, and sets the token's
+    /// [`symbol`](Token::symbol).
+    pub fn with_interner(mut self, interner: impl Intern + 'src) -> Self {
+        self.interner = Some(Box::new(interner));
+        self
+    }
+
+    /// The character at byte `index` after phases 1 and 2, and the index
+    /// just past it.
+    fn character_at(&self, index: usize) -> Option<(char, usize)> {
+        let byte = *self.source.as_bytes().get(index)?;
+        if class(byte) & SPECIAL == 0 {
+            return Some((char::from(byte), index + 1));
+        }
+        let index = index + splice_length(self.source.as_bytes(), index, self.trigraphs);
+        let (character, length) = logical_character(self.source, index, self.trigraphs)?;
+        Some((character, index + length))
+    }
+
+    /// The character `offset` characters ahead after phases 1 and 2.
+    fn peek(&self, offset: usize) -> Option<char> {
+        let mut index = self.location.offset;
+        for _ in 0..offset {
+            index = self.character_at(index)?.1;
+        }
+        self.character_at(index).map(|(character, _)| character)
+    }
+
+    /// The index of the first byte from the current position on that is
+    /// not `wanted`, judged by its class alone. Bytes that are
+    /// [`SPECIAL`] always stop the run, so the caller can look at them
+    /// with [`Lexer::peek`].
+    fn run_end(&self, wanted: impl Fn(u8) -> bool) -> usize {
+        let bytes = self.source.as_bytes();
+        let start = self.location.offset;
+        bytes[start..]
+            .iter()
+            .position(|&byte| class(byte) & SPECIAL != 0 || !wanted(byte))
+            .map_or(bytes.len(), |length| start + length)
+    }
+
+    /// Moves past any backslash-newlines, so the location is that of the
+    /// next character.
+    fn skip_splices(&mut self) {
+        let offset = self.location.offset;
+        let length = splice_length(self.source.as_bytes(), offset, self.trigraphs);
+        self.advance_to(offset + length);
+    }
+
+    /// Consumes the next character after phases 1 and 2.
+    fn bump(&mut self) -> Option<char> {
+        let (character, end) = self.character_at(self.location.offset)?;
+        self.advance_to(end);
+        Some(character)
+    }
+
+    /// Moves the location to byte `end` of the source as written, counting
+    /// lines and columns on the way.
+    fn advance_to(&mut self, end: usize) {
+        let location = &mut self.location;
+        for &byte in &self.source.as_bytes()[location.offset..end] {
+            match byte {
+                b'\n' => {
+                    location.line += 1;
+                    location.column = 1;
+                    location.utf16_column = location.utf16_column.map(|_| 1);
+                    continue;
+                }
+                b'\t' => location.column += self.tab_width - (location.column - 1) % self.tab_width,
+                // A continuation byte belongs to the character before it.
+                0x80..=0xbf => continue,
+                _ => location.column += 1,
+            }
+            // Only characters written with four bytes take two UTF-16 units.
+            let units = if byte >= 0xf0 { 2 } else { 1 };
+            location.utf16_column = location.utf16_column.map(|column| column + units);
+        }
+        location.offset = end;
+    }
+
+    fn bump_many(&mut self, count: usize) {
+        for _ in 0..count {
+            self.bump();
+        }
+    }
+
+    /// The text consumed since `starting_index`, after phases 1 and 2. It is
+    /// borrowed from the source unless there were splices to remove.
+    fn text_from(&self, starting_index: usize) -> Cow<'src, str> {
+        let written = &self.source[starting_index..self.location.offset];
+        splice_lines(written, self.trigraphs)
+    }
+
+    fn token_from(&self, start: Location, token_type: TokenType<'src>) -> Token<'src> {
+        Token {
+            span: Span {
+                start,
+                end: self.location,
+            },
+            token_type,
+            digraph: None,
+            symbol: None,
+        }
+    }
+
+    /// Queues an `Error` token for everything consumed since
+    /// `starting_index` and reports `error`.
+    fn recover(
+        &mut self,
+        error: LexError,
+        starting_index: usize,
+        start: Location,
+    ) -> Result<Token<'src>, LexError> {
+        let text = self.text_from(starting_index);
+        let token = self.token_from(start, TokenType::Error(text));
+        self.pending.push_back(Ok(token));
+        Err(error)
+    }
+
+    /// Passes `token` through, unless it uses a `feature` introduced after
+    /// the selected standard and warnings were asked for; then the warning
+    /// comes first and the token after it.
+    fn check_feature(
+        &mut self,
+        token: Token<'src>,
+        feature: Option<(&'static str, Standard)>,
+    ) -> Result<Token<'src>, LexError> {
+        match feature {
+            Some((feature, introduced))
+                if self.warn_newer_features && introduced > self.standard =>
+            {
+                let warning = LexError::NewerFeature {
+                    feature,
+                    introduced,
+                    selected: self.standard,
+                    location: token.span.start,
+                };
+                self.pending.push_back(Ok(token));
+                Err(warning)
+            }
+            _ => Ok(token),
+        }
+    }
+
+    /// Skips whitespace up to the end of the line. Lines continued with a
+    /// backslash count as one.
+    fn skip_line_whitespace(&mut self) {
+        let end = self.run_end(|byte| class(byte) & WHITESPACE != 0 && byte != b'\n');
+        self.advance_to(end);
+        while self
+            .peek(0)
+            .is_some_and(|character| character.is_whitespace() && character != '\n')
+        {
+            self.bump();
+        }
+        self.skip_splices();
+    }
+
+    /// Whether the input continues with `#` or its digraph `%:`, and how
+    /// long that is.
+    fn hash_length(&self) -> Option<usize> {
+        let (index, length) = self.punctuator()?;
+        let (_, punctuator, _) = PUNCTUATORS[index];
+        (punctuator == Punctuator::Delimiter(Delimiter::Hash)).then_some(length)
+    }
+
+    /// The longest punctuator the input starts with, as its index in
+    /// [`PUNCTUATORS`] and its length. Spellings of extensions that are not
+    /// enabled, or of punctuators newer than the selected standard, are
+    /// passed over.
+    fn punctuator(&self) -> Option<(usize, usize)> {
+        let states = punctuators();
+        let mut state = 0;
+        let mut index = self.location.offset;
+        let mut length = 0;
+        let mut longest = None;
+        while let Some((character, next)) = self.character_at(index) {
+            let target = states[state].next.get(character as usize).copied();
+            let Some(target @ 1..) = target else {
+                break;
+            };
+            state = usize::from(target);
+            index = next;
+            length += 1;
+            if let Some(punctuator) = states[state].punctuator {
+                let (_, kind, extension) = PUNCTUATORS[usize::from(punctuator)];
+                if kind.standard() <= self.standard
+                    && extension.is_none_or(|extension| self.extensions.contains(&extension))
+                {
+                    longest = Some((usize::from(punctuator), length));
+                }
+            }
+        }
+        longest
+    }
+
+    /// Lexes a directive line starting at `#`, grouping its tokens with it.
+    /// Errors inside the line are queued ahead of the directive. If no
+    /// directive name follows the `#`, nothing is consumed.
+    fn directive(&mut self) -> Option<Token<'src>> {
+        let start = self.location;
+        self.bump_many(self.hash_length().unwrap_or(1));
+        self.skip_line_whitespace();
+        let name_index = self.location.offset;
+        while self
+            .peek(0)
+            .is_some_and(|character| character.is_ascii_alphanumeric() || character == '_')
+        {
+            self.bump();
+        }
+        let Some(directive) = directives().get(&self.text_from(name_index)).copied() else {
+            self.location = start;
+            return None;
+        };
+        if directive.standard() > self.standard {
+            if self.warn_newer_features {
+                self.pending.push_back(Err(LexError::NewerFeature {
+                    feature: "#elifdef and #elifndef",
+                    introduced: directive.standard(),
+                    selected: self.standard,
+                    location: start,
+                }));
+            }
+            self.location = start;
+            return None;
+        }
+
+        let mut end = self.location;
+        let mut tokens = vec![];
+        let mut errors = vec![];
+        loop {
+            self.skip_line_whitespace();
+            if matches!(self.peek(0), None | Some('\n')) {
+                break;
+            }
+            let expects_header_name = match directive {
+                Directive::Include | Directive::Embed => tokens.is_empty(),
+                Directive::If | Directive::Elif => follows_has_include(&tokens),
+                _ => false,
+            };
+            let result = if expects_header_name {
+                self.header_name().map(Ok).or_else(|| self.scan())
+            } else {
+                self.scan()
+            };
+            match result {
+                Some(Ok(token)) => tokens.push(token),
+                Some(Err(error)) => errors.push(error),
+                None => break,
+            }
+            while let Some(result) = self.pending.pop_front() {
+                match result {
+                    Ok(token) => tokens.push(token),
+                    Err(error) => errors.push(error),
+                }
+            }
+            end = self.location;
+        }
+
+        self.pending.extend(errors.into_iter().map(Err));
+        Some(Token {
+            span: Span { start, end },
+            token_type: TokenType::Directive(directive, tokens),
+            digraph: None,
+            symbol: None,
+        })
+    }
+
+    /// Lexes `<name>` or `"name"` if it is closed on the same line. Unlike a
+    /// string literal, backslashes in it are not escapes.
+    fn header_name(&mut self) -> Option<Token<'src>> {
+        let closing = match self.peek(0)? {
+            '<' => '>',
+            '"' => '"',
+            _ => return None,
+        };
+        let length = (1..)
+            .map_while(|offset| self.peek(offset).filter(|character| *character != '\n'))
+            .position(|character| character == closing)?;
+        let start = self.location;
+        self.bump();
+        let starting_index = self.location.offset;
+        self.bump_many(length);
+        let name = self.text_from(starting_index);
+        self.bump();
+        let header_name = HeaderName {
+            name,
+            angled: closing == '>',
+        };
+        Some(self.token_from(start, TokenType::HeaderName(header_name)))
+    }
+
+    fn line_comment(&mut self) -> Token<'src> {
+        let start = self.location;
+        self.bump_many(2);
+        let starting_index = self.location.offset;
+        loop {
+            let end = self.run_end(|byte| byte != b'\n');
+            self.advance_to(end);
+            if matches!(self.peek(0), Some('\n') | None) {
+                break;
+            }
+            self.bump();
+        }
+        self.token_from(start, TokenType::Comment(self.text_from(starting_index)))
+    }
+
+    fn block_comment(&mut self) -> Result<Token<'src>, LexError> {
+        let opening_index = self.location.offset;
+        let start = self.location;
+        self.bump_many(2);
+        let starting_index = self.location.offset;
+        loop {
+            let end = self.run_end(|byte| byte != b'*');
+            self.advance_to(end);
+            match (self.peek(0), self.peek(1)) {
+                (Some('*'), Some('/')) => break,
+                (None, _) => {
+                    let error = LexError::UnterminatedBlockComment { location: start };
+                    return self.recover(error, opening_index, start);
+                }
+                _ => {
+                    self.bump();
+                }
+            }
+        }
+        let comment = self.text_from(starting_index);
+        self.bump_many(2);
+        Ok(self.token_from(start, TokenType::Comment(comment)))
+    }
+
+    /// Consumes an encoding prefix `prefix_length` characters long, which
+    /// the caller has already recognised.
+    fn encoding(&mut self, prefix_length: usize) -> Encoding {
+        let prefix = (0..prefix_length)
+            .filter_map(|offset| self.peek(offset))
+            .collect::<String>();
+        self.bump_many(prefix_length);
+        Encoding::from_prefix(&prefix).unwrap_or(Encoding::Plain)
+    }
+
+    /// Advances to the closing `quote`, or to the end of the line if there
+    /// is none, skipping over escaped characters.
+    fn skip_quoted(&mut self, quote: char) {
+        loop {
+            let end = self.run_end(|byte| byte != b'\n' && char::from(byte) != quote);
+            self.advance_to(end);
+            match (self.peek(0), self.peek(1)) {
+                (Some('\n') | None, _) => break,
+                (Some(character), _) if character == quote => break,
+                (Some('\\'), Some(next)) if next != '\n' => self.bump_many(2),
+                _ => {
+                    self.bump();
+                }
+            }
+        }
+    }
+
+    /// Scans a string literal whose encoding prefix, if any, is
+    /// `prefix_length` characters long.
+    fn literal(&mut self, prefix_length: usize) -> Result<Token<'src>, LexError> {
+        let opening_index = self.location.offset;
+        let start = self.location;
+        let encoding = self.encoding(prefix_length);
+        self.bump();
+        let starting_index = self.location.offset;
+        self.skip_quoted('"');
+        // A string literal cannot span lines, so the end of the line is where
+        // scanning resumes.
+        if self.peek(0) != Some('"') {
+            let error = LexError::UnterminatedString { location: start };
+            return self.recover(error, opening_index, start);
+        }
+        let literal = StringLiteral::new(encoding, self.text_from(starting_index));
+        self.bump();
+        match literal.validate() {
+            Ok(()) => Ok(self.token_from(start, TokenType::Literal(literal))),
+            Err(reason) => {
+                let error = LexError::MalformedStringLiteral {
+                    reason,
+                    location: start,
+                };
+                self.recover(error, opening_index, start)
+            }
+        }
+    }
+
+    /// Scans a character constant whose encoding prefix, if any, is
+    /// `prefix_length` characters long.
+    fn character(&mut self, prefix_length: usize) -> Result<Token<'src>, LexError> {
+        let opening_index = self.location.offset;
+        let start = self.location;
+        let encoding = self.encoding(prefix_length);
+        self.bump();
+        let starting_index = self.location.offset;
+        self.skip_quoted('\'');
+        let spelling = self.text_from(starting_index);
+        let result = if self.peek(0) == Some('\'') {
+            self.bump();
+            match spelling {
+                Cow::Borrowed(spelling) => CharacterConstant::parse(encoding, spelling),
+                Cow::Owned(spelling) => {
+                    CharacterConstant::parse(encoding, &spelling).map(CharacterConstant::into_owned)
+                }
+            }
+        } else {
+            Err(LiteralError::Unterminated)
+        };
+        match result {
+            Ok(constant) => Ok(self.token_from(start, TokenType::Character(constant))),
+            Err(reason) => {
+                let error = LexError::MalformedCharacterLiteral {
+                    reason,
+                    location: start,
+                };
+                self.recover(error, opening_index, start)
+            }
+        }
+    }
+
+    /// The length of the encoding prefix if the input continues with one
+    /// followed by `quote`. A prefix newer than the selected standard is
+    /// left to lex as an identifier.
+    fn encoding_prefix(&self, quote: char) -> Option<usize> {
+        let first = self
+            .peek(0)
+            .filter(|first| matches!(first, 'L' | 'u' | 'U'))?;
+        let (prefix, length) = match (first, self.peek(1)?, self.peek(2)) {
+            ('u', '8', Some(next)) if next == quote => ("u8", 2),
+            ('L', next, _) if next == quote => ("L", 1),
+            ('u', next, _) if next == quote => ("u", 1),
+            ('U', next, _) if next == quote => ("U", 1),
+            _ => return None,
+        };
+        prefix_feature(prefix, quote)
+            .is_none_or(|(_, introduced)| introduced <= self.standard)
+            .then_some(length)
+    }
+
+    /// Scans a preprocessing number, which is deliberately greedy (`0x1e+1`
+    /// is one token), and then checks that it is a valid numeric constant.
+    /// Digit separators only belong to one from C23 on; before, `1'2'` is a
+    /// number followed by a character constant.
+    fn number(&mut self) -> Result<Token<'src>, LexError> {
+        let starting_index = self.location.offset;
+        let start = self.location;
+        let separators = self.standard >= Standard::C23;
+        self.bump();
+        loop {
+            self.skip_plain_number(separators);
+            match (self.peek(0), self.peek(1)) {
+                (Some('e' | 'E' | 'p' | 'P'), Some('+' | '-')) => self.bump_many(2),
+                (Some('\''), Some(next))
+                    if separators && (next.is_alphanumeric() || next == '_') =>
+                {
+                    self.bump_many(2)
+                }
+                (Some(character), _) if character.is_alphanumeric() || "_.".contains(character) => {
+                    self.bump();
+                }
+                _ => break,
+            }
+        }
+        let spelling = self.text_from(starting_index);
+        let result = match &spelling {
+            Cow::Borrowed(spelling) => Number::parse(spelling),
+            Cow::Owned(spelling) => Number::parse(spelling).map(Number::into_owned),
+        };
+        match result {
+            Ok(number) => {
+                let feature = number.feature();
+                let token = self.token_from(start, TokenType::Number(number));
+                self.check_feature(token, feature)
+            }
+            Err(reason) => {
+                let error = LexError::InvalidNumber {
+                    number: spelling.into_owned(),
+                    reason,
+                    location: start,
+                };
+                self.recover(error, starting_index, start)
+            }
+        }
+    }
+
+    /// Moves over the part of a preprocessing number ahead that can be read
+    /// byte by byte, stopping before anything that needs a closer look.
+    fn skip_plain_number(&mut self, separators: bool) {
+        let bytes = self.source.as_bytes();
+        let mut end = self.location.offset;
+        while let Some(&byte) = bytes.get(end) {
+            let next = bytes.get(end + 1).copied().unwrap_or(0);
+            end += match byte {
+                b'e' | b'E' | b'p' | b'P' if matches!(next, b'+' | b'-') => 2,
+                b'\'' if separators && class(next) & WORD != 0 => 2,
+                _ if class(byte) & WORD != 0 || byte == b'.' => {
+                    // An exponent sign or separator written across a line
+                    // splice is left to the slow path.
+                    if matches!(byte, b'e' | b'E' | b'p' | b'P') && class(next) & SPECIAL != 0 {
+                        break;
+                    }
+                    1
+                }
+                _ => break,
+            };
+        }
+        self.advance_to(end);
+    }
+
+    /// The keyword `word` is under the selected standard and extensions, if
+    /// any, and the feature to warn about if it is one only in a newer
+    /// standard.
+    fn keyword(&self, word: &str) -> (Option<Keyword>, Option<(&'static str, Standard)>) {
+        match keywords().get_key_value(word) {
+            Some((_, keyword)) if keyword.standard() <= self.standard => (Some(*keyword), None),
+            // Spellings like `_Bool` are reserved in every standard, so
+            // using one before it became a keyword is worth a warning.
+            Some((spelling, keyword)) if spelling.starts_with('_') => {
+                (None, Some((spelling, keyword.standard())))
+            }
+            Some(_) | None => (self.extension_keyword(word), None),
+        }
+    }
+
+    /// Where the word ahead ends if it is all ASCII letters, digits and
+    /// underscores, or `None` if it goes on with something to decode.
+    fn plain_word_end(&self) -> Option<usize> {
+        let end = self.run_end(|byte| class(byte) & WORD != 0);
+        let bytes = self.source.as_bytes();
+        let continues = match bytes.get(end) {
+            Some(b'\\') => true,
+            Some(b'?') => self.trigraphs && bytes.get(end + 1) == Some(&b'?'),
+            Some(b'$') => !self.extensions.is_empty(),
+            Some(&byte) => !byte.is_ascii(),
+            None => false,
+        };
+        (!continues).then_some(end)
+    }
+
+    fn extension_keyword(&self, word: &str) -> Option<Keyword> {
+        self.extensions.iter().find_map(|extension| {
+            let keywords = match extension {
+                Extension::Gnu => gnu_keywords(),
+                Extension::Msvc => msvc_keywords(),
+            };
+            keywords.get(word).copied()
+        })
+    }
+
+    /// Whether the character `offset` characters ahead belongs to a word,
+    /// which is an identifier or keyword or was meant to be one. Characters
+    /// outside ASCII all count, so that one not allowed in identifiers is
+    /// reported as such rather than as unknown.
+    fn is_word_character(&self, offset: usize) -> bool {
+        match self.peek(offset) {
+            Some('\\') => matches!(self.peek(offset + 1), Some('u' | 'U')),
+            Some('$') => !self.extensions.is_empty(),
+            Some(character) if character.is_ascii() => {
+                character.is_ascii_alphanumeric() || character == '_'
+            }
+            Some(character) => !character.is_whitespace(),
+            None => false,
+        }
+    }
+
+    /// Reads a word, decoding universal character names, and checks that
+    /// each character may appear where it is in an identifier. After an
+    /// error the rest of the word is still consumed.
+    fn word(&mut self) -> Result<String, LexError> {
+        let mut word = String::new();
+        let mut error = None;
+        while self.is_word_character(0) {
+            self.skip_splices();
+            let location = self.location;
+            let Some(mut character) = self.bump() else {
+                break;
+            };
+            if character == '\\' {
+                let escape = self.bump().unwrap_or('u');
+                let length = if escape == 'u' { 4 } else { 8 };
+                let mut digits = String::new();
+                while digits.len() < length
+                    && self.peek(0).is_some_and(|digit| digit.is_ascii_hexdigit())
+                {
+                    digits.extend(self.bump());
+                }
+                match universal_character_name(escape, &mut digits.chars().peekable()) {
+                    Ok(named) => character = named,
+                    Err(_) => {
+                        error.get_or_insert(LexError::InvalidUniversalCharacterName {
+                            name: format!("\\{}{}", escape, digits),
+                            location,
+                        });
+                        continue;
+                    }
+                }
+            }
+            let allowed = (character == '$' && !self.extensions.is_empty())
+                || if word.is_empty() {
+                    is_identifier_start(character)
+                } else {
+                    is_identifier_continue(character)
+                };
+            if !allowed {
+                error.get_or_insert(LexError::DisallowedIdentifierCharacter {
+                    character,
+                    first: word.is_empty(),
+                    location,
+                });
+            }
+            word.push(character);
+        }
+        match error {
+            Some(error) => Err(error),
+            None => Ok(word),
+        }
+    }
+}
+
+impl<'src> Iterator for Lexer<'src> {
+    type Item = Result<Token<'src>, LexError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(result) = self.pending.pop_front() {
+            return Some(result);
+        }
+
+        loop {
+            let end = self.run_end(|byte| class(byte) & WHITESPACE != 0);
+            let skipped = &self.source.as_bytes()[self.location.offset..end];
+            self.at_line_start |= skipped.contains(&b'\n');
+            self.advance_to(end);
+            // Line splices and whitespace outside ASCII.
+            match self.peek(0) {
+                Some(character) if character.is_whitespace() => {
+                    self.at_line_start |= character == '\n';
+                    self.bump();
+                }
+                _ => break,
+            }
+        }
+        if self.at_line_start && self.hash_length().is_some() {
+            if let Some(token) = self.directive() {
+                self.at_line_start = false;
+                self.pending.push_back(Ok(token));
+                return self.pending.pop_front();
+            }
+            if !self.pending.is_empty() {
+                // The warning for a newer directive goes before its `#`.
+                self.at_line_start = false;
+                let hash = self.scan()?;
+                self.pending.push_back(hash);
+                return self.pending.pop_front();
+            }
+        }
+        // Comments count as whitespace, so a directive may follow one, and
+        // at the end of the source the line may go on in more text.
+        let comment = self.peek(0) == Some('/') && matches!(self.peek(1), Some('/' | '*'));
+        if self.peek(0).is_some() && !comment {
+            self.at_line_start = false;
+        }
+        self.scan()
+    }
+}
+
+impl<'src> Lexer<'src> {
+    /// Lexes the token at the current position, which is not whitespace.
+    fn scan(&mut self) -> Option<Result<Token<'src>, LexError>> {
+        self.skip_splices();
+        let character = self.peek(0)?;
+        let next = self.peek(1);
+
+        if character == '/' && next == Some('/') {
+            let token = self.line_comment();
+            return Some(self.check_feature(token, Some(("// comments", Standard::C99))));
+        } else if character == '/' && next == Some('*') {
+            return Some(self.block_comment());
+        } else if character == '"' {
+            return Some(self.literal(0));
+        } else if character == '\'' {
+            return Some(self.character(0));
+        } else if let Some(prefix_length) = self.encoding_prefix('"') {
+            return Some(self.literal(prefix_length));
+        } else if let Some(prefix_length) = self.encoding_prefix('\'') {
+            return Some(self.character(prefix_length));
+        }
+
+        if character.is_ascii_digit()
+            || (character == '.' && next.is_some_and(|next| next.is_ascii_digit()))
+        {
+            return Some(self.number());
+        }
+
+        if let Some((index, length)) = self.punctuator() {
+            let (spelling, punctuator, _) = PUNCTUATORS[index];
+            let start = self.location;
+            self.bump_many(length);
+            let token_type = match punctuator {
+                Punctuator::Operator(operator) => TokenType::Operator(operator),
+                Punctuator::Delimiter(delimiter) => TokenType::Delimiter(delimiter),
+            };
+            let mut token = self.token_from(start, token_type);
+            if spelling != punctuator.spelling() {
+                token.digraph = Some(spelling);
+                return Some(self.check_feature(token, Some(("digraphs", Standard::C95))));
+            }
+            return Some(Ok(token));
+        }
+
+        let (starting_index, start) = (self.location.offset, self.location);
+        if !self.is_word_character(0) {
+            self.bump();
+            let error = LexError::UnknownCharacter {
+                character,
+                location: start,
+            };
+            return Some(self.recover(error, starting_index, start));
+        }
+        // Most words are plain ASCII and are looked up where they are
+        // written; the rest are decoded as they are read.
+        let plain_end = self.plain_word_end();
+        let word = match plain_end {
+            Some(end) => Cow::Borrowed(&self.source[starting_index..end]),
+            None => match self.word() {
+                Ok(word) => Cow::Owned(word),
+                Err(error) => return Some(self.recover(error, starting_index, start)),
+            },
+        };
+        let (keyword, mut feature) = self.keyword(&word);
+        if let Some(end) = plain_end {
+            // A prefix the standard does not have yet, as in `u8"..."`.
+            feature = feature.or_else(|| match self.source.as_bytes().get(end) {
+                Some(b'"') => prefix_feature(&word, '"'),
+                Some(b'\'') => prefix_feature(&word, '\''),
+                _ => None,
+            });
+        }
+        let symbol = self
+            .interner
+            .as_mut()
+            .map(|interner| interner.intern(&word));
+        let token_type = match keyword {
+            Some(keyword) => TokenType::Keyword(keyword),
+            None => TokenType::Identifier(word),
+        };
+        if let Some(end) = plain_end {
+            self.advance_to(end);
+        }
+
+        let mut token = self.token_from(start, token_type);
+        token.symbol = symbol;
+        Some(self.check_feature(token, feature))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{symbol::Interner, tokens::Operator};
+
+    /// The punctuator `lexer` finds if its source is exactly one, with how
+    /// the token displays and the digraph it was written as.
+    fn lex_punctuator(lexer: Lexer) -> Option<(Punctuator, String, Option<&'static str>)> {
+        let tokens: Vec<Token> = lexer.collect::<Result<_, _>>().ok()?;
+        let [token] = tokens.as_slice() else {
+            return None;
+        };
+        match &token.token_type {
+            TokenType::Operator(operator) => Some((
+                Punctuator::Operator(*operator),
+                operator.to_string(),
+                token.digraph,
+            )),
+            TokenType::Delimiter(delimiter) => Some((
+                Punctuator::Delimiter(*delimiter),
+                delimiter.to_string(),
+                token.digraph,
+            )),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn every_punctuator_spelling_lexes_to_its_entry() {
+        for (spelling, punctuator, extension) in PUNCTUATORS {
+            let mut lexer = Lexer::new(spelling).with_standard(Standard::C23);
+            if let Some(extension) = extension {
+                lexer = lexer.with_extension(*extension);
+            }
+            let Some((lexed, displayed, digraph)) = lex_punctuator(lexer) else {
+                panic!("{} does not lex as one punctuator", spelling);
+            };
+            let standard_spelling = PUNCTUATORS
+                .iter()
+                .find(|(_, other, _)| other == punctuator)
+                .map(|(spelling, _, _)| *spelling);
+            assert_eq!(lexed, *punctuator, "{}", spelling);
+            assert_eq!(Some(displayed.as_str()), standard_spelling, "{}", spelling);
+            let is_digraph = standard_spelling != Some(*spelling);
+            assert_eq!(digraph, is_digraph.then_some(*spelling), "{}", spelling);
+        }
+    }
+
+    #[test]
+    fn punctuators_not_enabled_lex_as_shorter_ones() {
+        assert!(lex_punctuator(Lexer::new("?:")).is_none());
+        assert!(lex_punctuator(Lexer::new("::").with_standard(Standard::C17)).is_none());
+        let colons: Vec<TokenType> = Lexer::new("::")
+            .with_standard(Standard::C17)
+            .map(|result| result.map(|token| token.token_type))
+            .collect::<Result<_, _>>()
+            .unwrap_or_default();
+        assert!(matches!(
+            colons.as_slice(),
+            [
+                TokenType::Delimiter(Delimiter::Colon),
+                TokenType::Delimiter(Delimiter::Colon)
+            ]
+        ));
+    }
+
+    fn token_types(lexer: Lexer) -> Vec<Result<TokenType, LexError>> {
+        lexer
+            .map(|result| result.map(|token| token.token_type))
+            .collect()
+    }
+
+    #[test]
+    fn encoding_prefixes_before_their_standard_are_identifiers() {
+        let tokens = token_types(Lexer::new("u8\"x\"").with_standard(Standard::C99));
+        assert!(matches!(
+            tokens.as_slice(),
+            [Ok(TokenType::Identifier(name)), Ok(TokenType::Literal(literal))]
+                if name == "u8" && literal.encoding == Encoding::Plain
+        ));
+        let tokens = token_types(Lexer::new("u8\"x\"").with_standard(Standard::C11));
+        assert!(matches!(
+            tokens.as_slice(),
+            [Ok(TokenType::Literal(literal))] if literal.encoding == Encoding::Utf8
+        ));
+        let tokens = token_types(Lexer::new("u8'x'").with_standard(Standard::C17));
+        assert!(matches!(
+            tokens.as_slice(),
+            [Ok(TokenType::Identifier(_)), Ok(TokenType::Character(_))]
+        ));
+        let tokens = token_types(Lexer::new("L'x' U\"x\"").with_standard(Standard::C89));
+        assert!(matches!(
+            tokens.as_slice(),
+            [
+                Ok(TokenType::Character(_)),
+                Ok(TokenType::Identifier(_)),
+                Ok(TokenType::Literal(_))
+            ]
+        ));
+
+        let tokens = token_types(
+            Lexer::new("u\"x\"")
+                .with_standard(Standard::C99)
+                .warning_on_newer_features(),
+        );
+        assert!(matches!(
+            tokens.as_slice(),
+            [
+                Err(LexError::NewerFeature {
+                    introduced: Standard::C11,
+                    ..
+                }),
+                Ok(TokenType::Identifier(_)),
+                Ok(TokenType::Literal(_))
+            ]
+        ));
+    }
+
+    #[test]
+    fn digraphs_date_from_the_first_amendment() {
+        let tokens = token_types(
+            Lexer::new("<:")
+                .with_standard(Standard::C89)
+                .warning_on_newer_features(),
+        );
+        assert!(matches!(
+            tokens.as_slice(),
+            [
+                Err(LexError::NewerFeature {
+                    introduced: Standard::C95,
+                    ..
+                }),
+                Ok(TokenType::Delimiter(Delimiter::OpeningBracket))
+            ]
+        ));
+        let tokens = token_types(
+            Lexer::new("<:")
+                .with_standard(Standard::C95)
+                .warning_on_newer_features(),
+        );
+        assert!(matches!(
+            tokens.as_slice(),
+            [Ok(TokenType::Delimiter(Delimiter::OpeningBracket))]
+        ));
+    }
+
+    #[test]
+    fn elifdef_is_unknown_before_c23() {
+        let tokens = token_types(Lexer::new("#elifdef X\n").with_standard(Standard::C23));
+        assert!(matches!(
+            tokens.as_slice(),
+            [Ok(TokenType::Directive(Directive::Elifdef, _))]
+        ));
+        let tokens = token_types(
+            Lexer::new("#elifndef X\n")
+                .with_standard(Standard::C17)
+                .warning_on_newer_features(),
+        );
+        assert!(matches!(
+            tokens.as_slice(),
+            [
+                Err(LexError::NewerFeature { introduced: Standard::C23, .. }),
+                Ok(TokenType::Delimiter(Delimiter::Hash)),
+                Ok(TokenType::Identifier(name)),
+                Ok(TokenType::Identifier(_))
+            ] if name == "elifndef"
+        ));
+        let tokens = token_types(Lexer::new("#ifdef X\n").with_standard(Standard::C89));
+        assert!(matches!(
+            tokens.as_slice(),
+            [Ok(TokenType::Directive(Directive::Ifdef, _))]
+        ));
+    }
+
+    /// Each token `lexer` yields as its kind and the source it spans, with
+    /// `error` for each error.
+    fn spelled(lexer: Lexer) -> Vec<String> {
+        let source = lexer.source;
+        lexer
+            .map(|result| match result {
+                Ok(token) => format!(
+                    "{} {}",
+                    token.token_type,
+                    &source[token.span.start.offset..token.span.end.offset]
+                ),
+                Err(_) => "error".to_string(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn input_cut_short_is_lexed_to_its_end() {
+        let cases: [(&str, &[&str]); 9] = [
+            ("", &[]),
+            ("+", &["Operator +"]),
+            ("a/", &["Identifier a", "Operator /"]),
+            ("a??", &["Identifier a", "Operator ?", "Operator ?"]),
+            ("'a", &["error", "Error 'a"]),
+            ("'\\", &["error", "Error '\\"]),
+            ("\"a", &["error", "Error \"a"]),
+            ("x /* a", &["Identifier x", "error", "Error /* a"]),
+            ("#include <a", &["Directive #include <a"]),
+        ];
+        for (source, expected) in cases {
+            assert_eq!(spelled(Lexer::new(source)), expected, "{:?}", source);
+        }
+    }
+
+    #[test]
+    fn lexing_resumes_after_bad_input() {
+        let source = "int a = `1; char *s = \"oops\nint b = 'x;\n0x1g c\n";
+        assert_eq!(
+            spelled(Lexer::new(source)),
+            [
+                "Keyword int",
+                "Identifier a",
+                "Operator =",
+                "error",
+                "Error `",
+                "Number 1",
+                "Delimiter ;",
+                "Keyword char",
+                "Operator *",
+                "Identifier s",
+                "Operator =",
+                "error",
+                "Error \"oops",
+                "Keyword int",
+                "Identifier b",
+                "Operator =",
+                "error",
+                "Error 'x;",
+                "error",
+                "Error 0x1g",
+                "Identifier c",
+            ]
+        );
+        let errors: Vec<LexError> = Lexer::new(source).filter_map(Result::err).collect();
+        assert!(matches!(
+            errors.as_slice(),
+            [
+                LexError::UnknownCharacter { character: '`', .. },
+                LexError::UnterminatedString { .. },
+                LexError::MalformedCharacterLiteral {
+                    reason: LiteralError::Unterminated,
+                    ..
+                },
+                LexError::InvalidNumber { .. }
+            ]
+        ));
+    }
+
+    /// A location with no UTF-16 column.
+    fn at(offset: usize, line: usize, column: usize) -> Location {
+        Location {
+            offset,
+            line,
+            column,
+            utf16_column: None,
+        }
+    }
+
+    fn spans(lexer: Lexer) -> Vec<Span> {
+        lexer
+            .filter_map(Result::ok)
+            .map(|token| token.span)
+            .collect()
+    }
+
+    #[test]
+    fn spans_run_from_the_start_to_the_end_of_each_token() {
+        let source = "int x;\n/* a\n b */ \"s\"";
+        assert_eq!(
+            spans(Lexer::new(source)),
+            [
+                Span {
+                    start: at(0, 1, 1),
+                    end: at(3, 1, 4)
+                },
+                Span {
+                    start: at(4, 1, 5),
+                    end: at(5, 1, 6)
+                },
+                Span {
+                    start: at(5, 1, 6),
+                    end: at(6, 1, 7)
+                },
+                Span {
+                    start: at(7, 2, 1),
+                    end: at(17, 3, 6)
+                },
+                Span {
+                    start: at(18, 3, 7),
+                    end: at(21, 3, 10)
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn tabs_advance_to_the_next_tab_stop() {
+        let columns = |lexer: Lexer| -> Vec<(usize, usize)> {
+            spans(lexer)
+                .iter()
+                .map(|span| (span.start.column, span.end.column))
+                .collect()
+        };
+        assert_eq!(columns(Lexer::new("a\tb\n\tc")), [(1, 2), (3, 4), (2, 3)]);
+        assert_eq!(
+            columns(Lexer::new("a\tb\n\tc").with_tab_width(4)),
+            [(1, 2), (5, 6), (5, 6)]
+        );
+        assert_eq!(
+            columns(Lexer::new("abc\t\td").with_tab_width(8)),
+            [(1, 4), (17, 18)]
+        );
+    }
+
+    #[test]
+    fn utf16_columns_count_astral_characters_twice() {
+        let columns = |lexer: Lexer| -> Vec<(usize, Option<usize>)> {
+            spans(lexer)
+                .iter()
+                .flat_map(|span| [span.start, span.end])
+                .map(|location| (location.column, location.utf16_column))
+                .collect()
+        };
+        assert_eq!(
+            columns(Lexer::new("\u{1d4b3} = \"\u{1f600}\";").with_utf16_columns()),
+            [
+                (1, Some(1)),
+                (2, Some(3)),
+                (3, Some(4)),
+                (4, Some(5)),
+                (5, Some(6)),
+                (8, Some(10)),
+                (8, Some(10)),
+                (9, Some(11)),
+            ]
+        );
+        assert!(columns(Lexer::new("\u{1d4b3}"))
+            .iter()
+            .all(|(_, utf16_column)| utf16_column.is_none()));
+    }
+
+    #[test]
+    fn spliced_tokens_keep_the_lines_they_were_written_on() {
+        let source = "a\\\nb \"x\\\ny\" //c\\\nd\ne";
+        let tokens: Vec<Token> = Lexer::new(source).collect::<Result<_, _>>().unwrap();
+        let [name, literal, comment, last] = tokens.as_slice() else {
+            panic!("{} tokens", tokens.len());
+        };
+        assert!(matches!(&name.token_type, TokenType::Identifier(name) if name == "ab"));
+        assert!(matches!(
+            &literal.token_type,
+            TokenType::Literal(literal) if literal.piece == "xy"
+        ));
+        assert!(matches!(&comment.token_type, TokenType::Comment(text) if text == "cd"));
+        assert!(matches!(&last.token_type, TokenType::Identifier(name) if name == "e"));
+        let spans: Vec<String> = tokens.iter().map(|token| token.span.to_string()).collect();
+        assert_eq!(spans, ["1:1-2:2", "2:3-3:3", "3:4-4:2", "5:1-5:2"]);
+        assert_eq!(
+            (literal.span.start.offset, literal.span.end.offset),
+            (5, 11)
+        );
+    }
+
+    #[test]
+    fn trigraphs_are_replaced_until_c23() {
+        let source = "??=define X ??/\n1\n??( ??' ??!";
+        let tokens: Vec<Token> = Lexer::new(source).collect::<Result<_, _>>().unwrap();
+        let [directive, bracket, caret, bar] = tokens.as_slice() else {
+            panic!("{} tokens", tokens.len());
+        };
+        assert!(matches!(
+            &directive.token_type,
+            TokenType::Directive(Directive::Define, rest) if rest.len() == 2
+        ));
+        assert!(matches!(
+            bracket.token_type,
+            TokenType::Delimiter(Delimiter::OpeningBracket)
+        ));
+        assert!(matches!(
+            caret.token_type,
+            TokenType::Operator(Operator::ExclusiveOr)
+        ));
+        assert!(matches!(
+            bar.token_type,
+            TokenType::Operator(Operator::BitwiseOr)
+        ));
+        let spans: Vec<String> = tokens.iter().map(|token| token.span.to_string()).collect();
+        assert_eq!(spans, ["1:1-2:2", "3:1-3:4", "3:5-3:8", "3:9-3:12"]);
+        assert_eq!(bracket.digraph, None);
+
+        for lexer in [
+            Lexer::new("??(").with_standard(Standard::C23),
+            Lexer::new("??(").with_extension(Extension::Gnu),
+        ] {
+            assert_eq!(spelled(lexer), ["Operator ?", "Operator ?", "Delimiter ("]);
+        }
+    }
+
+    #[test]
+    fn digraphs_are_their_punctuators_spelled_differently() {
+        let source = "%:define X <:%>\n%:%:";
+        let tokens: Vec<Token> = Lexer::new(source).collect::<Result<_, _>>().unwrap();
+        let [directive, hashes] = tokens.as_slice() else {
+            panic!("{} tokens", tokens.len());
+        };
+        let TokenType::Directive(Directive::Define, rest) = &directive.token_type else {
+            panic!("{} is not #define", directive.token_type);
+        };
+        let digraphs: Vec<_> = rest.iter().map(|token| token.digraph).collect();
+        assert_eq!(digraphs, [None, Some("<:"), Some("%>")]);
+        assert!(matches!(
+            rest[1].token_type,
+            TokenType::Delimiter(Delimiter::OpeningBracket)
+        ));
+        assert_eq!(rest[2].span.to_string(), "1:14-1:16");
+        assert!(matches!(
+            hashes.token_type,
+            TokenType::Delimiter(Delimiter::DoubleHash)
+        ));
+        assert_eq!(hashes.span.to_string(), "2:1-2:5");
+    }
+
+    #[test]
+    fn identifiers_start_with_xid_start_and_continue_with_xid_continue() {
+        assert_eq!(
+            spelled(Lexer::new("größe été x· _1 ·x ¨")),
+            [
+                "Identifier größe",
+                "Identifier été",
+                "Identifier x·",
+                "Identifier _1",
+                "error",
+                "Error ·x",
+                "error",
+                "Error ¨",
+            ]
+        );
+        let errors: Vec<LexError> = Lexer::new("·x ¨").filter_map(Result::err).collect();
+        assert!(matches!(
+            errors.as_slice(),
+            [
+                LexError::DisallowedIdentifierCharacter {
+                    character: '·',
+                    first: true,
+                    ..
+                },
+                LexError::DisallowedIdentifierCharacter {
+                    character: '¨',
+                    first: true,
+                    ..
+                }
+            ]
+        ));
+    }
+
+    #[test]
+    fn universal_character_names_are_decoded() {
+        let mut interner = Interner::default();
+        let tokens: Vec<Token> = Lexer::new("a\\u00E9b aéb \\U0001D4B3 \\u00e\\\n9")
+            .with_interner(&mut interner)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        let names: Vec<&str> = tokens
+            .iter()
+            .filter_map(|token| match &token.token_type {
+                TokenType::Identifier(name) => Some(name.as_ref()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(names, ["aéb", "aéb", "\u{1d4b3}", "é"]);
+        assert_eq!(tokens[0].symbol, tokens[1].symbol);
+        assert_eq!(tokens[0].span.to_string(), "1:1-1:9");
+    }
+
+    #[test]
+    fn universal_character_names_below_a0_are_only_dollar_at_and_backtick() {
+        for name in [
+            "\\u0041",
+            "\\u0000",
+            "\\u009F",
+            "\\uD800",
+            "\\U00110000",
+            "\\u00",
+        ] {
+            let errors: Vec<LexError> = Lexer::new(name).filter_map(Result::err).collect();
+            assert!(
+                matches!(
+                    errors.as_slice(),
+                    [LexError::InvalidUniversalCharacterName { .. }]
+                ),
+                "{}",
+                name
+            );
+        }
+        // These three may be written as names, though only `$` is allowed in
+        // identifiers, and only as an extension.
+        for (name, character) in [("\\u0024", '$'), ("\\u0040", '@'), ("\\u0060", '`')] {
+            let errors: Vec<LexError> = Lexer::new(name).filter_map(Result::err).collect();
+            assert!(
+                matches!(
+                    errors.as_slice(),
+                    [LexError::DisallowedIdentifierCharacter { character: found, .. }]
+                        if *found == character
+                ),
+                "{}",
+                name
+            );
+        }
+        let dollar = token_types(Lexer::new("a\\u0024").with_extension(Extension::Gnu));
+        assert!(matches!(
+            dollar.as_slice(),
+            [Ok(TokenType::Identifier(name))] if name == "a$"
+        ));
+    }
+}
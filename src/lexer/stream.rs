@@ -0,0 +1,338 @@
+use std::{collections::VecDeque, io::BufRead};
+
+use crate::{
+    error::LexError,
+    standard::{Extension, Standard},
+    symbol::Intern,
+    tokens::{Location, Token, TokenType},
+};
+
+use super::Lexer;
+
+/// How much text is read before it is lexed.
+const WINDOW_SIZE: usize = 64 << 10;
+
+/// A [`Lexer`] over text read from `reader` as it is needed, such as
+/// standard input or a generated file too large to hold in memory.
+///
+/// Lines are read into a window, which is lexed once it holds
+/// [`WINDOW_SIZE`] bytes and ends with a line that is not continued by a
+/// backslash. Only block comments run on past such a line end, so a comment
+/// still open at the end of the window is lexed again once more has been
+/// read. Memory therefore stays proportional to the window and the longest
+/// line or comment, whatever the size of the input.
+///
+/// Tokens own their text, since the window they were read from is dropped.
+/// Offsets count bytes from the start of the input. A read error or
+/// invalid UTF-8 is reported after the tokens before it and ends the input.
+pub struct StreamingLexer<'i, R> {
+    reader: R,
+    /// The lexer's settings, over no source of its own.
+    settings: Lexer<'i>,
+    /// Text read but not yet lexed, starting where a token may begin.
+    window: String,
+    /// The offset of the start of the window in the input.
+    base: usize,
+    /// Where the window starts, with the offset counted from there.
+    location: Location,
+    at_line_start: bool,
+    /// The line being read, as bytes until it is known to be UTF-8.
+    line: Vec<u8>,
+    lines_read: usize,
+    ready: VecDeque<Result<Token<'static>, LexError>>,
+    /// Why the input ended early, reported once the text before is lexed.
+    error: Option<LexError>,
+    at_end: bool,
+}
+
+impl<'i, R: BufRead> StreamingLexer<'i, R> {
+    pub fn new(reader: R) -> Self {
+        StreamingLexer {
+            reader,
+            settings: Lexer::new(""),
+            window: String::new(),
+            base: 0,
+            location: Location::START,
+            at_line_start: true,
+            line: vec![],
+            lines_read: 0,
+            ready: VecDeque::new(),
+            error: None,
+            at_end: false,
+        }
+    }
+
+    /// See [`Lexer::with_standard`].
+    pub fn with_standard(mut self, standard: Standard) -> Self {
+        self.settings = self.settings.with_standard(standard);
+        self
+    }
+
+    /// See [`Lexer::warning_on_newer_features`].
+    pub fn warning_on_newer_features(mut self) -> Self {
+        self.settings = self.settings.warning_on_newer_features();
+        self
+    }
+
+    /// See [`Lexer::with_extension`].
+    pub fn with_extension(mut self, extension: Extension) -> Self {
+        self.settings = self.settings.with_extension(extension);
+        self
+    }
+
+    /// See [`Lexer::with_tab_width`].
+    pub fn with_tab_width(mut self, tab_width: usize) -> Self {
+        self.settings = self.settings.with_tab_width(tab_width);
+        self
+    }
+
+    /// See [`Lexer::with_utf16_columns`].
+    pub fn with_utf16_columns(mut self) -> Self {
+        self.settings = self.settings.with_utf16_columns();
+        self.location.utf16_column = Some(1);
+        self
+    }
+
+    /// See [`Lexer::with_interner`].
+    pub fn with_interner(mut self, interner: impl Intern + 'i) -> Self {
+        self.settings = self.settings.with_interner(interner);
+        self
+    }
+
+    /// Reads lines into the window until it holds at least `size` bytes
+    /// and its last line is not continued, or the input ends.
+    fn fill(&mut self, size: usize) {
+        while !self.at_end && (self.window.len() < size || self.is_continued()) {
+            self.line.clear();
+            match self.reader.read_until(b'\n', &mut self.line) {
+                Ok(0) => self.at_end = true,
+                Ok(_) => match std::str::from_utf8(&self.line) {
+                    Ok(line) => {
+                        self.window.push_str(line);
+                        self.lines_read += 1;
+                    }
+                    Err(error) => {
+                        let valid = &self.line[..error.valid_up_to()];
+                        // The bytes before the error are known to be UTF-8,
+                        // so this never falls back.
+                        let valid = std::str::from_utf8(valid).unwrap_or_default();
+                        self.error = Some(LexError::InvalidEncoding {
+                            location: Location {
+                                offset: self.base + self.window.len() + valid.len(),
+                                line: self.lines_read + 1,
+                                column: valid.chars().count() + 1,
+                                utf16_column: None,
+                            },
+                        });
+                        self.window.push_str(valid);
+                        self.at_end = true;
+                    }
+                },
+                Err(error) => {
+                    self.error = Some(LexError::Io(error));
+                    self.at_end = true;
+                }
+            }
+        }
+    }
+
+    /// Whether the window ends with a backslash-newline, which joins the
+    /// next line onto it.
+    fn is_continued(&self) -> bool {
+        let Some(line) = self.window.as_bytes().strip_suffix(b"\n") else {
+            return false;
+        };
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        line.ends_with(b"\\") || (self.settings.trigraphs && line.ends_with(b"??/"))
+    }
+
+    /// Lexes the window, moving the tokens to `ready` and dropping their
+    /// text. A block comment left open at the end of the window stays for
+    /// the next time, unless the input has ended.
+    fn lex_window(&mut self) {
+        let settings = &mut self.settings;
+        let mut lexer = Lexer {
+            source: &self.window,
+            location: self.location,
+            tab_width: settings.tab_width,
+            standard: settings.standard,
+            warn_newer_features: settings.warn_newer_features,
+            extensions: settings.extensions.clone(),
+            at_line_start: self.at_line_start,
+            trigraphs: settings.trigraphs,
+            interner: settings
+                .interner
+                .as_mut()
+                .map(|interner| Box::new(&mut **interner) as Box<dyn Intern>),
+            pending: VecDeque::new(),
+        };
+        // The results of the token being lexed, with any errors and
+        // warnings that belong to it.
+        let mut results = vec![];
+        let mut lexed = (0, self.location, self.at_line_start);
+        loop {
+            let Some(result) = lexer.next() else {
+                lexed = (lexer.location.offset, lexer.location, lexer.at_line_start);
+                break;
+            };
+            results.push(result);
+            if !lexer.pending.is_empty() {
+                continue;
+            }
+            // The window ends with a line break, which only an unfinished
+            // block comment takes in.
+            if !self.at_end && lexer.location.offset == lexer.source.len() {
+                break;
+            }
+            let base = self.base;
+            self.ready.extend(
+                results
+                    .drain(..)
+                    .map(|result| shift(result.map(Token::into_owned), base)),
+            );
+            lexed = (lexer.location.offset, lexer.location, lexer.at_line_start);
+        }
+        let (offset, location, at_line_start) = lexed;
+        drop(lexer);
+        self.window.drain(..offset);
+        self.base += offset;
+        self.location = Location {
+            offset: 0,
+            ..location
+        };
+        self.at_line_start = at_line_start;
+    }
+}
+
+impl<R: BufRead> Iterator for StreamingLexer<'_, R> {
+    type Item = Result<Token<'static>, LexError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.ready.is_empty() {
+            if self.at_end && self.window.is_empty() {
+                return self.error.take().map(Err);
+            }
+            // What is left of the window is a block comment that did not
+            // fit, so read twice as much to keep relexing it rare.
+            self.fill(WINDOW_SIZE.max(2 * self.window.len()));
+            self.lex_window();
+        }
+        self.ready.pop_front()
+    }
+}
+
+/// Moves the offsets in `result`, which count from the start of a window,
+/// to count from `base` instead.
+fn shift(
+    mut result: Result<Token<'static>, LexError>,
+    base: usize,
+) -> Result<Token<'static>, LexError> {
+    match &mut result {
+        Ok(token) => shift_token(token, base),
+        Err(error) => {
+            if let Some(location) = error.location_mut() {
+                location.offset += base;
+            }
+        }
+    }
+    result
+}
+
+fn shift_token(token: &mut Token, base: usize) {
+    token.span.start.offset += base;
+    token.span.end.offset += base;
+    if let TokenType::Directive(_, tokens) = &mut token.token_type {
+        for token in tokens {
+            shift_token(token, base);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A token as text, with its span, payload and children.
+    fn describe_token(token: &Token) -> String {
+        let mut text = format!(
+            "{} {:?} {:?}",
+            token.token_type,
+            token.token_type.content(),
+            token.span
+        );
+        if let TokenType::Directive(_, tokens) = &token.token_type {
+            for child in tokens {
+                text.push_str(&format!(" [{}]", describe_token(child)));
+            }
+        }
+        text
+    }
+
+    /// The results after `from` bytes, as text.
+    fn describe<'src>(
+        results: impl Iterator<Item = Result<Token<'src>, LexError>>,
+        from: usize,
+    ) -> Vec<String> {
+        results
+            .filter_map(|result| match result {
+                Ok(token) if token.span.end.offset <= from => None,
+                Ok(token) => Some(describe_token(&token)),
+                Err(error) => Some(format!("error: {}", error)),
+            })
+            .collect()
+    }
+
+    /// `text` preceded by lines of padding that end `before` bytes short
+    /// of the window size, and followed by a line more.
+    fn straddling(text: &str, before: usize) -> String {
+        let mut source = String::new();
+        let padding = WINDOW_SIZE - before;
+        while padding - source.len() >= 16 {
+            source.push_str("int padding;\n");
+        }
+        let comment = padding - source.len() - 3;
+        source.push_str(&format!("//{}\n", " ".repeat(comment)));
+        source.push_str(text);
+        source.push_str("int after;\n");
+        source
+    }
+
+    #[test]
+    fn tokens_across_the_window_boundary_match_the_lexer() {
+        let texts = [
+            "int a_rather_long_identifier = \"and a string literal\" + 0x1234;\n",
+            "int spl\\\nit = 1 + \\\n2;\n#define F(x) \\\n  ((x) + \\\n   1)\n",
+            "/* a block comment\n   that runs on\n   past the window */ int behind;\n",
+            "x /* one line */ + /* and\n another */ y;\n",
+        ];
+        for text in texts {
+            // The window ends with the line the boundary falls in, so it is
+            // enough to end each line of `text` at the boundary or just
+            // past it.
+            let line_ends = text
+                .match_indices('\n')
+                .flat_map(|(index, _)| [index, index + 1]);
+            for before in line_ends {
+                let source = straddling(text, before);
+                let from = WINDOW_SIZE - before;
+                assert_eq!(
+                    describe(StreamingLexer::new(source.as_bytes()), from),
+                    describe(Lexer::new(&source), from),
+                    "{:?} {} bytes before the window ends",
+                    text,
+                    before
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn comments_longer_than_the_window_match_the_lexer() {
+        let body = "a comment line\n".repeat(3 * WINDOW_SIZE / 15);
+        let source = format!("int before;\n/*{}*/ int after;\n", body);
+        assert_eq!(
+            describe(StreamingLexer::new(source.as_bytes()), 0),
+            describe(Lexer::new(&source), 0)
+        );
+    }
+}
@@ -0,0 +1,18 @@
+pub mod compile_commands;
+pub mod dependencies;
+pub mod error;
+pub mod expression;
+pub mod lexer;
+pub mod literal;
+pub mod number;
+pub mod perfect_hash;
+pub mod preprocessor;
+pub mod standard;
+pub mod symbol;
+pub mod tokens;
+pub mod unifdef;
+
+pub use error::{CompileCommandsError, LexError, PreprocessError};
+pub use lexer::{read_source, Lexer, StreamingLexer};
+pub use preprocessor::Preprocessor;
+pub use unifdef::Unifdef;
@@ -0,0 +1,872 @@
+use std::{borrow::Cow, collections::VecDeque, fmt::Display, iter::Peekable, str::Chars};
+
+use crate::{
+    error::LexError,
+    number::{CType, Constant, Value},
+    tokens::{Token, TokenType},
+};
+
+/// The encoding prefix of a character constant or string literal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    /// No prefix; the execution character set is taken to be UTF-8.
+    Plain,
+    /// `L`, with a 32-bit `wchar_t`.
+    Wide,
+    /// `u8`
+    Utf8,
+    /// `u`
+    Utf16,
+    /// `U`
+    Utf32,
+}
+
+impl Encoding {
+    /// Recognises an encoding prefix spelled exactly as `prefix`.
+    pub fn from_prefix(prefix: &str) -> Option<Encoding> {
+        match prefix {
+            "" => Some(Encoding::Plain),
+            "L" => Some(Encoding::Wide),
+            "u8" => Some(Encoding::Utf8),
+            "u" => Some(Encoding::Utf16),
+            "U" => Some(Encoding::Utf32),
+            _ => None,
+        }
+    }
+
+    /// The width in bits of one code unit.
+    pub fn code_unit_width(&self) -> u32 {
+        match self {
+            Encoding::Plain | Encoding::Utf8 => 8,
+            Encoding::Utf16 => 16,
+            Encoding::Wide | Encoding::Utf32 => 32,
+        }
+    }
+
+    /// Appends the code units encoding `character`.
+    fn encode(&self, character: char, code_units: &mut impl Extend<u32>) {
+        match self {
+            Encoding::Plain | Encoding::Utf8 => {
+                let mut buffer = [0; 4];
+                let bytes = character.encode_utf8(&mut buffer).as_bytes();
+                code_units.extend(bytes.iter().map(|byte| *byte as u32));
+            }
+            Encoding::Utf16 => {
+                let mut buffer = [0; 2];
+                let units = character.encode_utf16(&mut buffer);
+                code_units.extend(units.iter().map(|unit| *unit as u32));
+            }
+            Encoding::Wide | Encoding::Utf32 => code_units.extend([character as u32]),
+        }
+    }
+}
+
+impl Display for Encoding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Encoding::Plain => write!(f, ""),
+            Encoding::Wide => write!(f, "L"),
+            Encoding::Utf8 => write!(f, "u8"),
+            Encoding::Utf16 => write!(f, "u"),
+            Encoding::Utf32 => write!(f, "U"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LiteralError {
+    Unterminated,
+    Empty,
+    InvalidEscape(char),
+    MissingHexDigits,
+    /// An octal or hexadecimal escape whose value does not fit a code unit.
+    EscapeOutOfRange(String),
+    InvalidUniversalCharacterName(String),
+    /// A character that needs more than one code unit in a `u8`, `u` or `U`
+    /// character constant.
+    NotRepresentable,
+    TooManyCharacters,
+}
+
+impl Display for LiteralError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LiteralError::Unterminated => write!(f, "missing closing quote"),
+            LiteralError::Empty => write!(f, "empty character constant"),
+            LiteralError::InvalidEscape(character) => {
+                write!(f, "unknown escape sequence \\{}", character)
+            }
+            LiteralError::MissingHexDigits => write!(f, "\\x used with no following hex digits"),
+            LiteralError::EscapeOutOfRange(escape) => {
+                write!(f, "escape sequence {} is out of range", escape)
+            }
+            LiteralError::InvalidUniversalCharacterName(name) => {
+                write!(f, "{} is not a valid universal character name", name)
+            }
+            LiteralError::NotRepresentable => {
+                write!(f, "character does not fit in a single code unit")
+            }
+            LiteralError::TooManyCharacters => {
+                write!(f, "character constant has more than one character")
+            }
+        }
+    }
+}
+
+impl std::error::Error for LiteralError {}
+
+/// Decodes the text between the quotes of a character constant or string
+/// literal into the code units it stands for.
+pub fn decode(content: &str, encoding: Encoding) -> Result<Vec<u32>, LiteralError> {
+    let mut code_units = vec![];
+    decode_into(content, encoding, &mut code_units)?;
+    Ok(code_units)
+}
+
+/// Like [`decode`], but hands the code units to `code_units` rather than
+/// collecting them.
+fn decode_into(
+    content: &str,
+    encoding: Encoding,
+    code_units: &mut impl Extend<u32>,
+) -> Result<(), LiteralError> {
+    let mut characters = content.chars().peekable();
+    while let Some(character) = characters.next() {
+        if character != '\\' {
+            encoding.encode(character, code_units);
+            continue;
+        }
+        let Some(escape) = characters.next() else {
+            return Err(LiteralError::Unterminated);
+        };
+        let simple = match escape {
+            '\'' | '"' | '?' | '\\' => Some(escape as u32),
+            'a' => Some(0x07),
+            'b' => Some(0x08),
+            'f' => Some(0x0C),
+            'n' => Some(0x0A),
+            'r' => Some(0x0D),
+            't' => Some(0x09),
+            'v' => Some(0x0B),
+            _ => None,
+        };
+        if let Some(code_unit) = simple {
+            code_units.extend([code_unit]);
+            continue;
+        }
+        match escape {
+            '0'..='7' => {
+                let mut spelling = format!("\\{}", escape);
+                while spelling.len() < 4 {
+                    match characters.next_if(|digit| ('0'..='7').contains(digit)) {
+                        Some(digit) => spelling.push(digit),
+                        None => break,
+                    }
+                }
+                code_units.extend([numeric_escape(&spelling[1..], 8, &spelling, encoding)?]);
+            }
+            'x' => {
+                let mut digits = String::new();
+                while let Some(digit) = characters.next_if(char::is_ascii_hexdigit) {
+                    digits.push(digit);
+                }
+                if digits.is_empty() {
+                    return Err(LiteralError::MissingHexDigits);
+                }
+                let spelling = format!("\\x{}", digits);
+                code_units.extend([numeric_escape(&digits, 16, &spelling, encoding)?]);
+            }
+            'u' | 'U' => {
+                let character = universal_character_name(escape, &mut characters)?;
+                encoding.encode(character, code_units);
+            }
+            _ => return Err(LiteralError::InvalidEscape(escape)),
+        }
+    }
+    Ok(())
+}
+
+/// What [`CharacterConstant::parse`] needs to know of the code units of a
+/// constant, gathered without storing them.
+#[derive(Default)]
+struct Summary {
+    count: usize,
+    last: u32,
+    /// The code units shifted in a byte at a time, keeping the low 32 bits.
+    packed: u32,
+}
+
+impl Extend<u32> for Summary {
+    fn extend<I: IntoIterator<Item = u32>>(&mut self, code_units: I) {
+        for code_unit in code_units {
+            self.count += 1;
+            self.last = code_unit;
+            self.packed = (self.packed << 8) | code_unit;
+        }
+    }
+}
+
+fn numeric_escape(
+    digits: &str,
+    radix: u32,
+    spelling: &str,
+    encoding: Encoding,
+) -> Result<u32, LiteralError> {
+    let out_of_range = || LiteralError::EscapeOutOfRange(spelling.to_string());
+    let value = u64::from_str_radix(digits, radix).map_err(|_| out_of_range())?;
+    if value >> encoding.code_unit_width() != 0 {
+        return Err(out_of_range());
+    }
+    Ok(value as u32)
+}
+
+/// Reads the digits of a `\u` or `\U` escape and checks they name a
+/// character that may be written that way.
+pub(crate) fn universal_character_name(
+    escape: char,
+    characters: &mut Peekable<Chars>,
+) -> Result<char, LiteralError> {
+    let length = if escape == 'u' { 4 } else { 8 };
+    let mut digits = String::new();
+    while digits.len() < length {
+        match characters.next_if(char::is_ascii_hexdigit) {
+            Some(digit) => digits.push(digit),
+            None => break,
+        }
+    }
+    let spelling = format!("\\{}{}", escape, digits);
+    let invalid = || LiteralError::InvalidUniversalCharacterName(spelling.clone());
+    if digits.len() < length {
+        return Err(invalid());
+    }
+    let code_point = u32::from_str_radix(&digits, 16).map_err(|_| invalid())?;
+    // Below U+00A0 only `$`, `@` and `` ` `` may be named this way;
+    // surrogates and values past U+10FFFF are rejected by `char::from_u32`.
+    if code_point < 0xA0 && ![0x24, 0x40, 0x60].contains(&code_point) {
+        return Err(invalid());
+    }
+    char::from_u32(code_point).ok_or_else(invalid)
+}
+
+/// A character constant such as `'a'`, `'\n'` or `L'\x41'`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CharacterConstant<'src> {
+    pub encoding: Encoding,
+    /// The text between the quotes, with escapes as written.
+    pub spelling: Cow<'src, str>,
+    pub value: Constant,
+}
+
+impl<'src> CharacterConstant<'src> {
+    /// Decodes a character constant and works out its value.
+    ///
+    /// Plain `char` is taken to be signed. A plain constant holding several
+    /// bytes, such as `'ab'` or `'é'`, gets the implementation-defined value
+    /// GCC and Clang give it: the bytes are shifted in one at a time, keeping
+    /// the low 32 bits. A multi-character `L` constant takes the value of its
+    /// last character.
+    pub fn parse(
+        encoding: Encoding,
+        spelling: &'src str,
+    ) -> Result<CharacterConstant<'src>, LiteralError> {
+        let mut code_units = Summary::default();
+        decode_into(spelling, encoding, &mut code_units)?;
+        if code_units.count == 0 {
+            return Err(LiteralError::Empty);
+        }
+        let last = code_units.last;
+        let value = match encoding {
+            Encoding::Plain if code_units.count == 1 => Constant {
+                value: Value::Signed(last as u8 as i8 as i128),
+                c_type: CType::Int,
+            },
+            Encoding::Plain => Constant {
+                value: Value::Signed(code_units.packed as i32 as i128),
+                c_type: CType::Int,
+            },
+            Encoding::Wide => Constant {
+                value: Value::Signed(last as i32 as i128),
+                c_type: CType::Int,
+            },
+            Encoding::Utf8 | Encoding::Utf16 | Encoding::Utf32 => {
+                if code_units.count > 1 {
+                    let mut characters = Summary::default();
+                    decode_into(spelling, Encoding::Utf32, &mut characters)?;
+                    return Err(if characters.count > 1 {
+                        LiteralError::TooManyCharacters
+                    } else {
+                        LiteralError::NotRepresentable
+                    });
+                }
+                let c_type = match encoding {
+                    Encoding::Utf8 => CType::UnsignedChar,
+                    Encoding::Utf16 => CType::UnsignedShort,
+                    _ => CType::UnsignedInt,
+                };
+                Constant {
+                    value: Value::Unsigned(last as u128),
+                    c_type,
+                }
+            }
+        };
+        Ok(CharacterConstant {
+            encoding,
+            spelling: Cow::Borrowed(spelling),
+            value,
+        })
+    }
+
+    /// The code units the constant is made of.
+    pub fn code_units(&self) -> Vec<u32> {
+        // Parsing already decoded the spelling once, so this cannot fail.
+        decode(&self.spelling, self.encoding).unwrap_or_default()
+    }
+
+    /// The same constant with its spelling copied out of the source.
+    pub fn into_owned(self) -> CharacterConstant<'static> {
+        CharacterConstant {
+            encoding: self.encoding,
+            spelling: Cow::Owned(self.spelling.into_owned()),
+            value: self.value,
+        }
+    }
+}
+
+impl Display for CharacterConstant<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}'{}'", self.encoding, self.spelling)
+    }
+}
+
+/// A string literal such as `"abc"` or `u8"abc"`.
+///
+/// Literals joined by [`concatenate`] keep one piece per original literal, so
+/// that an escape such as `"\x1" "2"` is still decoded within its own piece.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StringLiteral<'src> {
+    pub encoding: Encoding,
+    /// The text between the quotes of the first literal, with escapes as
+    /// written.
+    pub piece: Cow<'src, str>,
+    /// The pieces of the literals joined onto the first one.
+    pub joined: Vec<Cow<'src, str>>,
+}
+
+impl<'src> StringLiteral<'src> {
+    /// A single literal with the text between its quotes.
+    pub fn new(encoding: Encoding, piece: Cow<'src, str>) -> Self {
+        StringLiteral {
+            encoding,
+            piece,
+            joined: vec![],
+        }
+    }
+
+    /// The text between the quotes of each literal, with escapes as written.
+    pub fn pieces(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(&*self.piece).chain(self.joined.iter().map(|piece| &**piece))
+    }
+
+    /// Decodes the literal into code units of its encoding, without the
+    /// terminating null.
+    pub fn code_units(&self) -> Result<Vec<u32>, LiteralError> {
+        let mut code_units = vec![];
+        for piece in self.pieces() {
+            decode_into(piece, self.encoding, &mut code_units)?;
+        }
+        Ok(code_units)
+    }
+
+    /// Checks that every escape in the literal is valid, without keeping the
+    /// code units.
+    pub fn validate(&self) -> Result<(), LiteralError> {
+        self.pieces()
+            .try_for_each(|piece| decode_into(piece, self.encoding, &mut Summary::default()))
+    }
+
+    /// The same literal with its pieces copied out of the source.
+    pub fn into_owned(self) -> StringLiteral<'static> {
+        StringLiteral {
+            encoding: self.encoding,
+            piece: Cow::Owned(self.piece.into_owned()),
+            joined: self
+                .joined
+                .into_iter()
+                .map(|piece| Cow::Owned(piece.into_owned()))
+                .collect(),
+        }
+    }
+
+    /// Decodes the literal into bytes, storing wider code units in
+    /// little-endian order.
+    pub fn bytes(&self) -> Result<Vec<u8>, LiteralError> {
+        let code_units = self.code_units()?;
+        Ok(match self.encoding.code_unit_width() {
+            8 => code_units.iter().map(|unit| *unit as u8).collect(),
+            16 => code_units
+                .iter()
+                .flat_map(|unit| (*unit as u16).to_le_bytes())
+                .collect(),
+            _ => code_units
+                .iter()
+                .flat_map(|unit| unit.to_le_bytes())
+                .collect(),
+        })
+    }
+}
+
+impl Display for StringLiteral<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (index, piece) in self.pieces().enumerate() {
+            if index > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{}\"{}\"", self.encoding, piece)?;
+        }
+        Ok(())
+    }
+}
+
+/// Joins adjacent string literals into one, as translation phase 6 does.
+///
+/// Comments between two joined literals are dropped, since by this phase
+/// they would have been replaced by whitespace. A literal whose prefix
+/// conflicts with the ones before it is reported, then starts a new token.
+pub fn concatenate<'src, I>(tokens: I) -> Concatenate<'src, I::IntoIter>
+where
+    I: IntoIterator<Item = Result<Token<'src>, LexError>>,
+{
+    Concatenate {
+        tokens: tokens.into_iter().peekable(),
+        pending: VecDeque::new(),
+    }
+}
+
+pub struct Concatenate<'src, I: Iterator<Item = Result<Token<'src>, LexError>>> {
+    tokens: Peekable<I>,
+    pending: VecDeque<Result<Token<'src>, LexError>>,
+}
+
+impl<'src, I: Iterator<Item = Result<Token<'src>, LexError>>> Iterator for Concatenate<'src, I> {
+    type Item = Result<Token<'src>, LexError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(pending) = self.pending.pop_front() {
+            return Some(pending);
+        }
+        let mut token = match self.tokens.next()? {
+            Ok(token) => token,
+            Err(error) => return Some(Err(error)),
+        };
+        let TokenType::Literal(literal) = &mut token.token_type else {
+            return Some(Ok(token));
+        };
+        let mut comments = vec![];
+        let mut error = None;
+        while let Some(Ok(next)) = self.tokens.peek() {
+            let start = next.span.start;
+            let next = match &next.token_type {
+                TokenType::Comment(_) => {
+                    comments.extend(self.tokens.next());
+                    continue;
+                }
+                TokenType::Literal(next) => next,
+                _ => break,
+            };
+            let encoding = match (literal.encoding, next.encoding) {
+                (first, Encoding::Plain) => first,
+                (Encoding::Plain, second) => second,
+                (first, second) if first == second => first,
+                // The literal that does not fit starts a run of its own.
+                (first, second) => {
+                    error = Some(LexError::IncompatibleStringPrefixes {
+                        first,
+                        second,
+                        location: start,
+                    });
+                    break;
+                }
+            };
+            let Some(Ok(Token {
+                span,
+                token_type: TokenType::Literal(next),
+                ..
+            })) = self.tokens.next()
+            else {
+                unreachable!("the peeked token is a literal");
+            };
+            literal.encoding = encoding;
+            literal.joined.push(next.piece);
+            literal.joined.extend(next.joined);
+            token.span.end = span.end;
+            comments.clear();
+        }
+        match error {
+            Some(error) => {
+                self.pending.push_back(Ok(token));
+                self.pending.extend(comments);
+                Some(Err(error))
+            }
+            None => {
+                self.pending.extend(comments);
+                Some(Ok(token))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{lexer::Lexer, standard::Standard};
+
+    fn constant(encoding: Encoding, spelling: &str) -> Result<Constant, LiteralError> {
+        CharacterConstant::parse(encoding, spelling).map(|constant| constant.value)
+    }
+
+    /// The string literals of `source` after phase 6, each as its encoding
+    /// and code units, and the errors found on the way.
+    fn concatenated(source: &str) -> (Vec<(Encoding, Vec<u32>)>, Vec<LexError>) {
+        let mut literals = vec![];
+        let mut errors = vec![];
+        for result in concatenate(Lexer::new(source).with_standard(Standard::C23)) {
+            match result {
+                Ok(Token {
+                    token_type: TokenType::Literal(literal),
+                    ..
+                }) => literals.push((literal.encoding, literal.code_units().unwrap())),
+                Ok(_) => {}
+                Err(error) => errors.push(error),
+            }
+        }
+        (literals, errors)
+    }
+
+    #[test]
+    fn escapes_decode_to_their_code_units() {
+        let cases: &[(&str, &[u32])] = &[
+            (r"\n", &[0x0A]),
+            (r"\a\b\f\r\t\v", &[0x07, 0x08, 0x0C, 0x0D, 0x09, 0x0B]),
+            (r#"\'\"\?\\"#, &[0x27, 0x22, 0x3F, 0x5C]),
+            (r"\0", &[0]),
+            (r"\101", &[0x41]),
+            // An octal escape stops after three digits.
+            (r"\1011", &[0x41, 0x31]),
+            (r"\x41", &[0x41]),
+            (r"\x4g", &[0x04, 0x67]),
+            (r"\u00E9", &[0xC3, 0xA9]),
+            (r"\U0001F600", &[0xF0, 0x9F, 0x98, 0x80]),
+            ("é", &[0xC3, 0xA9]),
+        ];
+        for (content, code_units) in cases {
+            assert_eq!(
+                decode(content, Encoding::Plain).as_deref(),
+                Ok(*code_units),
+                "{}",
+                content
+            );
+        }
+    }
+
+    #[test]
+    fn character_constants_take_the_type_of_their_prefix() {
+        let cases = [
+            (Encoding::Plain, "a", Value::Signed(0x61), CType::Int),
+            (Encoding::Plain, r"\n", Value::Signed(0x0A), CType::Int),
+            // Plain `char` is signed.
+            (Encoding::Plain, r"\xff", Value::Signed(-1), CType::Int),
+            (Encoding::Plain, r"\377", Value::Signed(-1), CType::Int),
+            (Encoding::Wide, r"\xffffffff", Value::Signed(-1), CType::Int),
+            (Encoding::Wide, r"\u00E9", Value::Signed(0xE9), CType::Int),
+            (
+                Encoding::Utf8,
+                "a",
+                Value::Unsigned(0x61),
+                CType::UnsignedChar,
+            ),
+            (
+                Encoding::Utf8,
+                r"\xff",
+                Value::Unsigned(0xFF),
+                CType::UnsignedChar,
+            ),
+            (
+                Encoding::Utf16,
+                "é",
+                Value::Unsigned(0xE9),
+                CType::UnsignedShort,
+            ),
+            (
+                Encoding::Utf16,
+                r"\xffff",
+                Value::Unsigned(0xFFFF),
+                CType::UnsignedShort,
+            ),
+            (
+                Encoding::Utf32,
+                "😀",
+                Value::Unsigned(0x1F600),
+                CType::UnsignedInt,
+            ),
+            (
+                Encoding::Utf32,
+                r"\U0001F600",
+                Value::Unsigned(0x1F600),
+                CType::UnsignedInt,
+            ),
+        ];
+        for (encoding, spelling, value, c_type) in cases {
+            assert_eq!(
+                constant(encoding, spelling),
+                Ok(Constant { value, c_type }),
+                "{}'{}'",
+                encoding,
+                spelling
+            );
+        }
+    }
+
+    #[test]
+    fn multi_character_constants_pack_bytes_or_keep_the_last() {
+        let cases = [
+            (Encoding::Plain, "ab", Value::Signed(0x6162)),
+            (Encoding::Plain, "abcd", Value::Signed(0x61626364)),
+            // Only the low 32 bits are kept.
+            (Encoding::Plain, "abcde", Value::Signed(0x62636465)),
+            (Encoding::Plain, "é", Value::Signed(0xC3A9)),
+            (Encoding::Plain, r"\xff\xff\xff\xff", Value::Signed(-1)),
+            (Encoding::Wide, "ab", Value::Signed(0x62)),
+        ];
+        for (encoding, spelling, value) in cases {
+            assert_eq!(
+                constant(encoding, spelling),
+                Ok(Constant {
+                    value,
+                    c_type: CType::Int
+                }),
+                "{}'{}'",
+                encoding,
+                spelling
+            );
+        }
+    }
+
+    #[test]
+    fn malformed_character_constants_are_diagnosed() {
+        let cases = [
+            (Encoding::Plain, "", LiteralError::Empty),
+            (Encoding::Plain, r"\q", LiteralError::InvalidEscape('q')),
+            (Encoding::Plain, r"\x", LiteralError::MissingHexDigits),
+            (Encoding::Plain, r"\", LiteralError::Unterminated),
+            (
+                Encoding::Plain,
+                r"\x100",
+                LiteralError::EscapeOutOfRange(r"\x100".to_string()),
+            ),
+            (
+                Encoding::Plain,
+                r"\400",
+                LiteralError::EscapeOutOfRange(r"\400".to_string()),
+            ),
+            (
+                Encoding::Utf16,
+                r"\x10000",
+                LiteralError::EscapeOutOfRange(r"\x10000".to_string()),
+            ),
+            (
+                Encoding::Plain,
+                r"\u00",
+                LiteralError::InvalidUniversalCharacterName(r"\u00".to_string()),
+            ),
+            (
+                Encoding::Plain,
+                r"\u0041",
+                LiteralError::InvalidUniversalCharacterName(r"\u0041".to_string()),
+            ),
+            (
+                Encoding::Plain,
+                r"\uD800",
+                LiteralError::InvalidUniversalCharacterName(r"\uD800".to_string()),
+            ),
+            (Encoding::Utf8, "é", LiteralError::NotRepresentable),
+            (Encoding::Utf16, "😀", LiteralError::NotRepresentable),
+            (Encoding::Utf8, "ab", LiteralError::TooManyCharacters),
+            (Encoding::Utf32, "ab", LiteralError::TooManyCharacters),
+        ];
+        for (encoding, spelling, error) in cases {
+            assert_eq!(
+                constant(encoding, spelling),
+                Err(error),
+                "{}'{}'",
+                encoding,
+                spelling
+            );
+        }
+    }
+
+    #[test]
+    fn strings_decode_into_code_units_of_their_encoding() {
+        let cases: &[(Encoding, &str, &[u32], &[u8])] = &[
+            (
+                Encoding::Plain,
+                "aé",
+                &[0x61, 0xC3, 0xA9],
+                &[0x61, 0xC3, 0xA9],
+            ),
+            (
+                Encoding::Utf8,
+                "aé",
+                &[0x61, 0xC3, 0xA9],
+                &[0x61, 0xC3, 0xA9],
+            ),
+            (Encoding::Utf16, "aé", &[0x61, 0xE9], &[0x61, 0, 0xE9, 0]),
+            (
+                Encoding::Utf16,
+                "😀",
+                &[0xD83D, 0xDE00],
+                &[0x3D, 0xD8, 0x00, 0xDE],
+            ),
+            (
+                Encoding::Utf32,
+                "a😀",
+                &[0x61, 0x1F600],
+                &[0x61, 0, 0, 0, 0x00, 0xF6, 0x01, 0],
+            ),
+            (
+                Encoding::Wide,
+                r"\x12345678",
+                &[0x12345678],
+                &[0x78, 0x56, 0x34, 0x12],
+            ),
+            (
+                Encoding::Plain,
+                r#"\"a\"\\"#,
+                &[0x22, 0x61, 0x22, 0x5C],
+                &[0x22, 0x61, 0x22, 0x5C],
+            ),
+            (Encoding::Plain, r"'", &[0x27], &[0x27]),
+            (Encoding::Plain, "", &[], &[]),
+        ];
+        for (encoding, piece, code_units, bytes) in cases {
+            let literal = StringLiteral::new(*encoding, Cow::Borrowed(piece));
+            assert_eq!(
+                literal.code_units().as_deref(),
+                Ok(*code_units),
+                "{}",
+                literal
+            );
+            assert_eq!(literal.bytes().as_deref(), Ok(*bytes), "{}", literal);
+            assert_eq!(literal.validate(), Ok(()), "{}", literal);
+        }
+    }
+
+    #[test]
+    fn invalid_escapes_in_strings_are_diagnosed() {
+        let cases = [
+            (Encoding::Plain, r"a\qb", LiteralError::InvalidEscape('q')),
+            (
+                Encoding::Utf8,
+                r"\x100",
+                LiteralError::EscapeOutOfRange(r"\x100".to_string()),
+            ),
+            (
+                Encoding::Utf16,
+                r"\x10000",
+                LiteralError::EscapeOutOfRange(r"\x10000".to_string()),
+            ),
+            (
+                Encoding::Utf32,
+                r"\U00110000",
+                LiteralError::InvalidUniversalCharacterName(r"\U00110000".to_string()),
+            ),
+        ];
+        for (encoding, piece, error) in cases {
+            let literal = StringLiteral::new(encoding, Cow::Borrowed(piece));
+            assert_eq!(literal.validate(), Err(error.clone()), "{}", literal);
+            assert_eq!(literal.code_units(), Err(error), "{}", literal);
+        }
+    }
+
+    #[test]
+    fn adjacent_strings_are_joined_with_a_common_prefix() {
+        let cases: &[(&str, Encoding, &[u32])] = &[
+            (r#""a" "b""#, Encoding::Plain, &[0x61, 0x62]),
+            (r#""a" /* c */ "b" // d"#, Encoding::Plain, &[0x61, 0x62]),
+            ("\"a\"\n\"b\"", Encoding::Plain, &[0x61, 0x62]),
+            // A plain piece takes the prefix of the other.
+            (r#""é" u"é""#, Encoding::Utf16, &[0xE9, 0xE9]),
+            (r#"L"a" "b""#, Encoding::Wide, &[0x61, 0x62]),
+            (r#"u8"a" "b" u8"c""#, Encoding::Utf8, &[0x61, 0x62, 0x63]),
+            (r#"U"a" U"b""#, Encoding::Utf32, &[0x61, 0x62]),
+            // Escapes end with their own piece.
+            (r#""\x1" "2""#, Encoding::Plain, &[0x01, 0x32]),
+            (r#""\1" "23""#, Encoding::Plain, &[0x01, 0x32, 0x33]),
+        ];
+        for (source, encoding, code_units) in cases {
+            let (literals, errors) = concatenated(source);
+            assert_eq!(literals, [(*encoding, code_units.to_vec())], "{}", source);
+            assert!(errors.is_empty(), "{}: {:?}", source, errors);
+        }
+    }
+
+    #[test]
+    fn differing_prefixes_cannot_be_joined() {
+        for (source, encodings) in [
+            (r#"u"a" U"b""#, &[Encoding::Utf16, Encoding::Utf32][..]),
+            (r#"L"a" u8"b""#, &[Encoding::Wide, Encoding::Utf8]),
+            (r#"u8"a" "b" u"c""#, &[Encoding::Utf8, Encoding::Utf16]),
+        ] {
+            let (literals, errors) = concatenated(source);
+            let found: Vec<_> = literals.iter().map(|(encoding, _)| *encoding).collect();
+            assert_eq!(found, encodings, "{}", source);
+            assert!(
+                matches!(errors[..], [LexError::IncompatibleStringPrefixes { .. }]),
+                "{}: {:?}",
+                source,
+                errors
+            );
+        }
+    }
+
+    #[test]
+    fn each_incompatible_literal_keeps_its_own_prefix() {
+        let source = r#""a" L"b" u"é" U"d""#;
+        let results: Vec<_> = concatenate(Lexer::new(source).with_standard(Standard::C23))
+            .map(|result| match result {
+                Ok(Token {
+                    token_type: TokenType::Literal(literal),
+                    ..
+                }) => Ok(literal.to_string()),
+                Ok(token) => panic!("{}", token.token_type),
+                Err(LexError::IncompatibleStringPrefixes {
+                    first,
+                    second,
+                    location,
+                }) => Err((first, second, location.offset)),
+                Err(error) => panic!("{}", error),
+            })
+            .collect();
+        assert_eq!(
+            results,
+            [
+                Err((Encoding::Wide, Encoding::Utf16, 9)),
+                Ok(r#"L"a" L"b""#.to_owned()),
+                Err((Encoding::Utf16, Encoding::Utf32, 15)),
+                Ok(r#"u"é""#.to_owned()),
+                Ok(r#"U"d""#.to_owned()),
+            ]
+        );
+        let (literals, _) = concatenated(source);
+        assert_eq!(literals[1], (Encoding::Utf16, vec![0xE9]));
+    }
+
+    #[test]
+    fn strings_are_not_joined_across_other_tokens() {
+        let (literals, errors) = concatenated(r#""a" , "b""#);
+        assert_eq!(
+            literals,
+            [(Encoding::Plain, vec![0x61]), (Encoding::Plain, vec![0x62])]
+        );
+        assert!(errors.is_empty());
+    }
+}
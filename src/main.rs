@@ -1,767 +1,722 @@
-pub mod tokens;
+use std::{
+    env::args,
+    fs::File,
+    io::{self, BufRead, BufReader, Write},
+    path::Path,
+    process::ExitCode,
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+};
 
-use std::{collections::HashMap, env::args, fs, path::Path, process::ExitCode};
-
-use regex::Regex;
+use cust::{
+    compile_commands::{definition, read_compile_commands, CompileCommand},
+    dependencies::DependencyGraph,
+    literal,
+    number::DataModel,
+    preprocessor::{Position, Printer},
+    read_source,
+    standard::{Extension, Standard},
+    symbol::SharedInterner,
+    tokens::{Location, Token, TokenType},
+    LexError, Lexer, Preprocessor, StreamingLexer, Unifdef,
+};
 use tabled::{builder::Builder, settings::Style};
-use tokens::{Delimiter, Keyword, Operator, Token, TokenType};
 
 fn main() -> ExitCode {
-    let args: Vec<String> = args().collect();
+    let mut file_paths = vec![];
+    let mut concatenate_strings = false;
+    let mut warn_newer_features = false;
+    let mut preprocess = false;
+    let mut format = "dot".to_string();
+    let mut compile_commands = None;
+    // The options given here, which apply on top of those from the
+    // compilation database.
+    let mut options = CompileCommand::default();
+    let mut arguments = args().skip(1).peekable();
+    // `eval` takes an expression where the other modes take a file, `deps`
+    // and `symbols` take any number of files and `explain` a place in one.
+    let command = arguments.next_if(|argument| {
+        matches!(
+            argument.as_str(),
+            "eval" | "unifdef" | "deps" | "symbols" | "explain"
+        )
+    });
+    while let Some(argument) = arguments.next() {
+        match argument.as_str() {
+            "--help" => {
+                println!("{}", help_text());
+                return ExitCode::SUCCESS;
+            }
+            "--concatenate-strings" => concatenate_strings = true,
+            "--warn-newer-features" => warn_newer_features = true,
+            "--preprocess" => preprocess = true,
+            "--compile-commands" => match arguments.next() {
+                Some(path) => compile_commands = Some(path),
+                None => {
+                    eprintln!("{}", help_text());
+                    return ExitCode::FAILURE;
+                }
+            },
+            "-I" => match arguments.next() {
+                Some(path) => options.include_paths.push(path.into()),
+                None => {
+                    eprintln!("{}", help_text());
+                    return ExitCode::FAILURE;
+                }
+            },
+            "-iquote" => match arguments.next() {
+                Some(path) => options.quote_include_paths.push(path.into()),
+                None => {
+                    eprintln!("{}", help_text());
+                    return ExitCode::FAILURE;
+                }
+            },
+            "-isystem" => match arguments.next() {
+                Some(path) => options.system_include_paths.push(path.into()),
+                None => {
+                    eprintln!("{}", help_text());
+                    return ExitCode::FAILURE;
+                }
+            },
+            _ if argument.starts_with("-I") => options.include_paths.push(argument[2..].into()),
+            "-D" | "-U" => match arguments.next() {
+                Some(name) => options.definitions.push(definition(&argument, &name)),
+                None => {
+                    eprintln!("{}", help_text());
+                    return ExitCode::FAILURE;
+                }
+            },
+            _ if argument.starts_with("-D") || argument.starts_with("-U") => options
+                .definitions
+                .push(definition(&argument[..2], &argument[2..])),
+            _ if argument.starts_with("--ext=") => match Extension::from_name(&argument[6..]) {
+                Some(extension) => options.extensions.push(extension),
+                None => {
+                    eprintln!("{}", help_text());
+                    return ExitCode::FAILURE;
+                }
+            },
+            _ if argument.starts_with("--format=") => match &argument[9..] {
+                "dot" | "json" | "make" => format = argument[9..].to_string(),
+                _ => {
+                    eprintln!("{}", help_text());
+                    return ExitCode::FAILURE;
+                }
+            },
+            _ if argument.starts_with("--std=") => match Standard::from_name(&argument[6..]) {
+                Some(selected) => options.standard = Some(selected),
+                None => {
+                    eprintln!("{}", help_text());
+                    return ExitCode::FAILURE;
+                }
+            },
+            _ if argument.starts_with("--") => {
+                eprintln!("{}", help_text());
+                return ExitCode::FAILURE;
+            }
+            _ => file_paths.push(argument),
+        }
+    }
 
-    if args.len() != 2 {
-        println!("{}", help_text());
+    if command.as_deref() == Some("eval") {
+        let [expression] = file_paths.as_slice() else {
+            eprintln!("{}", help_text());
+            return ExitCode::FAILURE;
+        };
+        return evaluate_expression(expression, &options);
+    }
+    let mut place = None;
+    if command.as_deref() == Some("explain") {
+        let [argument] = file_paths.as_slice() else {
+            eprintln!("{}", help_text());
+            return ExitCode::FAILURE;
+        };
+        let Some((file_path, line, column)) = parse_place(argument) else {
+            eprintln!("{}", help_text());
+            return ExitCode::FAILURE;
+        };
+        file_paths = vec![file_path];
+        place = Some((line, column));
+    }
+    let commands: Vec<CompileCommand> = match &compile_commands {
+        // Files named alongside the database pick out its entries.
+        Some(path) => match read_compile_commands(Path::new(path)) {
+            Ok(entries) => entries
+                .into_iter()
+                .filter(|entry| {
+                    file_paths.is_empty()
+                        || file_paths
+                            .iter()
+                            .any(|file_path| is_same_file(&entry.file, Path::new(file_path)))
+                })
+                .map(|mut entry| {
+                    entry.extend(&options);
+                    entry
+                })
+                .collect(),
+            Err(error) => {
+                eprintln!("{}: {}", path, error);
+                return ExitCode::FAILURE;
+            }
+        },
+        None if file_paths.len() == 1 || matches!(command.as_deref(), Some("deps" | "symbols")) => {
+            file_paths
+                .iter()
+                .map(|file_path| CompileCommand {
+                    file: file_path.into(),
+                    ..options.clone()
+                })
+                .collect()
+        }
+        None => vec![],
+    };
+    if commands.is_empty() {
+        eprintln!("{}", help_text());
         return ExitCode::FAILURE;
     }
-
-    if args.get(1).unwrap() == "--help" {
-        println!("{}", help_text());
-        return ExitCode::SUCCESS;
+    if command.as_deref() == Some("deps") {
+        return print_dependencies(&commands, &format);
+    }
+    if command.as_deref() == Some("symbols") {
+        return print_symbols(&commands);
+    }
+    if let Some((line, column)) = place {
+        return explain(&commands[0], line, column);
     }
 
-    let file_path = Path::new(args.get(1).unwrap());
-    let file = fs::read_to_string(file_path).unwrap();
-
-    let keywords: HashMap<&str, Keyword> = HashMap::from([
-        ("auto", Keyword::Auto),
-        ("break", Keyword::Break),
-        ("case", Keyword::Case),
-        ("char", Keyword::Char),
-        ("const", Keyword::Const),
-        ("continue", Keyword::Continue),
-        ("default", Keyword::Default),
-        ("do", Keyword::Do),
-        ("double", Keyword::Double),
-        ("else", Keyword::Else),
-        ("enum", Keyword::Enum),
-        ("extern", Keyword::Extern),
-        ("float", Keyword::Float),
-        ("for", Keyword::For),
-        ("goto", Keyword::Goto),
-        ("if", Keyword::If),
-        ("inline", Keyword::Inline),
-        ("int", Keyword::Int),
-        ("long", Keyword::Long),
-        ("register", Keyword::Register),
-        ("restrict", Keyword::Restrict),
-        ("return", Keyword::Return),
-        ("short", Keyword::Short),
-        ("signed", Keyword::Signed),
-        ("sizeof", Keyword::Sizeof),
-        ("static", Keyword::Static),
-        ("struct", Keyword::Struct),
-        ("switch", Keyword::Switch),
-        ("typedef", Keyword::Typedef),
-        ("union", Keyword::Union),
-        ("unsigned", Keyword::Unsigned),
-        ("void", Keyword::Void),
-        ("volatile", Keyword::Volatile),
-        ("while", Keyword::Wihle),
-        ("_Bool", Keyword::_Bool),
-        ("_Complex", Keyword::_Complex),
-        ("_Imaginary", Keyword::_Imaginary),
-    ]);
-
-    let operators: HashMap<&str, Operator> = HashMap::from([
-        // Arithmetic Operators
-        ("+", Operator::Addition),
-        ("-", Operator::Subtraction),
-        ("*", Operator::Multiplication),
-        ("/", Operator::Division),
-        ("%", Operator::Remainder),
-        ("++", Operator::Increment),
-        ("--", Operator::Decrement),
-        // Relational Operators
-        ("==", Operator::Equal),
-        ("!=", Operator::NotEqual),
-        (">", Operator::Bigger),
-        (">=", Operator::BiggerOrEqual),
-        ("<", Operator::Smaller),
-        ("<=", Operator::SmallerOrEqual),
-        // Logical and Bitwise Operators
-        ("&&", Operator::LogicalAnd),
-        ("||", Operator::LogicalOr),
-        ("&", Operator::BitwiseAnd),
-        ("!", Operator::LogicalNegation),
-        ("|", Operator::BitwiseOr),
-        ("^", Operator::ExclusiveOr),
-        ("~", Operator::BitwiseNegation),
-        ("<<", Operator::LeftShift),
-        (">>", Operator::RightShift),
-        // Assignment Operators
-        ("=", Operator::Assignment),
-        ("+=", Operator::AdditionAndAssignment),
-        ("-=", Operator::SubtractionAndAssignment),
-        ("*=", Operator::MultiplicationAndAssignment),
-        ("/=", Operator::DivisionAndAssignment),
-        ("%=", Operator::RemainderAndAssignment),
-        (">>=", Operator::RightShiftAndAssignment),
-        ("<<=", Operator::LeftShiftAndAssignment),
-        ("&=", Operator::BitwiseAndAssignment),
-        ("|=", Operator::BitwiseOrAssignment),
-        ("^=", Operator::BitwiseExclusiveOrAndAssignment),
-    ]);
-
-    let delimiters: HashMap<&str, Delimiter> = HashMap::from([
-        (",", Delimiter::Comma),
-        (";", Delimiter::SemiColon),
-        ("(", Delimiter::OpeningParenthesis),
-        (")", Delimiter::ClosingParenthesis),
-        ("[", Delimiter::OpeningBracket),
-        ("]", Delimiter::ClosingBracket),
-        ("{", Delimiter::OpeningCurlyBracket),
-        ("}", Delimiter::ClosingCurlyBracket),
-    ]);
+    let mut status = ExitCode::SUCCESS;
+    for compile_command in &commands {
+        if commands.len() > 1 {
+            println!("{}:", compile_command.file.display());
+        }
+        let result = if command.as_deref() == Some("unifdef") {
+            print_unifdef(compile_command)
+        } else if preprocess {
+            print_preprocessed(compile_command.configure(Preprocessor::new(&compile_command.file)))
+        } else {
+            print_tokens(compile_command, concatenate_strings, warn_newer_features)
+        };
+        if result == ExitCode::FAILURE {
+            status = ExitCode::FAILURE;
+        }
+    }
+    status
+}
 
-    let mut starting_index = 0;
-    let mut ending_index = 0;
-    let mut starting_line = 0;
-    let mut starting_block = 0;
+/// Whether two paths name the same file, comparing them as given if either
+/// does not exist.
+fn is_same_file(first: &Path, second: &Path) -> bool {
+    match (first.canonicalize(), second.canonicalize()) {
+        (Ok(first), Ok(second)) => first == second,
+        _ => first == second,
+    }
+}
 
-    let mut tokens: Vec<Token> = vec![];
+/// Splits `file:line:column` into its parts.
+fn parse_place(place: &str) -> Option<(String, usize, usize)> {
+    let mut parts = place.rsplitn(3, ':');
+    let column = parts.next()?.parse().ok()?;
+    let line = parts.next()?.parse().ok()?;
+    let file_path = parts.next().filter(|file_path| !file_path.is_empty())?;
+    Some((file_path.to_string(), line, column))
+}
 
-    let mut is_inside_block_comments = false;
-    let mut is_inside_linear_comments = false;
-    let mut is_inside_literal = false;
-    let mut line = 1;
-    let mut block = 1;
-    let characters: Vec<char> = file.chars().collect();
-    let mut index = 0;
-    let identifer_regex = Regex::new(r"^([a-zA-Z_])(0-9a-zA-Z_)*").unwrap();
-    let number_regex = Regex::new(r"^(-|\+)?\d+(.\d+)?*").unwrap();
-    while index + 1 != characters.len() {
-        let character = characters.get(index).unwrap();
-        if is_inside_linear_comments {
-            if character == &'\n' {
-                is_inside_linear_comments = false;
-                tokens.push(Token {
-                    line,
-                    block: starting_block,
-                    token_type: TokenType::Comment(
-                        characters
-                            .get(starting_index..(starting_index + ending_index))
-                            .unwrap()
-                            .iter()
-                            .collect(),
-                    ),
-                });
-                line += 1;
-                block = 1;
-                starting_block = 0;
-                starting_index = 0;
-                ending_index = 0;
-            } else {
-                ending_index += 1;
-                block += 1;
-            }
-            index += 1;
-            continue;
-        } else if is_inside_block_comments {
-            if character == &'*' && characters.get(index + 1).unwrap() == &'/' {
-                is_inside_block_comments = false;
-                tokens.push(Token {
-                    line: starting_line,
-                    block: starting_block,
-                    token_type: TokenType::Comment(
-                        characters
-                            .get(starting_index..(starting_index + ending_index))
-                            .unwrap()
-                            .iter()
-                            .collect(),
-                    ),
-                });
-                starting_index = 0;
-                starting_block = 0;
-                starting_line = 0;
-                block += 2;
-                ending_index = 0;
-                index += 2;
-            } else {
-                if *character == '\n' {
-                    line += 1;
+/// Prints how each token the preprocessor makes out of what is written at
+/// `line` and `column` of the file came to be.
+fn explain(compile_command: &CompileCommand, line: usize, column: usize) -> ExitCode {
+    let file_path = compile_command.file.as_path();
+    let is_place = |position: &Position| {
+        position.line == line
+            && position.column == column
+            && is_same_file(Path::new(&*position.file), file_path)
+    };
+    let mut found = false;
+    let mut has_errors = false;
+    for result in compile_command.configure(Preprocessor::new(file_path)) {
+        match result {
+            Ok(token) => {
+                let from_place = is_place(&token.written)
+                    || token
+                        .expansion
+                        .iter()
+                        .any(|step| is_place(&step.invocation));
+                if !from_place {
+                    continue;
                 }
-                block += 1;
-                ending_index += 1;
-                index += 1;
-            }
-            continue;
-        } else if is_inside_literal {
-            if character == &'"' {
-                is_inside_literal = false;
-                tokens.push(Token {
-                    line: starting_line,
-                    block: starting_block,
-                    token_type: TokenType::Literal(
-                        characters
-                            .get(starting_index..(starting_index + ending_index))
-                            .unwrap()
-                            .iter()
-                            .collect(),
-                    ),
-                });
-                starting_block = 0;
-                starting_line = 0;
-                starting_index = 0;
-                block += 1;
-                ending_index = 0;
-            } else {
-                if *character == '\n' {
-                    line += 1;
+                found = true;
+                println!("{} (line {} of {})", token.spelling, token.line, token.file);
+                println!("    written at {}", token.written);
+                for step in &token.expansion {
+                    println!("    {}", step);
                 }
-                block += 1;
-                ending_index += 1;
             }
-            index += 1;
-            continue;
-        }
-        if character.is_whitespace() {
-            if *character == '\n' {
-                line += 1;
-                block = 0;
-            } else {
-                block += 1;
+            Err(error) if error.is_warning() => {
+                eprintln!("{}: warning: {}", error.file().display(), error);
+            }
+            Err(error) => {
+                eprintln!("{}: {}", error.file().display(), error);
+                has_errors = true;
             }
-            index += 1;
-            continue;
-        } else if character == &'/' && characters.get(index + 1).unwrap() == &'/' {
-            index += 2;
-            block += 2;
-            is_inside_linear_comments = true;
-            starting_index = index;
-            starting_block = block;
-            continue;
-        } else if character == &'/' && characters.get(index + 1).unwrap() == &'*' {
-            index += 2;
-            block += 2;
-            is_inside_block_comments = true;
-            starting_index = index;
-            starting_block = block;
-            starting_line = line;
-            continue;
-        } else if character == &'"' {
-            index += 1;
-            block += 1;
-            is_inside_literal = true;
-            starting_index = index;
-            starting_line = line;
-            starting_block = block;
-            continue;
         }
+    }
 
-        let delimiter = match *character {
-            ',' => Some(Delimiter::Comma),
-            ';' => Some(Delimiter::SemiColon),
-            '(' => Some(Delimiter::OpeningParenthesis),
-            ')' => Some(Delimiter::ClosingParenthesis),
-            '[' => Some(Delimiter::OpeningBracket),
-            ']' => Some(Delimiter::ClosingBracket),
-            '{' => Some(Delimiter::OpeningCurlyBracket),
-            '}' => Some(Delimiter::ClosingCurlyBracket),
-            _ => None,
-        };
+    if !found {
+        eprintln!(
+            "{}:{}:{}: No preprocessed token comes from here",
+            file_path.display(),
+            line,
+            column
+        );
+        return ExitCode::FAILURE;
+    }
+    if has_errors {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
 
-        if let Some(delimiter) = delimiter {
-            tokens.push(Token {
-                line,
-                block,
-                token_type: TokenType::Delimiter(delimiter),
-            });
-            index += 1;
-            block += 1;
-            continue;
+/// Prints a table of the tokens in the file.
+fn print_tokens(
+    compile_command: &CompileCommand,
+    concatenate_strings: bool,
+    warn_newer_features: bool,
+) -> ExitCode {
+    let file_path = compile_command.file.as_path();
+    let standard = compile_command.standard.unwrap_or_default();
+    // Standard input is lexed and printed as it arrives, one row per line,
+    // so that memory does not grow with it. A file is read whole, so that
+    // its tokens can borrow from it, and printed as a table.
+    if file_path == Path::new("-") {
+        let mut lexer = StreamingLexer::new(io::stdin().lock()).with_standard(standard);
+        if warn_newer_features {
+            lexer = lexer.warning_on_newer_features();
+        }
+        for extension in &compile_command.extensions {
+            lexer = lexer.with_extension(*extension);
         }
+        return print_token_rows(lexer, concatenate_strings, file_path);
+    }
+    let file = match read_source(file_path) {
+        Ok(file) => file,
+        Err(error) => {
+            eprintln!("{}: {}", file_path.display(), error);
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut lexer = Lexer::new(&file).with_standard(standard);
+    if warn_newer_features {
+        lexer = lexer.warning_on_newer_features();
+    }
+    for extension in &compile_command.extensions {
+        lexer = lexer.with_extension(*extension);
+    }
+    print_token_table(lexer, concatenate_strings, file_path)
+}
 
-        if *character == '+' {
-            if characters[index + 1] == '+' {
-                tokens.push(Token {
-                    line,
-                    block,
-                    token_type: TokenType::Operator(Operator::Increment),
-                });
-                index += 2;
-                block += 2;
-                continue;
-            } else if characters[index + 1] == '=' {
-                tokens.push(Token {
-                    line,
-                    block,
-                    token_type: TokenType::Operator(Operator::AdditionAndAssignment),
-                });
-                index += 2;
-                block += 2;
-                continue;
-            } else {
-                tokens.push(Token {
-                    line,
-                    block,
-                    token_type: TokenType::Operator(Operator::Addition),
-                });
-                index += 1;
-                block += 1;
-                continue;
-            }
-        } else if *character == '-' {
-            if characters[index + 1] == '-' {
-                tokens.push(Token {
-                    line,
-                    block,
-                    token_type: TokenType::Operator(Operator::Decrement),
-                });
-                index += 2;
-                block += 2;
-                continue;
-            } else if characters[index + 1] == '=' {
-                tokens.push(Token {
-                    line,
-                    block,
-                    token_type: TokenType::Operator(Operator::SubtractionAndAssignment),
-                });
-                index += 2;
-                block += 2;
-                continue;
-            } else {
-                tokens.push(Token {
-                    line,
-                    block,
-                    token_type: TokenType::Operator(Operator::Subtraction),
-                });
-                index += 1;
-                block += 1;
-                continue;
-            }
-        } else if *character == '*' {
-            if characters[index + 1] == '=' {
-                tokens.push(Token {
-                    line,
-                    block,
-                    token_type: TokenType::Operator(Operator::MultiplicationAndAssignment),
-                });
-                index += 2;
-                block += 2;
-                continue;
-            } else {
-                tokens.push(Token {
-                    line,
-                    block,
-                    token_type: TokenType::Operator(Operator::Multiplication),
-                });
-                index += 1;
-                block += 1;
-                continue;
-            }
-        } else if *character == '/' {
-            if characters[index + 1] == '=' {
-                tokens.push(Token {
-                    line,
-                    block,
-                    token_type: TokenType::Operator(Operator::DivisionAndAssignment),
-                });
-                index += 2;
-                block += 2;
-                continue;
-            } else {
-                tokens.push(Token {
-                    line,
-                    block,
-                    token_type: TokenType::Operator(Operator::Division),
-                });
-                index += 1;
-                block += 1;
-                continue;
-            }
-        } else if *character == '%' {
-            if characters[index + 1] == '=' {
-                tokens.push(Token {
-                    line,
-                    block,
-                    token_type: TokenType::Operator(Operator::RemainderAndAssignment),
-                });
-                index += 2;
-                block += 2;
-                continue;
-            } else {
-                tokens.push(Token {
-                    line,
-                    block,
-                    token_type: TokenType::Operator(Operator::Remainder),
-                });
-                index += 1;
-                block += 1;
-                continue;
-            }
-        } else if *character == '=' {
-            if characters[index + 1] == '=' {
-                tokens.push(Token {
-                    line,
-                    block,
-                    token_type: TokenType::Operator(Operator::Equal),
-                });
-                index += 2;
-                block += 2;
-                continue;
-            } else {
-                tokens.push(Token {
-                    line,
-                    block,
-                    token_type: TokenType::Operator(Operator::Assignment),
-                });
-                index += 1;
-                block += 1;
-                continue;
-            }
-        } else if *character == '!' {
-            if characters[index + 1] == '=' {
-                tokens.push(Token {
-                    line,
-                    block,
-                    token_type: TokenType::Operator(Operator::NotEqual),
-                });
-                index += 2;
-                block += 2;
-                continue;
-            } else {
-                tokens.push(Token {
-                    line,
-                    block,
-                    token_type: TokenType::Operator(Operator::LogicalNegation),
-                });
-                index += 1;
-                block += 1;
-                continue;
-            }
-        } else if *character == '>' {
-            if characters[index + 1] == '=' {
-                tokens.push(Token {
-                    line,
-                    block,
-                    token_type: TokenType::Operator(Operator::BiggerOrEqual),
-                });
-                index += 2;
-                block += 2;
-                continue;
-            } else if characters[index + 1] == '>' {
-                if characters[index + 2] == '=' {
-                    tokens.push(Token {
-                        line,
-                        block,
-                        token_type: TokenType::Operator(Operator::RightShiftAndAssignment),
-                    });
-                    index += 3;
-                    block += 3;
-                    continue;
-                } else {
-                    tokens.push(Token {
-                        line,
-                        block,
-                        token_type: TokenType::Operator(Operator::RightShift),
-                    });
-                    index += 2;
-                    block += 2;
-                    continue;
-                }
-            } else {
-                tokens.push(Token {
-                    line,
-                    block,
-                    token_type: TokenType::Operator(Operator::Bigger),
-                });
-                index += 1;
-                block += 1;
-                continue;
+/// Joins adjacent string literals in `lexer`'s output if asked to.
+fn joined<'src>(
+    lexer: impl Iterator<Item = Result<Token<'src>, LexError>> + 'src,
+    concatenate_strings: bool,
+) -> Box<dyn Iterator<Item = Result<Token<'src>, LexError>> + 'src> {
+    if concatenate_strings {
+        Box::new(literal::concatenate(lexer))
+    } else {
+        Box::new(lexer)
+    }
+}
+
+fn print_token_table<'src>(
+    lexer: impl Iterator<Item = Result<Token<'src>, LexError>> + 'src,
+    concatenate_strings: bool,
+    file_path: &Path,
+) -> ExitCode {
+    let results = joined(lexer, concatenate_strings);
+    let mut tokens: Vec<Token> = vec![];
+    let mut has_errors = false;
+    for result in results {
+        match result {
+            Ok(token) => tokens.push(token),
+            Err(error) if error.is_warning() => {
+                eprintln!("{}: warning: {}", file_path.display(), error);
             }
-        } else if *character == '<' {
-            if characters[index + 1] == '=' {
-                tokens.push(Token {
-                    line,
-                    block,
-                    token_type: TokenType::Operator(Operator::SmallerOrEqual),
-                });
-                index += 2;
-                block += 2;
-                continue;
-            } else if characters[index + 1] == '>' {
-                if characters[index + 2] == '=' {
-                    tokens.push(Token {
-                        line,
-                        block,
-                        token_type: TokenType::Operator(Operator::LeftShiftAndAssignment),
-                    });
-                    index += 3;
-                    block += 3;
-                    continue;
-                } else {
-                    tokens.push(Token {
-                        line,
-                        block,
-                        token_type: TokenType::Operator(Operator::LeftShift),
-                    });
-                    index += 2;
-                    block += 2;
-                    continue;
-                }
-            } else {
-                tokens.push(Token {
-                    line,
-                    block,
-                    token_type: TokenType::Operator(Operator::Smaller),
-                });
-                index += 1;
-                block += 1;
-                continue;
+            Err(error) => {
+                eprintln!("{}: {}", file_path.display(), error);
+                has_errors = true;
             }
-        } else if *character == '&' {
-            if characters[index + 1] == '=' {
-                tokens.push(Token {
-                    line,
-                    block,
-                    token_type: TokenType::Operator(Operator::BitwiseAndAssignment),
-                });
-                index += 2;
-                block += 2;
-                continue;
-            } else if characters[index + 1] == '&' {
-                tokens.push(Token {
-                    line,
-                    block,
-                    token_type: TokenType::Operator(Operator::LogicalAnd),
-                });
-                index += 2;
-                block += 2;
-                continue;
-            } else {
-                tokens.push(Token {
-                    line,
-                    block,
-                    token_type: TokenType::Operator(Operator::BitwiseAnd),
-                });
-                index += 1;
-                block += 1;
+        }
+    }
+
+    let mut table = Builder::new();
+    table.push_record(["#", "Type", "Start", "End", "Content", "Value"]);
+    for (count, token) in (1..).zip(tokens) {
+        for (number, token) in numbered(count, &token) {
+            let (row, invalid) = token_row(number, token, file_path);
+            table.push_record(row);
+            has_errors |= invalid;
+        }
+    }
+    println!("{}", table.build().with(Style::sharp()));
+
+    if has_errors {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+/// Prints each token as soon as it is lexed, as a line of tab-separated
+/// columns, keeping nothing but the token being printed.
+fn print_token_rows<'src>(
+    lexer: impl Iterator<Item = Result<Token<'src>, LexError>> + 'src,
+    concatenate_strings: bool,
+    file_path: &Path,
+) -> ExitCode {
+    let mut stdout = io::stdout().lock();
+    let mut has_errors = false;
+    if writeln!(stdout, "#\tType\tStart\tEnd\tContent\tValue").is_err() {
+        return ExitCode::FAILURE;
+    }
+    for (count, result) in (1..).zip(joined(lexer, concatenate_strings)) {
+        let token = match result {
+            Ok(token) => token,
+            Err(error) if error.is_warning() => {
+                eprintln!("{}: warning: {}", file_path.display(), error);
                 continue;
             }
-        } else if *character == '|' {
-            if characters[index + 1] == '=' {
-                tokens.push(Token {
-                    line,
-                    block,
-                    token_type: TokenType::Operator(Operator::BitwiseOrAssignment),
-                });
-                index += 2;
-                block += 2;
-                continue;
-            } else if characters[index + 1] == '|' {
-                tokens.push(Token {
-                    line,
-                    block,
-                    token_type: TokenType::Operator(Operator::LogicalOr),
-                });
-                index += 2;
-                block += 2;
-                continue;
-            } else {
-                tokens.push(Token {
-                    line,
-                    block,
-                    token_type: TokenType::Operator(Operator::BitwiseOr),
-                });
-                index += 1;
-                block += 1;
+            Err(error) => {
+                eprintln!("{}: {}", file_path.display(), error);
+                has_errors = true;
                 continue;
             }
-        } else if *character == '^' {
-            if characters[index + 1] == '=' {
-                tokens.push(Token {
-                    line,
-                    block,
-                    token_type: TokenType::Operator(Operator::BitwiseExclusiveOrAndAssignment),
-                });
-                index += 2;
-                block += 2;
-                continue;
-            } else {
-                tokens.push(Token {
-                    line,
-                    block,
-                    token_type: TokenType::Operator(Operator::ExclusiveOr),
-                });
-                index += 1;
-                block += 1;
-                continue;
+        };
+        for (number, token) in numbered(count, &token) {
+            let (row, invalid) = token_row(number, token, file_path);
+            has_errors |= invalid;
+            // Comments and literals may hold tabs and newlines of their own.
+            let row: Vec<String> = row
+                .iter()
+                .map(|column| column.replace('\t', "\\t").replace('\n', "\\n"))
+                .collect();
+            if writeln!(stdout, "{}", row.join("\t")).is_err() {
+                // The reader went away, so there is no one left to print for.
+                return ExitCode::FAILURE;
             }
-        } else if *character == '~' {
-            tokens.push(Token {
-                line,
-                block,
-                token_type: TokenType::Operator(Operator::BitwiseNegation),
-            });
-            index += 1;
-            block += 1;
-            continue;
         }
+    }
 
-        if *character == '\'' {
-            if characters.get(index + 2).unwrap() == &'\'' {
-                tokens.push(Token {
-                    line,
-                    block,
-                    token_type: TokenType::Character(characters.get(index + 1).unwrap().to_owned()),
-                });
-            } else {
-                println!(
-                    "Error while tokenizing in line {} and block {}",
-                    line, block
-                );
-            }
-            block += 1;
-            index += 1;
-            continue;
+    if has_errors {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+/// The token numbered `count` followed by the tokens of its directive, if
+/// it is one, numbered after it.
+fn numbered<'a, 'src>(
+    count: usize,
+    token: &'a Token<'src>,
+) -> impl Iterator<Item = (String, &'a Token<'src>)> {
+    let nested = match &token.token_type {
+        TokenType::Directive(_, tokens) => tokens.as_slice(),
+        _ => &[],
+    };
+    std::iter::once((count.to_string(), token)).chain(
+        (1..)
+            .zip(nested)
+            .map(move |(index, token)| (format!("{}.{}", count, index), token)),
+    )
+}
+
+fn evaluate_expression(expression: &str, options: &CompileCommand) -> ExitCode {
+    let preprocessor = options.configure(Preprocessor::new("<command line>"));
+    match preprocessor.evaluate(expression) {
+        Ok(value) => {
+            println!("{}", value);
+            ExitCode::SUCCESS
         }
+        Err(error) => {
+            eprintln!("{}: {}", expression, error);
+            ExitCode::FAILURE
+        }
+    }
+}
 
-        let mut count = 0;
-        let mut word: String = String::new();
+fn print_unifdef(compile_command: &CompileCommand) -> ExitCode {
+    let file_path = compile_command.file.as_path();
+    let mut unifdef =
+        Unifdef::new(file_path).with_standard(compile_command.standard.unwrap_or_default());
+    for extension in &compile_command.extensions {
+        unifdef = unifdef.with_extension(*extension);
+    }
+    for (name, value) in &compile_command.definitions {
+        unifdef = match value {
+            Some(value) => unifdef.with_definition(name, value),
+            None => unifdef.with_undefinition(name),
+        };
+    }
+    let source = match read_source(file_path) {
+        Ok(source) => source,
+        Err(error) => {
+            eprintln!("{}: {}", file_path.display(), error);
+            return ExitCode::FAILURE;
+        }
+    };
+    match unifdef.process(&source) {
+        Ok(output) => {
+            print!("{}", output);
+            ExitCode::SUCCESS
+        }
+        Err(error) => {
+            eprintln!("{}: {}", error.file().display(), error);
+            ExitCode::FAILURE
+        }
+    }
+}
 
-        for character2 in characters[index..].iter() {
-            if *character2 == '\n'
-                || *character2 == ' '
-                || delimiters.get(String::from(*character2).as_str()).is_some()
-                || operators.get(String::from(*character2).as_str()).is_some()
-                || operators
-                    .get(
-                        characters[(index + count)..(index + count + 1)]
-                            .iter()
-                            .collect::<String>()
-                            .as_str(),
-                    )
-                    .is_some()
-                || operators
-                    .get(
-                        characters[(index + count)..(index + count + 2)]
-                            .iter()
-                            .collect::<String>()
-                            .as_str(),
-                    )
-                    .is_some()
-            {
-                break;
+/// Preprocesses every file to find what it includes, then prints the
+/// include graph in `format`.
+fn print_dependencies(commands: &[CompileCommand], format: &str) -> ExitCode {
+    let mut graph = DependencyGraph::new();
+    let mut has_errors = false;
+    for compile_command in commands {
+        let mut preprocessor = compile_command.configure(Preprocessor::new(&compile_command.file));
+        for result in preprocessor.by_ref() {
+            match result {
+                Ok(_) => {}
+                Err(error) if error.is_warning() => {}
+                Err(error) => {
+                    eprintln!("{}: {}", error.file().display(), error);
+                    has_errors = true;
+                }
             }
-            word.push(*character2);
-            count += 1;
         }
+        graph.add_source(&compile_command.file, preprocessor.includes());
+    }
+    for cycle in graph.cycles() {
+        let files: Vec<String> = cycle
+            .iter()
+            .map(|path| path.display().to_string())
+            .collect();
+        eprintln!("warning: include cycle {}", files.join(" -> "));
+    }
 
-        if let Some(keyword) = keywords.get(word.as_str()) {
-            tokens.push(Token {
-                line,
-                block,
-                token_type: TokenType::Keyword(*keyword),
-            });
-            index += count;
-            block += count;
-            continue;
-        } else if identifer_regex.is_match(&word) {
-            tokens.push(Token {
-                line,
-                block,
-                token_type: TokenType::Identifier(word),
-            });
-            index += count;
-            block += count;
-            continue;
-        } else if number_regex.is_match(&word) {
-            tokens.push(Token {
-                line,
-                block,
-                token_type: TokenType::Number(word),
-            });
-            index += count;
-            block += count;
-            continue;
-        } else {
-            println!(
-                "Error while tokenizing in line {} and block {}",
-                line, block
-            );
+    match format {
+        "json" => print!("{}", graph.to_json()),
+        "make" => print!("{}", graph.to_make()),
+        _ => print!("{}", graph.to_dot()),
+    }
+    if has_errors {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+/// Lexes the files on several threads into one symbol table, then prints
+/// how many identifiers and keywords they hold, how many distinct names
+/// those have and roughly how much memory the table takes.
+fn print_symbols(commands: &[CompileCommand]) -> ExitCode {
+    let interner = SharedInterner::new();
+    let next = AtomicUsize::new(0);
+    let threads = thread::available_parallelism().map_or(1, usize::from);
+    let mut scans: Vec<(usize, usize, Vec<String>)> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads.min(commands.len()))
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut scans = vec![];
+                    loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(compile_command) = commands.get(index) else {
+                            return scans;
+                        };
+                        let (occurrences, errors) = count_symbols(compile_command, &interner);
+                        scans.push((index, occurrences, errors));
+                    }
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().expect("scanning a file does not panic"))
+            .collect()
+    });
+    // Errors are reported in the order the files were given.
+    scans.sort_by_key(|(index, ..)| *index);
+    let mut occurrences = 0;
+    let mut has_errors = false;
+    for (_, count, errors) in scans {
+        occurrences += count;
+        for error in &errors {
+            eprintln!("{}", error);
         }
+        has_errors |= !errors.is_empty();
+    }
 
-        index += 1;
+    println!(
+        "{} identifiers and keywords in {} files",
+        occurrences,
+        commands.len()
+    );
+    println!(
+        "{} distinct names taking about {} bytes",
+        interner.len(),
+        interner.memory_usage()
+    );
+    if has_errors {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
     }
+}
 
-    let mut table = Builder::new();
-    table.push_record(["#", "Type", "Line", "Block", "Content"]);
-    let mut count = 1;
-    for token in tokens {
-        match token.token_type {
-            TokenType::Identifier(value) => table.push_record([
-                count.to_string(),
-                "Identifier".to_string(),
-                token.line.to_string(),
-                token.block.to_string(),
-                value,
-            ]),
-            TokenType::Comment(value) => table.push_record([
-                count.to_string(),
-                "Comment".to_string(),
-                token.line.to_string(),
-                token.block.to_string(),
-                value,
-            ]),
-            TokenType::Operator(value) => table.push_record([
-                count.to_string(),
-                TokenType::Operator(value).to_string(),
-                token.line.to_string(),
-                token.block.to_string(),
-                value.to_string(),
-            ]),
-            TokenType::Literal(value) => table.push_record([
-                count.to_string(),
-                "Literal".to_string(),
-                token.line.to_string(),
-                token.block.to_string(),
-                value,
-            ]),
-            TokenType::Keyword(value) => table.push_record([
-                count.to_string(),
-                TokenType::Keyword(value).to_string(),
-                token.line.to_string(),
-                token.block.to_string(),
-                value.to_string(),
-            ]),
-            TokenType::Number(value) => table.push_record([
-                count.to_string(),
-                "Number".to_string(),
-                token.line.to_string(),
-                token.block.to_string(),
-                value,
-            ]),
-            TokenType::Delimiter(value) => table.push_record([
-                count.to_string(),
-                TokenType::Delimiter(value).to_string(),
-                token.line.to_string(),
-                token.block.to_string(),
-                value.to_string(),
-            ]),
-            TokenType::Character(value) => table.push_record([
-                count.to_string(),
-                TokenType::Character(value).to_string(),
-                token.line.to_string(),
-                token.block.to_string(),
-                value.to_string(),
-            ]),
-        };
+/// Lexes a file as it is read, interning its names into `interner`, so that
+/// even a huge file takes little memory. Returns the number of identifiers
+/// and keywords found, counting those in directives, and the errors to
+/// report.
+fn count_symbols(
+    compile_command: &CompileCommand,
+    interner: &SharedInterner,
+) -> (usize, Vec<String>) {
+    let file_path = compile_command.file.as_path();
+    let reader: Box<dyn BufRead> = if file_path == Path::new("-") {
+        Box::new(io::stdin().lock())
+    } else {
+        match File::open(file_path) {
+            Ok(file) => Box::new(BufReader::new(file)),
+            Err(error) => {
+                let error = LexError::from(error);
+                return (0, vec![format!("{}: {}", file_path.display(), error)]);
+            }
+        }
+    };
+    let mut lexer = StreamingLexer::new(reader)
+        .with_standard(compile_command.standard.unwrap_or_default())
+        .with_interner(interner);
+    for extension in &compile_command.extensions {
+        lexer = lexer.with_extension(*extension);
+    }
+    let mut occurrences = 0;
+    let mut errors = vec![];
+    for result in lexer {
+        match result {
+            Ok(token) => {
+                let nested = match &token.token_type {
+                    TokenType::Directive(_, tokens) => tokens.as_slice(),
+                    _ => &[],
+                };
+                occurrences += std::iter::once(&token)
+                    .chain(nested)
+                    .filter(|token| token.symbol.is_some())
+                    .count();
+            }
+            Err(error) if error.is_warning() => {}
+            Err(error) => errors.push(format!("{}: {}", file_path.display(), error)),
+        }
+    }
+    (occurrences, errors)
+}
+
+fn print_preprocessed(preprocessor: Preprocessor) -> ExitCode {
+    let mut printer = Printer::new();
+    let mut has_errors = false;
+    for result in preprocessor {
+        match result {
+            Ok(token) => printer.print(&token),
+            Err(error) if error.is_warning() => {
+                eprintln!("{}: warning: {}", error.file().display(), error);
+            }
+            Err(error) => {
+                eprintln!("{}: {}", error.file().display(), error);
+                has_errors = true;
+            }
+        }
+    }
+    print!("{}", printer.finish());
 
-        count += 1;
+    if has_errors {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
     }
-    println!("{}", table.build().with(Style::sharp()));
+}
 
-    ExitCode::SUCCESS
+/// The columns of the row for `token`, and whether its value was invalid.
+fn token_row(number: String, token: &Token, file_path: &Path) -> ([String; 6], bool) {
+    let mut has_errors = false;
+    let value = match &token.token_type {
+        TokenType::Number(number) => match number.evaluate(DataModel::default()) {
+            Ok(constant) => constant.to_string(),
+            Err(reason) => {
+                let error = LexError::InvalidNumber {
+                    number: number.spelling.to_string(),
+                    reason,
+                    location: token.span.start,
+                };
+                eprintln!("{}: {}", file_path.display(), error);
+                has_errors = true;
+                String::new()
+            }
+        },
+        TokenType::Character(character) => character.value.to_string(),
+        _ => String::new(),
+    };
+    let row = [
+        number,
+        token.token_type.to_string(),
+        location(token.span.start),
+        location(token.span.end),
+        token
+            .digraph
+            .map_or_else(|| token.token_type.content(), str::to_string),
+        value,
+    ];
+    (row, has_errors)
+}
+
+fn location(location: Location) -> String {
+    format!("{}:{}", location.line, location.column)
 }
 
 fn help_text() -> String {
     "Name:
     c_scanner
 Synopsis:
-    c_scanner file_path
+    c_scanner [options] file_path|-
+    c_scanner eval [options] expression
+    c_scanner unifdef [options] file_path
+    c_scanner deps [options] file_path...
+    c_scanner symbols [options] file_path...
+    c_scanner explain [options] file_path:line:column
+    c_scanner [deps|symbols|unifdef] [options] --compile-commands file [file_path...]
 Description:
-    Prints the tokens found in the file specified
+    Prints the tokens found in the file specified as a table, with eval
+    the value of a preprocessor constant expression, with unifdef the file
+    with the conditionals decided by -D and -U removed, with deps the graph
+    of files the files include, with symbols how many distinct identifiers
+    the files use and the memory a table of them takes, or with explain the
+    macro expansions that turned what is written at a place into each
+    preprocessed token.
+
+    If the file is -, the tokens are read from standard input and printed
+    as they are lexed, one row per line with the columns separated by tabs.
 
-    --help display this help text and exit"
+    --help display this help text and exit
+    --concatenate-strings join adjacent string literals into one token
+    --std=<standard> lex as c89, c95, c99, c11, c17 (the default) or c23
+    --warn-newer-features warn about features newer than the selected standard
+    --ext=<extension> recognise gnu or msvc extensions (may be repeated)
+    --preprocess print the file as the compiler sees it after preprocessing
+    -I <directory> search the directory for included files (may be repeated)
+    -iquote <directory> search the directory for files included with quotes
+        before the -I directories (may be repeated)
+    -isystem <directory> search the directory for system headers after the
+        -I directories (may be repeated)
+    -D <name>[=<value>] define a macro, to 1 if no value is given
+    -U <name> undefine a macro, or with unifdef mark it as known undefined
+    --compile-commands <file> scan the files listed in a compile_commands.json
+        with the options of their commands, or only the file_paths given
+    --format=<format> print deps as dot (the default), json or make rules"
         .to_string()
 }
@@ -0,0 +1,873 @@
+use std::{borrow::Cow, fmt::Display};
+
+use crate::standard::Standard;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Radix {
+    Binary,
+    Octal,
+    Decimal,
+    Hexadecimal,
+}
+
+impl Radix {
+    pub fn value(&self) -> u32 {
+        match self {
+            Radix::Binary => 2,
+            Radix::Octal => 8,
+            Radix::Decimal => 10,
+            Radix::Hexadecimal => 16,
+        }
+    }
+}
+
+impl Display for Radix {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Radix::Binary => write!(f, "binary"),
+            Radix::Octal => write!(f, "octal"),
+            Radix::Decimal => write!(f, "decimal"),
+            Radix::Hexadecimal => write!(f, "hexadecimal"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IntegerSuffix {
+    None,
+    Unsigned,
+    Long,
+    UnsignedLong,
+    LongLong,
+    UnsignedLongLong,
+    /// C23 `wb`.
+    BitPrecise,
+    /// C23 `uwb`.
+    UnsignedBitPrecise,
+}
+
+impl Display for IntegerSuffix {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IntegerSuffix::None => write!(f, ""),
+            IntegerSuffix::Unsigned => write!(f, "u"),
+            IntegerSuffix::Long => write!(f, "l"),
+            IntegerSuffix::UnsignedLong => write!(f, "ul"),
+            IntegerSuffix::LongLong => write!(f, "ll"),
+            IntegerSuffix::UnsignedLongLong => write!(f, "ull"),
+            IntegerSuffix::BitPrecise => write!(f, "wb"),
+            IntegerSuffix::UnsignedBitPrecise => write!(f, "uwb"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FloatingSuffix {
+    None,
+    Float,
+    LongDouble,
+}
+
+impl Display for FloatingSuffix {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FloatingSuffix::None => write!(f, ""),
+            FloatingSuffix::Float => write!(f, "f"),
+            FloatingSuffix::LongDouble => write!(f, "l"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NumberKind {
+    Integer(IntegerSuffix),
+    Floating(FloatingSuffix),
+}
+
+/// A numeric constant, split into its parts.
+///
+/// Digit separators are removed from `integer`, `fraction` and `exponent`,
+/// and so is the `0x`/`0b` prefix, but the leading `0` of an octal constant
+/// is kept. The parts borrow from the spelling unless separators had to be
+/// taken out of them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Number<'src> {
+    pub spelling: Cow<'src, str>,
+    pub radix: Radix,
+    pub integer: Cow<'src, str>,
+    pub fraction: Option<Cow<'src, str>>,
+    /// The exponent including its sign, if it has one.
+    pub exponent: Option<Cow<'src, str>>,
+    pub kind: NumberKind,
+}
+
+impl Display for Number<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.spelling)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NumberError {
+    InvalidDigit { digit: char, radix: Radix },
+    MissingDigits,
+    MissingExponentDigits,
+    MissingBinaryExponent,
+    BinaryFloating,
+    InvalidSuffix(String),
+    MisplacedSeparator,
+    TooLarge,
+    OutOfRange,
+}
+
+impl Display for NumberError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NumberError::InvalidDigit { digit, radix } => {
+                write!(f, "invalid digit {:?} in {} constant", digit, radix)
+            }
+            NumberError::MissingDigits => write!(f, "no digits"),
+            NumberError::MissingExponentDigits => write!(f, "exponent has no digits"),
+            NumberError::MissingBinaryExponent => {
+                write!(f, "hexadecimal floating constant requires an exponent")
+            }
+            NumberError::BinaryFloating => write!(f, "binary constants cannot be floating"),
+            NumberError::InvalidSuffix(suffix) => write!(f, "invalid suffix \"{}\"", suffix),
+            NumberError::MisplacedSeparator => {
+                write!(f, "digit separator is not between two digits")
+            }
+            NumberError::TooLarge => {
+                write!(f, "integer constant is too large for any permitted type")
+            }
+            NumberError::OutOfRange => write!(f, "floating constant is out of range"),
+        }
+    }
+}
+
+impl std::error::Error for NumberError {}
+
+impl<'src> Number<'src> {
+    /// Parses the spelling of a preprocessing number as a numeric constant.
+    pub fn parse(spelling: &'src str) -> Result<Number<'src>, NumberError> {
+        let prefix = spelling.get(..2).unwrap_or_default();
+        let (mut radix, body) = if prefix.eq_ignore_ascii_case("0x") {
+            (Radix::Hexadecimal, &spelling[2..])
+        } else if prefix.eq_ignore_ascii_case("0b") {
+            (Radix::Binary, &spelling[2..])
+        } else {
+            (Radix::Decimal, spelling)
+        };
+
+        let mut scanner = Scanner { rest: body };
+        let is_digit = |character: char| match radix {
+            Radix::Hexadecimal => character.is_ascii_hexdigit(),
+            _ => character.is_ascii_digit(),
+        };
+        let integer = scanner.digits(is_digit)?;
+        let fraction = if scanner.eat('.') {
+            Some(scanner.digits(is_digit)?)
+        } else {
+            None
+        };
+        let exponent_markers = match radix {
+            Radix::Hexadecimal => ['p', 'P'],
+            _ => ['e', 'E'],
+        };
+        let exponent = if scanner.eat(exponent_markers[0]) || scanner.eat(exponent_markers[1]) {
+            let signed = scanner.rest;
+            let is_negative = scanner.eat('-');
+            if !is_negative {
+                scanner.eat('+');
+            }
+            let digits = scanner.digits(|character| character.is_ascii_digit())?;
+            if digits.is_empty() {
+                return Err(NumberError::MissingExponentDigits);
+            }
+            Some(match digits {
+                Cow::Borrowed(digits) if is_negative => Cow::Borrowed(&signed[..digits.len() + 1]),
+                Cow::Owned(digits) if is_negative => Cow::Owned(format!("-{}", digits)),
+                digits => digits,
+            })
+        } else {
+            None
+        };
+        let suffix = scanner.rest;
+
+        if integer.is_empty() && fraction.as_deref().unwrap_or_default().is_empty() {
+            return Err(NumberError::MissingDigits);
+        }
+
+        let is_floating = fraction.is_some() || exponent.is_some();
+        let kind = if is_floating {
+            match radix {
+                Radix::Binary => return Err(NumberError::BinaryFloating),
+                Radix::Hexadecimal if exponent.is_none() => {
+                    return Err(NumberError::MissingBinaryExponent)
+                }
+                _ => {}
+            }
+            NumberKind::Floating(floating_suffix(suffix)?)
+        } else {
+            if radix == Radix::Decimal && integer.starts_with('0') {
+                radix = Radix::Octal;
+            }
+            let limit = match radix {
+                Radix::Binary => '1',
+                Radix::Octal => '7',
+                _ => '9',
+            };
+            if let Some(digit) = integer
+                .chars()
+                .find(|digit| digit.is_ascii_digit() && *digit > limit)
+            {
+                return Err(NumberError::InvalidDigit { digit, radix });
+            }
+            NumberKind::Integer(integer_suffix(suffix)?)
+        };
+
+        Ok(Number {
+            spelling: Cow::Borrowed(spelling),
+            radix,
+            integer,
+            fraction,
+            exponent,
+            kind,
+        })
+    }
+
+    /// The newest feature the constant relies on, with the standard that
+    /// introduced it, if that is later than C89.
+    pub fn feature(&self) -> Option<(&'static str, Standard)> {
+        if self.spelling.contains('\'') {
+            return Some(("digit separators", Standard::C23));
+        }
+        if self.radix == Radix::Binary {
+            return Some(("binary constants", Standard::C23));
+        }
+        match self.kind {
+            NumberKind::Integer(IntegerSuffix::BitPrecise | IntegerSuffix::UnsignedBitPrecise) => {
+                Some(("bit-precise integer constants", Standard::C23))
+            }
+            NumberKind::Integer(IntegerSuffix::LongLong | IntegerSuffix::UnsignedLongLong) => {
+                Some(("long long constants", Standard::C99))
+            }
+            NumberKind::Floating(_) if self.radix == Radix::Hexadecimal => {
+                Some(("hexadecimal floating constants", Standard::C99))
+            }
+            _ => None,
+        }
+    }
+
+    /// The same constant with its spelling and parts copied out of the
+    /// source.
+    pub fn into_owned(self) -> Number<'static> {
+        Number {
+            spelling: Cow::Owned(self.spelling.into_owned()),
+            radix: self.radix,
+            integer: Cow::Owned(self.integer.into_owned()),
+            fraction: self
+                .fraction
+                .map(|fraction| Cow::Owned(fraction.into_owned())),
+            exponent: self
+                .exponent
+                .map(|exponent| Cow::Owned(exponent.into_owned())),
+            kind: self.kind,
+        }
+    }
+}
+
+struct Scanner<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Scanner<'a> {
+    fn eat(&mut self, expected: char) -> bool {
+        match self.rest.strip_prefix(expected) {
+            Some(rest) => {
+                self.rest = rest;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Takes a run of digits, dropping the digit separators between them.
+    /// Only a run that has separators needs copying.
+    fn digits(&mut self, is_digit: impl Fn(char) -> bool) -> Result<Cow<'a, str>, NumberError> {
+        let mut end = self.rest.len();
+        let mut has_separators = false;
+        let mut characters = self.rest.char_indices().peekable();
+        while let Some((index, character)) = characters.next() {
+            if is_digit(character) {
+                continue;
+            } else if character == '\'' {
+                let is_next_digit = characters
+                    .peek()
+                    .is_some_and(|(_, character)| is_digit(*character));
+                if index == 0 || !is_next_digit {
+                    return Err(NumberError::MisplacedSeparator);
+                }
+                has_separators = true;
+            } else {
+                end = index;
+                break;
+            }
+        }
+        let (digits, rest) = self.rest.split_at(end);
+        self.rest = rest;
+        Ok(if has_separators {
+            Cow::Owned(digits.replace('\'', ""))
+        } else {
+            Cow::Borrowed(digits)
+        })
+    }
+}
+
+fn integer_suffix(suffix: &str) -> Result<IntegerSuffix, NumberError> {
+    let mut is_unsigned = false;
+    let mut size: Option<&str> = None;
+    let mut rest = suffix;
+    while !rest.is_empty() {
+        if let (false, Some(stripped)) = (is_unsigned, rest.strip_prefix(['u', 'U'])) {
+            is_unsigned = true;
+            rest = stripped;
+            continue;
+        }
+        if size.is_some() {
+            return Err(NumberError::InvalidSuffix(suffix.to_string()));
+        }
+        let Some(found) = ["ll", "LL", "l", "L", "wb", "WB"]
+            .into_iter()
+            .find(|candidate| rest.starts_with(candidate))
+        else {
+            return Err(NumberError::InvalidSuffix(suffix.to_string()));
+        };
+        size = Some(found);
+        rest = &rest[found.len()..];
+    }
+    let size = size.map(str::to_ascii_lowercase);
+    Ok(match (is_unsigned, size.as_deref()) {
+        (false, None) => IntegerSuffix::None,
+        (true, None) => IntegerSuffix::Unsigned,
+        (false, Some("l")) => IntegerSuffix::Long,
+        (true, Some("l")) => IntegerSuffix::UnsignedLong,
+        (false, Some("ll")) => IntegerSuffix::LongLong,
+        (true, Some("ll")) => IntegerSuffix::UnsignedLongLong,
+        (false, _) => IntegerSuffix::BitPrecise,
+        (true, _) => IntegerSuffix::UnsignedBitPrecise,
+    })
+}
+
+fn floating_suffix(suffix: &str) -> Result<FloatingSuffix, NumberError> {
+    match suffix {
+        "" => Ok(FloatingSuffix::None),
+        "f" | "F" => Ok(FloatingSuffix::Float),
+        "l" | "L" => Ok(FloatingSuffix::LongDouble),
+        _ => Err(NumberError::InvalidSuffix(suffix.to_string())),
+    }
+}
+
+/// The widths in bits of the standard integer types, which decide the type
+/// an integer constant gets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DataModel {
+    pub int: u32,
+    pub long: u32,
+    pub long_long: u32,
+}
+
+impl DataModel {
+    /// 64-bit Linux and macOS.
+    pub const LP64: DataModel = DataModel {
+        int: 32,
+        long: 64,
+        long_long: 64,
+    };
+    /// 64-bit Windows.
+    pub const LLP64: DataModel = DataModel {
+        int: 32,
+        long: 32,
+        long_long: 64,
+    };
+    /// Most 32-bit targets.
+    pub const ILP32: DataModel = DataModel {
+        int: 32,
+        long: 32,
+        long_long: 64,
+    };
+}
+
+impl Default for DataModel {
+    fn default() -> Self {
+        DataModel::LP64
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CType {
+    UnsignedChar,
+    UnsignedShort,
+    Int,
+    UnsignedInt,
+    Long,
+    UnsignedLong,
+    LongLong,
+    UnsignedLongLong,
+    BitInt(u32),
+    UnsignedBitInt(u32),
+    Float,
+    Double,
+    LongDouble,
+}
+
+impl CType {
+    pub fn is_unsigned(&self) -> bool {
+        matches!(
+            self,
+            CType::UnsignedChar
+                | CType::UnsignedShort
+                | CType::UnsignedInt
+                | CType::UnsignedLong
+                | CType::UnsignedLongLong
+                | CType::UnsignedBitInt(_)
+        )
+    }
+
+    /// The largest value an integer type can hold.
+    fn max(&self, data_model: DataModel) -> u128 {
+        let width = match self {
+            CType::UnsignedChar => 8,
+            CType::UnsignedShort => 16,
+            CType::Int | CType::UnsignedInt => data_model.int,
+            CType::Long | CType::UnsignedLong => data_model.long,
+            CType::LongLong | CType::UnsignedLongLong => data_model.long_long,
+            CType::BitInt(width) | CType::UnsignedBitInt(width) => *width,
+            CType::Float | CType::Double | CType::LongDouble => return 0,
+        };
+        let width = if self.is_unsigned() { width } else { width - 1 };
+        u128::MAX >> (128 - width)
+    }
+}
+
+impl Display for CType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CType::UnsignedChar => write!(f, "unsigned char"),
+            CType::UnsignedShort => write!(f, "unsigned short"),
+            CType::Int => write!(f, "int"),
+            CType::UnsignedInt => write!(f, "unsigned int"),
+            CType::Long => write!(f, "long"),
+            CType::UnsignedLong => write!(f, "unsigned long"),
+            CType::LongLong => write!(f, "long long"),
+            CType::UnsignedLongLong => write!(f, "unsigned long long"),
+            CType::BitInt(width) => write!(f, "_BitInt({})", width),
+            CType::UnsignedBitInt(width) => write!(f, "unsigned _BitInt({})", width),
+            CType::Float => write!(f, "float"),
+            CType::Double => write!(f, "double"),
+            CType::LongDouble => write!(f, "long double"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Value {
+    Signed(i128),
+    Unsigned(u128),
+    /// `long double` constants are evaluated with `f64` precision too.
+    Floating(f64),
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Signed(value) => write!(f, "{}", value),
+            Value::Unsigned(value) => write!(f, "{}", value),
+            Value::Floating(value) => write!(f, "{:?}", value),
+        }
+    }
+}
+
+/// The value of a numeric constant together with its C type.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Constant {
+    pub value: Value,
+    pub c_type: CType,
+}
+
+impl Display for Constant {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({})", self.value, self.c_type)
+    }
+}
+
+impl Number<'_> {
+    /// Works out the value of the constant and the type C gives it, following
+    /// the C17 rules for picking the first integer type the value fits in.
+    pub fn evaluate(&self, data_model: DataModel) -> Result<Constant, NumberError> {
+        match self.kind {
+            NumberKind::Integer(suffix) => self.evaluate_integer(suffix, data_model),
+            NumberKind::Floating(suffix) => self.evaluate_floating(suffix),
+        }
+    }
+
+    fn evaluate_integer(
+        &self,
+        suffix: IntegerSuffix,
+        data_model: DataModel,
+    ) -> Result<Constant, NumberError> {
+        let value = u128::from_str_radix(&self.integer, self.radix.value())
+            .map_err(|_| NumberError::TooLarge)?;
+        let is_decimal = self.radix == Radix::Decimal;
+        let candidates: &[CType] = match (suffix, is_decimal) {
+            (IntegerSuffix::None, true) => &[CType::Int, CType::Long, CType::LongLong],
+            (IntegerSuffix::None, false) => &[
+                CType::Int,
+                CType::UnsignedInt,
+                CType::Long,
+                CType::UnsignedLong,
+                CType::LongLong,
+                CType::UnsignedLongLong,
+            ],
+            (IntegerSuffix::Unsigned, _) => &[
+                CType::UnsignedInt,
+                CType::UnsignedLong,
+                CType::UnsignedLongLong,
+            ],
+            (IntegerSuffix::Long, true) => &[CType::Long, CType::LongLong],
+            (IntegerSuffix::Long, false) => &[
+                CType::Long,
+                CType::UnsignedLong,
+                CType::LongLong,
+                CType::UnsignedLongLong,
+            ],
+            (IntegerSuffix::UnsignedLong, _) => &[CType::UnsignedLong, CType::UnsignedLongLong],
+            (IntegerSuffix::LongLong, true) => &[CType::LongLong],
+            (IntegerSuffix::LongLong, false) => &[CType::LongLong, CType::UnsignedLongLong],
+            (IntegerSuffix::UnsignedLongLong, _) => &[CType::UnsignedLongLong],
+            (IntegerSuffix::BitPrecise, _) => {
+                let width = (u128::BITS - value.leading_zeros() + 1).max(2);
+                if width > u128::BITS {
+                    return Err(NumberError::TooLarge);
+                }
+                &[CType::BitInt(width)]
+            }
+            (IntegerSuffix::UnsignedBitPrecise, _) => &[CType::UnsignedBitInt(
+                (u128::BITS - value.leading_zeros()).max(1),
+            )],
+        };
+        let c_type = candidates
+            .iter()
+            .find(|c_type| value <= c_type.max(data_model))
+            .ok_or(NumberError::TooLarge)?;
+        let value = if c_type.is_unsigned() {
+            Value::Unsigned(value)
+        } else {
+            // Fits, because the signed types are at most 128 bits wide.
+            Value::Signed(value as i128)
+        };
+        Ok(Constant {
+            value,
+            c_type: *c_type,
+        })
+    }
+
+    fn evaluate_floating(&self, suffix: FloatingSuffix) -> Result<Constant, NumberError> {
+        let fraction = self.fraction.as_deref().unwrap_or_default();
+        let exponent: i32 = match &self.exponent {
+            // Exponents too large for an `i32` overflow every floating type.
+            Some(exponent) => exponent.parse().unwrap_or(if exponent.starts_with('-') {
+                i32::MIN
+            } else {
+                i32::MAX
+            }),
+            None => 0,
+        };
+        let value = if self.radix == Radix::Hexadecimal {
+            hexadecimal_floating(&self.integer, fraction, exponent)
+        } else {
+            let integer = if self.integer.is_empty() {
+                "0"
+            } else {
+                &self.integer
+            };
+            format!("{}.{}e{}", integer, fraction, exponent)
+                .parse()
+                .map_err(|_| NumberError::OutOfRange)?
+        };
+        let c_type = match suffix {
+            FloatingSuffix::None => CType::Double,
+            FloatingSuffix::Float => CType::Float,
+            FloatingSuffix::LongDouble => CType::LongDouble,
+        };
+        let is_out_of_range = match c_type {
+            CType::Float => (value as f32).is_infinite(),
+            _ => value.is_infinite(),
+        };
+        if is_out_of_range {
+            return Err(NumberError::OutOfRange);
+        }
+        Ok(Constant {
+            value: Value::Floating(value),
+            c_type,
+        })
+    }
+}
+
+/// Converts the digits of a hexadecimal floating constant to the nearest
+/// `f64`, rounding once however many digits there are.
+///
+/// The digits are gathered into an integer mantissa; digits that no longer fit
+/// only matter as a sticky bit for rounding. The mantissa is then rounded to
+/// the 53 bits a double holds, or fewer for subnormals, and scaled by powers
+/// of two that are each exact on their own.
+fn hexadecimal_floating(integer: &str, fraction: &str, exponent: i32) -> f64 {
+    let mut mantissa: u128 = 0;
+    let mut scale = exponent as i64;
+    let mut sticky = false;
+    for (index, digit) in integer.chars().chain(fraction.chars()).enumerate() {
+        let Some(digit) = digit.to_digit(16) else {
+            continue;
+        };
+        let is_fraction = index >= integer.len();
+        if mantissa >> (u128::BITS - 4) == 0 {
+            mantissa = mantissa << 4 | digit as u128;
+            if is_fraction {
+                scale -= 4;
+            }
+        } else {
+            sticky |= digit != 0;
+            if !is_fraction {
+                scale += 4;
+            }
+        }
+    }
+    if mantissa == 0 {
+        return 0.0;
+    }
+    let bits = (u128::BITS - mantissa.leading_zeros()) as i64;
+    // The exponent of the leading bit, as in `1.xxx * 2^binary_exponent`.
+    let binary_exponent = bits - 1 + scale;
+    if binary_exponent > f64::MAX_EXP as i64 {
+        return f64::INFINITY;
+    }
+    let precision = if binary_exponent >= f64::MIN_EXP as i64 - 1 {
+        f64::MANTISSA_DIGITS as i64
+    } else {
+        // Subnormals lose a bit of precision for each step below the minimum.
+        f64::MANTISSA_DIGITS as i64 + binary_exponent - (f64::MIN_EXP as i64 - 1)
+    };
+    let shift = bits - precision;
+    let rounded = if shift <= 0 {
+        mantissa << -shift
+    } else {
+        let kept = if shift < u128::BITS as i64 {
+            mantissa >> shift
+        } else {
+            0
+        };
+        let half = shift - 1;
+        let is_half_set = half < u128::BITS as i64 && mantissa >> half & 1 == 1;
+        let below_half = if half >= u128::BITS as i64 {
+            mantissa
+        } else {
+            mantissa & ((1 << half) - 1)
+        };
+        let is_above_half = below_half != 0 || sticky;
+        if is_half_set && (is_above_half || kept & 1 == 1) {
+            kept + 1
+        } else {
+            kept
+        }
+    };
+    // At most 54 bits, so the conversion is exact.
+    let mut value = rounded as f64;
+    let mut scale = scale + shift;
+    while scale != 0 {
+        let step = scale.clamp(f64::MIN_EXP as i64 - 1, f64::MAX_EXP as i64 - 1);
+        value *= 2f64.powi(step as i32);
+        scale -= step;
+    }
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn evaluate(spelling: &str, data_model: DataModel) -> Result<Constant, NumberError> {
+        Number::parse(spelling)?.evaluate(data_model)
+    }
+
+    fn c_type(spelling: &str, data_model: DataModel) -> CType {
+        match evaluate(spelling, data_model) {
+            Ok(constant) => constant.c_type,
+            Err(error) => panic!("{}: {}", spelling, error),
+        }
+    }
+
+    #[test]
+    fn decimal_constants_only_take_signed_types() {
+        for (spelling, lp64, llp64) in [
+            ("2147483647", CType::Int, CType::Int),
+            ("2147483648", CType::Long, CType::LongLong),
+            ("9223372036854775807", CType::Long, CType::LongLong),
+            ("2147483648l", CType::Long, CType::LongLong),
+            ("1ll", CType::LongLong, CType::LongLong),
+        ] {
+            assert_eq!(c_type(spelling, DataModel::LP64), lp64, "{}", spelling);
+            assert_eq!(c_type(spelling, DataModel::LLP64), llp64, "{}", spelling);
+        }
+        for spelling in ["9223372036854775808", "9223372036854775808ll"] {
+            assert_eq!(
+                evaluate(spelling, DataModel::LP64),
+                Err(NumberError::TooLarge),
+                "{}",
+                spelling
+            );
+        }
+    }
+
+    #[test]
+    fn hexadecimal_and_octal_constants_may_take_unsigned_types() {
+        for (spelling, lp64, llp64) in [
+            ("0x7fffffff", CType::Int, CType::Int),
+            ("0x80000000", CType::UnsignedInt, CType::UnsignedInt),
+            ("017777777777", CType::Int, CType::Int),
+            ("020000000000", CType::UnsignedInt, CType::UnsignedInt),
+            (
+                "0b11111111111111111111111111111111",
+                CType::UnsignedInt,
+                CType::UnsignedInt,
+            ),
+            ("0x100000000", CType::Long, CType::LongLong),
+            (
+                "0xffffffffffffffff",
+                CType::UnsignedLong,
+                CType::UnsignedLongLong,
+            ),
+            ("0x80000000l", CType::Long, CType::UnsignedLong),
+            (
+                "0x8000000000000000ll",
+                CType::UnsignedLongLong,
+                CType::UnsignedLongLong,
+            ),
+        ] {
+            assert_eq!(c_type(spelling, DataModel::LP64), lp64, "{}", spelling);
+            assert_eq!(c_type(spelling, DataModel::LLP64), llp64, "{}", spelling);
+        }
+        assert_eq!(
+            evaluate("0x10000000000000000", DataModel::LP64),
+            Err(NumberError::TooLarge)
+        );
+    }
+
+    #[test]
+    fn suffixes_narrow_the_candidate_types() {
+        for (spelling, expected) in [
+            ("1u", CType::UnsignedInt),
+            ("4294967296U", CType::UnsignedLong),
+            ("1l", CType::Long),
+            ("1ul", CType::UnsignedLong),
+            ("1LU", CType::UnsignedLong),
+            ("1LL", CType::LongLong),
+            ("1ull", CType::UnsignedLongLong),
+            ("1llu", CType::UnsignedLongLong),
+            ("0wb", CType::BitInt(2)),
+            ("255wb", CType::BitInt(9)),
+            ("255uwb", CType::UnsignedBitInt(8)),
+            ("1.0", CType::Double),
+            ("1.0f", CType::Float),
+            ("1.0L", CType::LongDouble),
+        ] {
+            assert_eq!(c_type(spelling, DataModel::LP64), expected, "{}", spelling);
+        }
+        for spelling in ["1lul", "1uu", "1lL", "1.0u", "1f"] {
+            assert!(
+                matches!(Number::parse(spelling), Err(NumberError::InvalidSuffix(_))),
+                "{}",
+                spelling
+            );
+        }
+    }
+
+    #[test]
+    fn values_and_malformed_constants() {
+        for (spelling, expected) in [
+            ("0x1F", Value::Signed(31)),
+            ("017", Value::Signed(15)),
+            ("0b101", Value::Signed(5)),
+            ("1'000'000", Value::Signed(1_000_000)),
+            ("0x1p3", Value::Floating(8.0)),
+            ("0x.8p1", Value::Floating(1.0)),
+            (".5", Value::Floating(0.5)),
+            ("0x1p-1074", Value::Floating(f64::from_bits(1))),
+            ("0x.8p-1073", Value::Floating(f64::from_bits(1))),
+            ("0x1p-1075", Value::Floating(0.0)),
+            ("0x1.8p-1075", Value::Floating(f64::from_bits(1))),
+            (
+                "0x1.fffffffffffffp-1023",
+                Value::Floating(f64::MIN_POSITIVE),
+            ),
+            ("0x0.1p1024", Value::Floating(2f64.powi(1020))),
+            ("0x1000p-1080", Value::Floating(f64::from_bits(1 << 6))),
+            ("0x1.fffffffffffffp1023", Value::Floating(f64::MAX)),
+            ("0x1.0000000000000000001p0", Value::Floating(1.0)),
+            ("0x1.00000000000008p0", Value::Floating(1.0)),
+            (
+                "0x1.00000000000018p0",
+                Value::Floating(1.0 + 2.0 * f64::EPSILON),
+            ),
+            (
+                "0x1.00000000000008000000001p0",
+                Value::Floating(1.0 + f64::EPSILON),
+            ),
+            (
+                "0x100000000000000000000000000000000p-128",
+                Value::Floating(1.0),
+            ),
+            ("1e-9", Value::Floating(1e-9)),
+        ] {
+            let value = evaluate(spelling, DataModel::LP64).map(|constant| constant.value);
+            assert_eq!(value, Ok(expected), "{}", spelling);
+        }
+        for (spelling, expected) in [
+            (
+                "08",
+                NumberError::InvalidDigit {
+                    digit: '8',
+                    radix: Radix::Octal,
+                },
+            ),
+            (
+                "0b2",
+                NumberError::InvalidDigit {
+                    digit: '2',
+                    radix: Radix::Binary,
+                },
+            ),
+            ("0x", NumberError::MissingDigits),
+            ("1e", NumberError::MissingExponentDigits),
+            ("0x1.0", NumberError::MissingBinaryExponent),
+            ("0b1.0", NumberError::BinaryFloating),
+            ("1''0", NumberError::MisplacedSeparator),
+        ] {
+            assert_eq!(Number::parse(spelling), Err(expected), "{}", spelling);
+        }
+        assert_eq!(
+            evaluate("1e999", DataModel::LP64),
+            Err(NumberError::OutOfRange)
+        );
+        assert_eq!(
+            evaluate("0x0.1p1028", DataModel::LP64),
+            Err(NumberError::OutOfRange)
+        );
+        assert_eq!(
+            evaluate("0x1.fffffffffffff8p1023", DataModel::LP64),
+            Err(NumberError::OutOfRange)
+        );
+        assert_eq!(
+            evaluate("1e39f", DataModel::LP64),
+            Err(NumberError::OutOfRange)
+        );
+    }
+}
@@ -0,0 +1,85 @@
+/// A map over a fixed set of strings that finds a key with one hash and one
+/// comparison.
+///
+/// Building it searches for a seed under which no two keys share a slot, so
+/// there are no collisions to resolve when looking up. It suits the small
+/// tables of keywords and directive names, which are looked up for every
+/// word in a file.
+pub struct PerfectHash<V: 'static> {
+    seed: u64,
+    /// For each slot, the index in `entries` plus one, or zero if empty.
+    slots: Box<[u8]>,
+    entries: Vec<(&'static str, V)>,
+    /// The lengths of the shortest and longest key, so that other words
+    /// are turned away before they are hashed.
+    lengths: (usize, usize),
+}
+
+impl<V: 'static> PerfectHash<V> {
+    /// Builds the map. Panics if there are more than 255 entries, which no
+    /// table of C keywords comes near.
+    pub fn new<const N: usize>(entries: [(&'static str, V); N]) -> Self {
+        assert!(N < u8::MAX as usize, "too many keys for a perfect hash");
+        let lengths = entries
+            .iter()
+            .fold((usize::MAX, 0), |(shortest, longest), (key, _)| {
+                (shortest.min(key.len()), longest.max(key.len()))
+            });
+        // With about the square of the keys as slots, a random seed works
+        // more often than not.
+        let mut size = (N * N).next_power_of_two().max(16);
+        let mut seed = 0;
+        let slots = loop {
+            if let Some(slots) = place(&entries, seed, size) {
+                break slots;
+            }
+            seed += 1;
+            if seed % 64 == 0 {
+                size *= 2;
+            }
+        };
+        PerfectHash {
+            seed,
+            slots,
+            entries: entries.into(),
+            lengths,
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&V> {
+        self.get_key_value(key).map(|(_, value)| value)
+    }
+
+    /// The entry for `key`, with the key as it is stored.
+    pub fn get_key_value(&self, key: &str) -> Option<(&'static str, &V)> {
+        if key.len() < self.lengths.0 || key.len() > self.lengths.1 {
+            return None;
+        }
+        let slot = self.slots[slot(key, self.seed, self.slots.len())];
+        let (stored, value) = self.entries.get(usize::from(slot).checked_sub(1)?)?;
+        (*stored == key).then_some((*stored, value))
+    }
+}
+
+/// Assigns every key a slot under `seed`, or returns `None` if two collide.
+fn place<V>(entries: &[(&'static str, V)], seed: u64, size: usize) -> Option<Box<[u8]>> {
+    let mut slots = vec![0; size].into_boxed_slice();
+    for (index, (key, _)) in entries.iter().enumerate() {
+        let slot = &mut slots[slot(key, seed, size)];
+        if *slot != 0 {
+            return None;
+        }
+        *slot = index as u8 + 1;
+    }
+    Some(slots)
+}
+
+/// FNV-1a, started from `seed`, reduced to a slot of a power-of-two table.
+fn slot(key: &str, seed: u64, size: usize) -> usize {
+    let mut hash = 0xcbf2_9ce4_8422_2325 ^ seed.wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    for byte in key.bytes() {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    (hash >> 32) as usize & (size - 1)
+}
@@ -0,0 +1,2183 @@
+use std::{
+    borrow::Cow,
+    cell::RefCell,
+    collections::{BTreeSet, HashMap, HashSet, VecDeque},
+    fmt::Display,
+    fs, mem,
+    path::{Path, PathBuf},
+    rc::Rc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    error::{ExpressionError, LexError, PreprocessError},
+    expression::{self, Value},
+    lexer::{
+        is_identifier_continue, normalize_identifier, read_source, replaces_trigraphs,
+        splice_lines, Lexer,
+    },
+    standard::{Extension, Standard},
+    symbol::{Interner, Symbol},
+    tokens::{Delimiter, Directive, HeaderName, Location, Span, Token, TokenType, PUNCTUATORS},
+};
+
+/// How deeply includes may nest before we assume they recurse forever.
+const MAX_INCLUDE_DEPTH: usize = 200;
+
+/// A token as the compiler sees it after preprocessing.
+#[derive(Clone)]
+pub struct PreprocessedToken {
+    pub token: Token<'static>,
+    /// The token as written, or as built by `#` and `##`.
+    pub spelling: String,
+    /// The file and line the token counts as being on. For the result of a
+    /// macro expansion that is where the macro was used.
+    pub file: Rc<str>,
+    pub line: usize,
+    pub whitespace_before: bool,
+    /// Where the token was written: in a file, or in the body of the macro
+    /// it came from.
+    pub written: Position,
+    /// The macro expansions the token came out of, innermost first.
+    pub expansion: Vec<ExpansionStep>,
+    /// The macros that must not expand this token again.
+    hide_set: BTreeSet<Symbol>,
+}
+
+/// A place in a file as it is on disk, whatever `#line` says.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Position {
+    pub file: Rc<str>,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Position {
+    fn built_in() -> Self {
+        Position {
+            file: Rc::from("<built-in>"),
+            line: 1,
+            column: 1,
+        }
+    }
+}
+
+impl Display for Position {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.line, self.column)
+    }
+}
+
+/// How a token got into the expansion of a macro.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Origin {
+    /// The token is in the macro's body.
+    Body,
+    /// The token is part of the argument for the named parameter.
+    Argument(String),
+    /// `#` made the token from the argument for the named parameter.
+    Stringified(String),
+    /// `##` made the token.
+    Pasted,
+}
+
+/// One macro expansion a token came out of.
+#[derive(Debug, Clone)]
+pub struct ExpansionStep {
+    pub name: String,
+    /// Where the macro's name was when it was expanded.
+    pub invocation: Position,
+    /// Where the macro's name is in its `#define`.
+    pub definition: Position,
+    pub origin: Origin,
+}
+
+impl Display for ExpansionStep {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.origin {
+            Origin::Body => write!(f, "in the body of {}", self.name)?,
+            Origin::Argument(parameter) => {
+                write!(f, "substituted for {} in {}", parameter, self.name)?
+            }
+            Origin::Stringified(parameter) => write!(f, "made by #{} in {}", parameter, self.name)?,
+            Origin::Pasted => write!(f, "made by ## in {}", self.name)?,
+        }
+        write!(
+            f,
+            " (defined at {}), expanded at {}",
+            self.definition, self.invocation
+        )
+    }
+}
+
+impl PreprocessedToken {
+    /// Whether the token is the punctuator `punctuator`, however it is
+    /// written.
+    fn is_punctuator(&self, punctuator: &str) -> bool {
+        match self.token.digraph {
+            Some(_) => self.token.token_type.content() == punctuator,
+            None => self.spelling == punctuator,
+        }
+    }
+
+    /// The token's spelling with universal character names decoded, for
+    /// messages.
+    fn name(&self) -> Cow<'_, str> {
+        normalize_identifier(&self.spelling)
+    }
+
+    /// The interned name of an identifier or keyword, which is how macro
+    /// and parameter names are compared.
+    fn symbol(&self) -> Option<Symbol> {
+        self.token.symbol.filter(|_| self.is_identifier())
+    }
+
+    /// Whether the token can name a macro. Keywords can, too.
+    fn is_identifier(&self) -> bool {
+        matches!(
+            self.token.token_type,
+            TokenType::Identifier(_) | TokenType::Keyword(_)
+        )
+    }
+}
+
+#[derive(Clone)]
+struct Macro {
+    /// `None` for object-like macros. A variadic macro's last parameter
+    /// collects the variable arguments.
+    parameters: Option<Vec<Symbol>>,
+    variadic: bool,
+    body: Vec<PreprocessedToken>,
+    definition: Position,
+}
+
+impl Macro {
+    fn parameter(&self, token: &PreprocessedToken) -> Option<usize> {
+        let name = token.symbol()?;
+        let parameters = self.parameters.as_ref()?;
+        parameters.iter().position(|parameter| *parameter == name)
+    }
+
+    fn is_variable_arguments(&self, token: &PreprocessedToken) -> bool {
+        let count = self.parameters.as_ref().map_or(0, Vec::len);
+        self.variadic && self.parameter(token) == Some(count - 1)
+    }
+
+    /// Whether redefining `self` as `other` changes nothing, which the
+    /// standard allows without complaint.
+    fn is_equivalent(&self, other: &Macro) -> bool {
+        self.parameters == other.parameters
+            && self.variadic == other.variadic
+            && self.body.len() == other.body.len()
+            && self
+                .body
+                .iter()
+                .zip(&other.body)
+                .enumerate()
+                .all(|(index, (first, second))| {
+                    first.spelling == second.spelling
+                        && (index == 0 || first.whitespace_before == second.whitespace_before)
+                })
+    }
+}
+
+struct Conditional {
+    /// Whether the group around this conditional is kept.
+    parent_active: bool,
+    active: bool,
+    /// Whether one of the branches so far was taken.
+    taken: bool,
+    seen_else: bool,
+}
+
+/// A file being read, with the state `#line` and conditionals keep for it.
+struct Source {
+    path: PathBuf,
+    /// The name reported for the file, which `#line` can change.
+    name: Rc<str>,
+    /// The name the file was found by.
+    written_name: Rc<str>,
+    text: String,
+    /// The file's tokens, lexed up front since they cannot borrow from
+    /// `text` while it is stored alongside them.
+    tokens: VecDeque<Result<Token<'static>, LexError>>,
+    previous_end: usize,
+    previous_line: usize,
+    line_delta: isize,
+    conditionals: Vec<(Conditional, Location)>,
+}
+
+/// Tokens waiting to be read again, such as the result of expanding a
+/// macro. Once they run out reading continues in the current file, unless
+/// the input is a macro argument being expanded on its own.
+struct Input {
+    tokens: VecDeque<PreprocessedToken>,
+    from_file: bool,
+}
+
+impl Input {
+    fn new(tokens: Vec<PreprocessedToken>, from_file: bool) -> Self {
+        Input {
+            tokens: tokens.into(),
+            from_file,
+        }
+    }
+}
+
+/// An `#include` the preprocessor carried out, or tried to.
+#[derive(Clone)]
+pub struct Include {
+    /// The file containing the directive.
+    pub includer: PathBuf,
+    pub header: HeaderName<'static>,
+    /// Where the header was found, or `None` if it was not.
+    pub path: Option<PathBuf>,
+    /// Whether the header was found along a system include path.
+    pub system: bool,
+    pub location: Location,
+}
+
+/// Runs the C preprocessor over a file, yielding the tokens the compiler
+/// would see.
+///
+/// Like the [`Lexer`], the preprocessor reports problems as they are found
+/// and carries on, so one bad include does not hide the rest of the output.
+pub struct Preprocessor {
+    main: Option<PathBuf>,
+    quote_include_paths: Vec<PathBuf>,
+    include_paths: Vec<PathBuf>,
+    system_include_paths: Vec<PathBuf>,
+    standard: Standard,
+    extensions: Vec<Extension>,
+    macros: HashMap<Symbol, Macro>,
+    /// The names of macros and of every identifier lexed, so that they
+    /// compare as integers.
+    interner: RefCell<Interner>,
+    /// The `-D` and `-U` options in order, with `None` for `-U`.
+    command_line: Vec<(String, Option<String>)>,
+    sources: Vec<Source>,
+    input: Input,
+    included_once: HashSet<PathBuf>,
+    includes: Vec<Include>,
+    pending: VecDeque<Result<PreprocessedToken, PreprocessError>>,
+}
+
+impl Preprocessor {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Preprocessor {
+            main: Some(path.into()),
+            quote_include_paths: vec![],
+            include_paths: vec![],
+            system_include_paths: vec![],
+            standard: Standard::default(),
+            extensions: vec![],
+            macros: HashMap::new(),
+            interner: RefCell::new(Interner::new()),
+            command_line: vec![],
+            sources: vec![],
+            input: Input::new(vec![], true),
+            included_once: HashSet::new(),
+            includes: vec![],
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// Adds a directory to search for files included with quotes only, as
+    /// `-iquote` does, after the ones added before it and ahead of every
+    /// directory added with [`Preprocessor::with_include_path`].
+    pub fn with_quote_include_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.quote_include_paths.push(path.into());
+        self
+    }
+
+    /// Adds a directory to search for included files, after the ones added
+    /// before it.
+    pub fn with_include_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.include_paths.push(path.into());
+        self
+    }
+
+    /// Adds a directory to search for system headers, after every directory
+    /// added with [`Preprocessor::with_include_path`], as `-isystem` does.
+    pub fn with_system_include_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.system_include_paths.push(path.into());
+        self
+    }
+
+    /// Selects the C standard, which decides the keywords and
+    /// `__STDC_VERSION__`.
+    pub fn with_standard(mut self, standard: Standard) -> Self {
+        self.standard = standard;
+        self
+    }
+
+    /// Enables a compiler's extensions in the lexer.
+    pub fn with_extension(mut self, extension: Extension) -> Self {
+        if !self.extensions.contains(&extension) {
+            self.extensions.push(extension);
+        }
+        self
+    }
+
+    /// Defines `name` to `value` before the file is read, as
+    /// `-D name=value` does. A name with parameters, such as `f(x)`, defines
+    /// a function-like macro.
+    pub fn with_definition(mut self, name: &str, value: &str) -> Self {
+        self.command_line
+            .push((name.to_string(), Some(value.to_string())));
+        self
+    }
+
+    /// Undefines `name` before the file is read, as `-U name` does.
+    pub fn with_undefinition(mut self, name: &str) -> Self {
+        self.command_line.push((name.to_string(), None));
+        self
+    }
+
+    /// Evaluates `expression` as `#if` would, with the predefined macros and
+    /// the ones given with [`Preprocessor::with_definition`], instead of
+    /// reading the file given to [`Preprocessor::new`]. Quoted names given
+    /// to `__has_include` are looked for in the current directory first.
+    pub fn evaluate(mut self, expression: &str) -> Result<Value, PreprocessError> {
+        let file = PathBuf::from("<command line>");
+        self.main = None;
+        self.predefine();
+        let error = self
+            .lexer(expression)
+            .find_map(|result| result.err().filter(|error| !error.is_warning()));
+        if let Some(error) = error {
+            return Err(PreprocessError::Lex { file, error });
+        }
+        let tokens = self.tokens_from_text(expression, &Rc::from("<command line>"), 1);
+        self.evaluate_condition(&tokens)
+            .map_err(|error| PreprocessError::Expression {
+                error: Box::new(error),
+                file,
+                location: Location::START,
+            })
+    }
+
+    /// The `#include` directives carried out so far, in order.
+    pub fn includes(&self) -> &[Include] {
+        &self.includes
+    }
+
+    fn lexer<'src>(&'src self, text: &'src str) -> Lexer<'src> {
+        let mut lexer = Lexer::new(text)
+            .with_standard(self.standard)
+            .with_interner(&self.interner);
+        for extension in &self.extensions {
+            lexer = lexer.with_extension(*extension);
+        }
+        lexer
+    }
+
+    fn trigraphs(&self) -> bool {
+        replaces_trigraphs(self.standard, &self.extensions)
+    }
+
+    fn report(&mut self, error: PreprocessError) {
+        self.pending.push_back(Err(error));
+    }
+
+    fn current_file(&self) -> PathBuf {
+        self.sources
+            .last()
+            .map_or_else(|| PathBuf::from("<built-in>"), |source| source.path.clone())
+    }
+
+    fn is_active(&self) -> bool {
+        self.sources
+            .last()
+            .and_then(|source| source.conditionals.last())
+            .is_none_or(|(conditional, _)| conditional.active)
+    }
+
+    fn is_defined(&self, name: Symbol) -> bool {
+        self.macros.contains_key(&name)
+            || matches!(name, Symbol::FILE | Symbol::LINE | Symbol::HAS_INCLUDE)
+    }
+
+    fn resolve(&self, name: Symbol) -> String {
+        self.interner.borrow().resolve(name).to_string()
+    }
+
+    fn predefine(&mut self) {
+        let (date, time) = timestamp();
+        let mut definitions = vec![
+            "__STDC__ 1".to_string(),
+            "__STDC_HOSTED__ 1".to_string(),
+            format!("__DATE__ \"{}\"", date),
+            format!("__TIME__ \"{}\"", time),
+        ];
+        if let Some(version) = self.standard.version() {
+            definitions.push(format!("__STDC_VERSION__ {}L", version));
+        }
+        for definition in definitions {
+            let tokens = self.tokens_from_text(&definition, &Rc::from("<built-in>"), 1);
+            self.define(&tokens, Location::START);
+        }
+        for (name, value) in mem::take(&mut self.command_line) {
+            match value {
+                Some(value) => {
+                    let definition = format!("{} {}", name, value);
+                    let tokens = self.tokens_from_text(&definition, &Rc::from("<command line>"), 1);
+                    self.define(&tokens, Location::START);
+                }
+                None => {
+                    let name = self.interner.get_mut().intern(&name);
+                    self.macros.remove(&name);
+                }
+            }
+        }
+    }
+
+    /// Lexes `text` that is not part of any file, such as a predefined
+    /// macro or the spelling built by `##`.
+    fn tokens_from_text(&self, text: &str, file: &Rc<str>, line: usize) -> Vec<PreprocessedToken> {
+        let mut previous_end = 0;
+        self.lexer(text)
+            .filter_map(Result::ok)
+            .filter(|token| !matches!(token.token_type, TokenType::Comment(_)))
+            .map(|token| {
+                let whitespace_before = token.span.start.offset > previous_end;
+                previous_end = token.span.end.offset;
+                PreprocessedToken {
+                    spelling: splice_lines(
+                        &text[token.span.start.offset..token.span.end.offset],
+                        self.trigraphs(),
+                    )
+                    .into_owned(),
+                    written: Position {
+                        file: file.clone(),
+                        line,
+                        column: token.span.start.column,
+                    },
+                    token: token.into_owned(),
+                    file: file.clone(),
+                    line,
+                    whitespace_before,
+                    expansion: vec![],
+                    hide_set: BTreeSet::new(),
+                }
+            })
+            .collect()
+    }
+
+    /// Makes a token spelled `spelling` in place of `like`, if the spelling
+    /// lexes as exactly one token.
+    fn synthesize(&self, spelling: String, like: &PreprocessedToken) -> Option<PreprocessedToken> {
+        let token = {
+            let mut lexer = self.lexer(&spelling);
+            match (lexer.next(), lexer.next()) {
+                (Some(Ok(token)), None) => token.into_owned(),
+                _ => return None,
+            }
+        };
+        if matches!(
+            token.token_type,
+            TokenType::Comment(_) | TokenType::Directive(..) | TokenType::Error(_)
+        ) {
+            return None;
+        }
+        Some(PreprocessedToken {
+            token: Token {
+                span: like.token.span,
+                ..token
+            },
+            spelling,
+            ..like.clone()
+        })
+    }
+
+    fn include(&mut self, path: PathBuf, location: Location) {
+        if self.sources.len() >= MAX_INCLUDE_DEPTH {
+            let file = self.current_file();
+            self.report(PreprocessError::IncludeTooDeep { file, location });
+            return;
+        }
+        if let Ok(canonical) = path.canonicalize() {
+            if self.included_once.contains(&canonical) {
+                return;
+            }
+        }
+        match read_source(&path) {
+            Ok(text) => {
+                let tokens = self
+                    .lexer(&text)
+                    .map(|result| result.map(Token::into_owned))
+                    .collect();
+                self.sources.push(Source {
+                    name: Rc::from(path.display().to_string()),
+                    written_name: Rc::from(path.display().to_string()),
+                    path,
+                    text,
+                    tokens,
+                    previous_end: 0,
+                    previous_line: 0,
+                    line_delta: 0,
+                    conditionals: vec![],
+                });
+            }
+            Err(error) => self.report(PreprocessError::Lex { file: path, error }),
+        }
+    }
+
+    /// Finishes the current file, returning whether there is more input.
+    fn end_of_file(&mut self) -> bool {
+        if let Some(source) = self.sources.pop() {
+            for (_, location) in source.conditionals {
+                self.report(PreprocessError::UnterminatedConditional {
+                    file: source.path.clone(),
+                    location,
+                });
+            }
+        }
+        !self.sources.is_empty()
+    }
+
+    /// Looks for an included file next to the including one, or in the
+    /// current directory outside any file, and along the quote include paths
+    /// (for `"name"` only), then along the include paths and then along the
+    /// system include paths.
+    fn find_include(&self, header: &HeaderName) -> Option<PathBuf> {
+        if !header.angled {
+            let directory = match self.sources.last() {
+                Some(source) => source.path.parent(),
+                None => Some(Path::new("")),
+            };
+            let found = directory
+                .into_iter()
+                .chain(self.quote_include_paths.iter().map(PathBuf::as_path))
+                .map(|directory| directory.join(header.name.as_ref()))
+                .find(|candidate| candidate.is_file());
+            if found.is_some() {
+                return found;
+            }
+        }
+        self.include_paths
+            .iter()
+            .chain(&self.system_include_paths)
+            .map(|directory| directory.join(header.name.as_ref()))
+            .find(|candidate| candidate.is_file())
+    }
+
+    fn source_token(&mut self, token: Token<'static>) -> PreprocessedToken {
+        let trigraphs = self.trigraphs();
+        let source = self.sources.last_mut().expect("tokens come from a source");
+        let written = &source.text[token.span.start.offset..token.span.end.offset];
+        let spelling = splice_lines(written, trigraphs).into_owned();
+        let whitespace_before = token.span.start.offset > source.previous_end;
+        source.previous_end = token.span.end.offset;
+        let line = token.span.start.line as isize + source.line_delta;
+        PreprocessedToken {
+            written: Position {
+                file: source.written_name.clone(),
+                line: token.span.start.line,
+                column: token.span.start.column,
+            },
+            token,
+            spelling,
+            file: source.name.clone(),
+            line: line.max(1) as usize,
+            whitespace_before,
+            expansion: vec![],
+            hide_set: BTreeSet::new(),
+        }
+    }
+
+    fn next_input(&mut self, input: &mut Input) -> Option<PreprocessedToken> {
+        if let Some(token) = input.tokens.pop_front() {
+            return Some(token);
+        }
+        if input.from_file {
+            self.next_from_file()
+        } else {
+            None
+        }
+    }
+
+    /// Reads the next token of the current file that is not skipped,
+    /// carrying out the directives on the way.
+    fn next_from_file(&mut self) -> Option<PreprocessedToken> {
+        loop {
+            let source = self.sources.last_mut()?;
+            let result = source.tokens.pop_front()?;
+            let token = match result {
+                Ok(token) => token,
+                Err(error) => {
+                    let file = source.path.clone();
+                    if self.is_active() {
+                        self.report(PreprocessError::Lex { file, error });
+                    }
+                    continue;
+                }
+            };
+            let at_line_start = token.span.start.line > source.previous_line;
+            source.previous_line = token.span.end.line;
+            match token.token_type {
+                TokenType::Comment(_) => {}
+                TokenType::Directive(..) => self.directive(token),
+                TokenType::Delimiter(Delimiter::Hash) if at_line_start => {
+                    self.unknown_directive(token)
+                }
+                _ if self.is_active() => return Some(self.source_token(token)),
+                _ => {}
+            }
+        }
+    }
+
+    fn directive(&mut self, token: Token<'static>) {
+        let Span { start, end } = token.span;
+        let TokenType::Directive(directive, children) = token.token_type else {
+            return;
+        };
+        let tokens: Vec<PreprocessedToken> = children
+            .into_iter()
+            .filter(|child| !matches!(child.token_type, TokenType::Comment(_)))
+            .map(|child| self.source_token(child))
+            .collect();
+        if let Some(source) = self.sources.last_mut() {
+            source.previous_end = end.offset;
+        }
+
+        match directive {
+            Directive::If | Directive::Ifdef | Directive::Ifndef => {
+                let parent_active = self.is_active();
+                let active = parent_active && self.condition(directive, &tokens, start);
+                let conditional = Conditional {
+                    parent_active,
+                    active,
+                    taken: active,
+                    seen_else: false,
+                };
+                if let Some(source) = self.sources.last_mut() {
+                    source.conditionals.push((conditional, start));
+                }
+                return;
+            }
+            Directive::Elif | Directive::Elifdef | Directive::Elifndef | Directive::Else => {
+                let Some((conditional, _)) = self
+                    .sources
+                    .last()
+                    .and_then(|source| source.conditionals.last())
+                    .filter(|(conditional, _)| !conditional.seen_else)
+                else {
+                    let file = self.current_file();
+                    self.report(PreprocessError::UnbalancedConditional {
+                        directive,
+                        file,
+                        location: start,
+                    });
+                    return;
+                };
+                let candidate = conditional.parent_active && !conditional.taken;
+                let active = match directive {
+                    Directive::Else => candidate,
+                    _ => candidate && self.condition(directive, &tokens, start),
+                };
+                if let Some((conditional, _)) = self
+                    .sources
+                    .last_mut()
+                    .and_then(|source| source.conditionals.last_mut())
+                {
+                    conditional.active = active;
+                    conditional.taken |= active;
+                    conditional.seen_else = directive == Directive::Else;
+                }
+                return;
+            }
+            Directive::Endif => {
+                let popped = self
+                    .sources
+                    .last_mut()
+                    .and_then(|source| source.conditionals.pop());
+                if popped.is_none() {
+                    let file = self.current_file();
+                    self.report(PreprocessError::UnbalancedConditional {
+                        directive,
+                        file,
+                        location: start,
+                    });
+                }
+                return;
+            }
+            _ => {}
+        }
+        if !self.is_active() {
+            return;
+        }
+
+        match directive {
+            Directive::Include => {
+                if let Some(path) = self.header(directive, &tokens, start) {
+                    self.include(path, start);
+                }
+            }
+            Directive::Embed => self.embed(&tokens, start),
+            Directive::Define => self.define(&tokens, start),
+            Directive::Undef => match tokens.first().and_then(PreprocessedToken::symbol) {
+                Some(name) => {
+                    self.macros.remove(&name);
+                }
+                None => {
+                    let file = self.current_file();
+                    self.report(PreprocessError::MissingMacroName {
+                        directive,
+                        file,
+                        location: start,
+                    });
+                }
+            },
+            Directive::Line => self.line(&tokens, start, end.line),
+            Directive::Error => {
+                let file = self.current_file();
+                self.report(PreprocessError::ErrorDirective {
+                    message: spell(&tokens),
+                    file,
+                    location: start,
+                });
+            }
+            Directive::Warning => {
+                let file = self.current_file();
+                self.report(PreprocessError::WarningDirective {
+                    message: spell(&tokens),
+                    file,
+                    location: start,
+                });
+            }
+            Directive::Pragma => {
+                if tokens.len() == 1 && tokens[0].spelling == "once" {
+                    let source = self.sources.last().expect("directives come from a source");
+                    if let Ok(canonical) = source.path.canonicalize() {
+                        self.included_once.insert(canonical);
+                    }
+                    return;
+                }
+                let source = self.sources.last().expect("directives come from a source");
+                let pragma = PreprocessedToken {
+                    spelling: format!("#pragma {}", spell(&tokens)),
+                    token: Token {
+                        span: Span { start, end },
+                        token_type: TokenType::Directive(
+                            directive,
+                            tokens.iter().map(|token| token.token.clone()).collect(),
+                        ),
+                        digraph: None,
+                        symbol: None,
+                    },
+                    file: source.name.clone(),
+                    line: (start.line as isize + source.line_delta).max(1) as usize,
+                    whitespace_before: false,
+                    written: Position {
+                        file: source.written_name.clone(),
+                        line: start.line,
+                        column: start.column,
+                    },
+                    expansion: vec![],
+                    hide_set: BTreeSet::new(),
+                };
+                self.pending.push_back(Ok(pragma));
+            }
+            _ => {}
+        }
+    }
+
+    /// Handles a `#` at the start of a line that is not followed by a
+    /// directive name: nothing at all, a `# 12 "file"` line marker, or an
+    /// unknown directive.
+    fn unknown_directive(&mut self, hash: Token<'static>) {
+        let line = hash.span.start.line;
+        let source = self.sources.last_mut().expect("tokens come from a source");
+        let mut rest = vec![];
+        while let Some(result) = source.tokens.pop_front() {
+            let on_line = match &result {
+                Ok(token) => token.span.start.line == line,
+                Err(error) => error
+                    .location()
+                    .is_some_and(|location| location.line == line),
+            };
+            if !on_line {
+                source.tokens.push_front(result);
+                break;
+            }
+            if let Ok(token) = result {
+                if !matches!(token.token_type, TokenType::Comment(_)) {
+                    rest.push(token);
+                }
+            }
+        }
+        source.previous_line = line;
+        let tokens: Vec<PreprocessedToken> = rest
+            .into_iter()
+            .map(|token| self.source_token(token))
+            .collect();
+        if !self.is_active() {
+            return;
+        }
+        match tokens.first() {
+            None => {}
+            Some(first) if matches!(first.token.token_type, TokenType::Number(_)) => {
+                self.line(&tokens[..tokens.len().min(2)], hash.span.start, line);
+            }
+            Some(first) => {
+                let name = first.name().into_owned();
+                let file = self.current_file();
+                self.report(PreprocessError::UnknownDirective {
+                    name,
+                    file,
+                    location: hash.span.start,
+                });
+            }
+        }
+    }
+
+    /// Decides a conditional directive.
+    fn condition(
+        &mut self,
+        directive: Directive,
+        tokens: &[PreprocessedToken],
+        location: Location,
+    ) -> bool {
+        match directive {
+            Directive::Ifdef | Directive::Ifndef | Directive::Elifdef | Directive::Elifndef => {
+                let Some(name) = tokens.first().and_then(PreprocessedToken::symbol) else {
+                    let file = self.current_file();
+                    self.report(PreprocessError::MissingMacroName {
+                        directive,
+                        file,
+                        location,
+                    });
+                    return false;
+                };
+                let defined = self.is_defined(name);
+                defined == matches!(directive, Directive::Ifdef | Directive::Elifdef)
+            }
+            _ => match self.evaluate_condition(tokens) {
+                Ok(value) => value.is_true(),
+                Err(error) => {
+                    let file = self.current_file();
+                    self.report(PreprocessError::Expression {
+                        error: Box::new(error),
+                        file,
+                        location,
+                    });
+                    false
+                }
+            },
+        }
+    }
+
+    /// Expands the macros in the expression of `#if` or `#elif` and
+    /// evaluates it.
+    fn evaluate_condition(
+        &mut self,
+        tokens: &[PreprocessedToken],
+    ) -> Result<Value, ExpressionError> {
+        // The operands of `defined` are marked so they are not expanded like
+        // every other identifier.
+        let mut tokens = tokens.to_vec();
+        for index in 0..tokens.len() {
+            if tokens[index].symbol() != Some(Symbol::DEFINED) {
+                continue;
+            }
+            let mut operand = index + 1;
+            if tokens
+                .get(operand)
+                .is_some_and(|token| token.spelling == "(")
+            {
+                operand += 1;
+            }
+            if let Some(token) = tokens.get_mut(operand) {
+                token.hide_set.extend(token.symbol());
+            }
+        }
+        let tokens: Vec<Token> = self
+            .expand_list(tokens)
+            .into_iter()
+            .map(|token| token.token)
+            .collect();
+        expression::evaluate(
+            &tokens,
+            &self.extensions,
+            &|name| self.is_defined(name),
+            &|header| self.find_include(header).is_some(),
+        )
+    }
+
+    /// Finds the file named by `#include` or `#embed`, expanding macros if
+    /// the name is not written out.
+    fn header(
+        &mut self,
+        directive: Directive,
+        tokens: &[PreprocessedToken],
+        location: Location,
+    ) -> Option<PathBuf> {
+        let header = match tokens.first().map(|token| &token.token.token_type) {
+            Some(TokenType::HeaderName(header)) => Some(header.clone()),
+            _ => header_name(&self.expand_list(tokens.to_vec())),
+        };
+        let Some(header) = header else {
+            let file = self.current_file();
+            self.report(PreprocessError::InvalidInclude {
+                directive,
+                file,
+                location,
+            });
+            return None;
+        };
+        let path = self.find_include(&header);
+        if directive == Directive::Include {
+            let system = path.as_ref().is_some_and(|path| {
+                self.system_include_paths
+                    .iter()
+                    .any(|directory| path.starts_with(directory))
+            });
+            self.includes.push(Include {
+                includer: self.current_file(),
+                header: header.clone(),
+                path: path.clone(),
+                system,
+                location,
+            });
+        }
+        if path.is_none() {
+            let file = self.current_file();
+            self.report(PreprocessError::IncludeNotFound {
+                header,
+                file,
+                location,
+            });
+        }
+        path
+    }
+
+    /// Replaces `#embed` with the bytes of the file as a list of integers.
+    fn embed(&mut self, tokens: &[PreprocessedToken], location: Location) {
+        if tokens.len() > 1 {
+            let file = self.current_file();
+            self.report(PreprocessError::UnsupportedEmbedParameters { file, location });
+        }
+        let Some(path) = self.header(Directive::Embed, &tokens[..tokens.len().min(1)], location)
+        else {
+            return;
+        };
+        let bytes = match fs::read(&path) {
+            Ok(bytes) => bytes,
+            Err(error) => {
+                let error = LexError::Io(error);
+                self.report(PreprocessError::Lex { file: path, error });
+                return;
+            }
+        };
+        let text = bytes
+            .iter()
+            .map(u8::to_string)
+            .collect::<Vec<_>>()
+            .join(", ");
+        let source = self.sources.last().expect("directives come from a source");
+        let line = (location.line as isize + source.line_delta).max(1) as usize;
+        let name = source.name.clone();
+        for token in self.tokens_from_text(&text, &name, line) {
+            self.pending.push_back(Ok(token));
+        }
+    }
+
+    fn define(&mut self, tokens: &[PreprocessedToken], location: Location) {
+        let Some(name) = tokens.first().filter(|token| token.is_identifier()) else {
+            let file = self.current_file();
+            self.report(PreprocessError::MissingMacroName {
+                directive: Directive::Define,
+                file,
+                location,
+            });
+            return;
+        };
+        let position = name.written.clone();
+        let symbol = name.symbol().expect("identifiers are interned");
+        let name = name.name().into_owned();
+        let mut rest = &tokens[1..];
+        let mut parameters = None;
+        let mut variadic = false;
+        if rest
+            .first()
+            .is_some_and(|token| token.spelling == "(" && !token.whitespace_before)
+        {
+            let list = rest
+                .iter()
+                .position(|token| token.spelling == ")")
+                .and_then(|closing| Some((closing, macro_parameters(&rest[1..closing])?)));
+            let Some((closing, (names, is_variadic))) = list else {
+                let file = self.current_file();
+                self.report(PreprocessError::InvalidMacroParameters {
+                    name,
+                    file,
+                    location,
+                });
+                return;
+            };
+            parameters = Some(names);
+            variadic = is_variadic;
+            rest = &rest[closing + 1..];
+        }
+
+        let mut body = rest.to_vec();
+        if let Some(first) = body.first_mut() {
+            first.whitespace_before = false;
+        }
+        if body.first().is_some_and(|token| token.is_punctuator("##"))
+            || body.last().is_some_and(|token| token.is_punctuator("##"))
+        {
+            let file = self.current_file();
+            self.report(PreprocessError::PasteAtEdge {
+                name,
+                file,
+                location,
+            });
+            return;
+        }
+        let definition = Macro {
+            parameters,
+            variadic,
+            body,
+            definition: position,
+        };
+        if definition.parameters.is_some() {
+            let body = &definition.body;
+            let stray_hash = body.iter().enumerate().any(|(index, token)| {
+                token.is_punctuator("#")
+                    && body
+                        .get(index + 1)
+                        .and_then(|next| definition.parameter(next))
+                        .is_none()
+            });
+            if stray_hash {
+                let file = self.current_file();
+                self.report(PreprocessError::InvalidStringification {
+                    name,
+                    file,
+                    location,
+                });
+                return;
+            }
+        }
+        if let Some(existing) = self.macros.get(&symbol) {
+            if !existing.is_equivalent(&definition) {
+                let file = self.current_file();
+                self.report(PreprocessError::MacroRedefined {
+                    name: name.clone(),
+                    file,
+                    location,
+                });
+            }
+        }
+        self.macros.insert(symbol, definition);
+    }
+
+    /// Carries out `#line` (or a `# 12 "file"` marker) written on
+    /// `directive_line`, so the line after it gets the given number.
+    fn line(&mut self, tokens: &[PreprocessedToken], location: Location, directive_line: usize) {
+        let tokens = self.expand_list(tokens.to_vec());
+        let operands = match tokens.as_slice() {
+            [number] => Some((number, None)),
+            [number, name] if matches!(name.token.token_type, TokenType::Literal(_)) => {
+                Some((number, Some(name)))
+            }
+            _ => None,
+        };
+        let parsed = operands.and_then(|(number, name)| {
+            if !number.spelling.bytes().all(|byte| byte.is_ascii_digit()) {
+                return None;
+            }
+            let line = number.spelling.parse::<usize>().ok()?;
+            let name = match name {
+                Some(name) => {
+                    let quoted = name.spelling.strip_prefix('"')?.strip_suffix('"')?;
+                    Some(quoted.replace("\\\\", "\\"))
+                }
+                None => None,
+            };
+            Some((line, name))
+        });
+        let Some((line, name)) = parsed else {
+            let file = self.current_file();
+            self.report(PreprocessError::InvalidLine { file, location });
+            return;
+        };
+        let source = self
+            .sources
+            .last_mut()
+            .expect("directives come from a source");
+        source.line_delta = line as isize - (directive_line as isize + 1);
+        if let Some(name) = name {
+            source.name = Rc::from(name);
+        }
+    }
+
+    /// Replaces the macro named by `token`, if there is one to expand, by
+    /// putting its expansion back into `input`. Otherwise hands the token
+    /// back.
+    fn expand(&mut self, token: PreprocessedToken, input: &mut Input) -> Option<PreprocessedToken> {
+        let Some(name) = token.symbol().filter(|name| !token.hide_set.contains(name)) else {
+            return Some(token);
+        };
+        let dynamic = match name {
+            Symbol::FILE => Some(format!("\"{}\"", escape(&token.file))),
+            Symbol::LINE => Some(token.line.to_string()),
+            _ => None,
+        };
+        if let Some(spelling) = dynamic {
+            let step = ExpansionStep {
+                name: token.name().into_owned(),
+                invocation: token.written.clone(),
+                definition: Position::built_in(),
+                origin: Origin::Body,
+            };
+            let Some(mut synthesized) = self.synthesize(spelling, &token) else {
+                return Some(token);
+            };
+            synthesized.expansion.insert(0, step);
+            return Some(synthesized);
+        }
+        let Some(definition) = self.macros.get(&name).cloned() else {
+            return Some(token);
+        };
+
+        let mut hide_set = token.hide_set.clone();
+        let arguments = if definition.parameters.is_some() {
+            match self.next_input(input) {
+                Some(next) if next.spelling == "(" => {}
+                next => {
+                    // A function-like macro's name on its own is left alone.
+                    if let Some(next) = next {
+                        input.tokens.push_front(next);
+                    }
+                    return Some(token);
+                }
+            }
+            let (arguments, closing) = self.arguments(&token, &definition, input)?;
+            hide_set.retain(|name| closing.hide_set.contains(name));
+            arguments
+        } else {
+            vec![]
+        };
+        hide_set.insert(name);
+
+        let mut expansion = self.substitute(&definition, &arguments, &token);
+        for (index, expanded) in expansion.iter_mut().enumerate() {
+            expanded.hide_set.extend(hide_set.iter().cloned());
+            expanded.file = token.file.clone();
+            expanded.line = token.line;
+            if index == 0 {
+                expanded.whitespace_before = token.whitespace_before;
+            }
+        }
+        for expanded in expansion.into_iter().rev() {
+            input.tokens.push_front(expanded);
+        }
+        None
+    }
+
+    /// Collects the arguments of a function-like macro call up to and
+    /// including the closing parenthesis.
+    fn arguments(
+        &mut self,
+        name: &PreprocessedToken,
+        definition: &Macro,
+        input: &mut Input,
+    ) -> Option<(Vec<Vec<PreprocessedToken>>, PreprocessedToken)> {
+        let expected = definition.parameters.as_ref().map_or(0, Vec::len);
+        let mut arguments = vec![vec![]];
+        let mut depth = 0;
+        let closing = loop {
+            let Some(token) = self.next_input(input) else {
+                let file = self.current_file();
+                self.report(PreprocessError::UnterminatedArguments {
+                    name: name.name().into_owned(),
+                    file,
+                    location: name.token.span.start,
+                });
+                return None;
+            };
+            match token.spelling.as_str() {
+                ")" if depth == 0 => break token,
+                ")" => depth -= 1,
+                "(" => depth += 1,
+                // The variable arguments keep their commas.
+                "," if depth == 0 && !(definition.variadic && arguments.len() >= expected) => {
+                    arguments.push(vec![]);
+                    continue;
+                }
+                _ => {}
+            }
+            arguments
+                .last_mut()
+                .expect("there is always an argument")
+                .push(token);
+        };
+
+        let found = arguments.len();
+        if expected == 0 && found == 1 && arguments[0].is_empty() {
+            arguments.clear();
+        } else if definition.variadic && found + 1 == expected {
+            arguments.push(vec![]);
+        } else if found != expected {
+            let file = self.current_file();
+            self.report(PreprocessError::WrongArgumentCount {
+                name: name.name().into_owned(),
+                expected,
+                found,
+                file,
+                location: name.token.span.start,
+            });
+            return None;
+        }
+        Some((arguments, closing))
+    }
+
+    /// Fills in a macro's body: stringifies, pastes and replaces parameters
+    /// with their arguments, which are fully expanded first unless they are
+    /// next to `#` or `##`. Each token records how it got there on top of
+    /// the history of its argument or, for the rest, of `invocation`.
+    fn substitute(
+        &mut self,
+        definition: &Macro,
+        arguments: &[Vec<PreprocessedToken>],
+        invocation: &PreprocessedToken,
+    ) -> Vec<PreprocessedToken> {
+        let step = |origin: Origin| ExpansionStep {
+            name: invocation.name().into_owned(),
+            invocation: invocation.written.clone(),
+            definition: definition.definition.clone(),
+            origin,
+        };
+        let record = |mut token: PreprocessedToken, origin: Origin| {
+            let history = match origin {
+                Origin::Argument(_) => mem::take(&mut token.expansion),
+                _ => invocation.expansion.clone(),
+            };
+            token.expansion = vec![step(origin)];
+            token.expansion.extend(history);
+            token
+        };
+        let parameter_names: Vec<String> = definition
+            .parameters
+            .iter()
+            .flatten()
+            .map(|parameter| self.resolve(*parameter))
+            .collect();
+        let parameter_name = |parameter: usize| parameter_names[parameter].clone();
+        let body = &definition.body;
+        let mut output: Vec<PreprocessedToken> = vec![];
+        // Set when the last thing substituted was an empty argument, which
+        // `##` treats as a placemarker rather than pasting onto the token
+        // before it.
+        let mut placemarker = false;
+        let mut index = 0;
+        while let Some(token) = body.get(index) {
+            index += 1;
+            let next = body.get(index);
+
+            if definition.parameters.is_some() && token.is_punctuator("#") {
+                if let Some(parameter) = next.and_then(|next| definition.parameter(next)) {
+                    index += 1;
+                    let stringified = self.stringify(&arguments[parameter], token);
+                    output.push(record(
+                        stringified,
+                        Origin::Stringified(parameter_name(parameter)),
+                    ));
+                    placemarker = false;
+                    continue;
+                }
+            }
+
+            if token.is_punctuator("##") {
+                if let Some(right) = next {
+                    index += 1;
+                    let mut tokens: Vec<PreprocessedToken> = match definition.parameter(right) {
+                        Some(parameter) => arguments[parameter]
+                            .iter()
+                            .map(|token| {
+                                record(token.clone(), Origin::Argument(parameter_name(parameter)))
+                            })
+                            .collect(),
+                        None => vec![record(right.clone(), Origin::Body)],
+                    };
+                    if placemarker || output.is_empty() {
+                        placemarker = tokens.is_empty();
+                        output.extend(tokens);
+                    } else if definition.is_variable_arguments(right)
+                        && output.last().is_some_and(|last| last.spelling == ",")
+                    {
+                        // GNU's `, ## __VA_ARGS__` drops the comma when there
+                        // are no variable arguments and pastes nothing.
+                        if tokens.is_empty() {
+                            output.pop();
+                        }
+                        output.extend(tokens);
+                    } else if !tokens.is_empty() {
+                        let left = output.pop().expect("output is not empty");
+                        let first = tokens.remove(0);
+                        let mut pasted = self.paste(left, first);
+                        if pasted.len() == 1 {
+                            let token = pasted.remove(0);
+                            pasted.push(record(token, Origin::Pasted));
+                        }
+                        output.extend(pasted);
+                        output.extend(tokens);
+                    }
+                    continue;
+                }
+            }
+
+            if token.spelling == "__VA_OPT__"
+                && definition.variadic
+                && next.is_some_and(|next| next.spelling == "(")
+            {
+                if let Some(length) = matching_parenthesis(&body[index..]) {
+                    let content = body[index + 1..index + length].to_vec();
+                    index += length + 1;
+                    let variable = arguments.last().cloned().unwrap_or_default();
+                    if !self.expand_list(variable).is_empty() {
+                        let optional = Macro {
+                            body: content,
+                            ..definition.clone()
+                        };
+                        output.extend(self.substitute(&optional, arguments, invocation));
+                    }
+                    placemarker = false;
+                    continue;
+                }
+            }
+
+            if let Some(parameter) = definition.parameter(token) {
+                let argument = arguments[parameter].clone();
+                let pasted = next.is_some_and(|next| next.is_punctuator("##"));
+                let mut tokens = if pasted {
+                    argument
+                } else {
+                    self.expand_list(argument)
+                };
+                if let Some(first) = tokens.first_mut() {
+                    first.whitespace_before = token.whitespace_before;
+                }
+                placemarker = pasted && tokens.is_empty();
+                let origin = Origin::Argument(parameter_name(parameter));
+                output.extend(
+                    tokens
+                        .into_iter()
+                        .map(|token| record(token, origin.clone())),
+                );
+                continue;
+            }
+
+            output.push(record(token.clone(), Origin::Body));
+            placemarker = false;
+        }
+        output
+    }
+
+    fn stringify(
+        &mut self,
+        argument: &[PreprocessedToken],
+        hash: &PreprocessedToken,
+    ) -> PreprocessedToken {
+        let mut spelling = String::from('"');
+        for (index, token) in argument.iter().enumerate() {
+            if index > 0 && token.whitespace_before {
+                spelling.push(' ');
+            }
+            match token.token.token_type {
+                TokenType::Literal(_) | TokenType::Character(_) => {
+                    spelling.push_str(&escape(&token.spelling));
+                }
+                _ => spelling.push_str(&token.spelling),
+            }
+        }
+        spelling.push('"');
+        match self.synthesize(spelling.clone(), hash) {
+            Some(token) => token,
+            None => PreprocessedToken {
+                token: Token {
+                    span: hash.token.span,
+                    token_type: TokenType::Error(spelling.clone().into()),
+                    digraph: None,
+                    symbol: None,
+                },
+                spelling,
+                ..hash.clone()
+            },
+        }
+    }
+
+    fn paste(
+        &mut self,
+        left: PreprocessedToken,
+        right: PreprocessedToken,
+    ) -> Vec<PreprocessedToken> {
+        let spelling = format!("{}{}", left.spelling, right.spelling);
+        if let Some(token) = self.synthesize(spelling, &left) {
+            return vec![token];
+        }
+        let file = self.current_file();
+        self.report(PreprocessError::InvalidPaste {
+            left: left.spelling.clone(),
+            right: right.spelling.clone(),
+            file,
+            location: left.token.span.start,
+        });
+        vec![left, right]
+    }
+
+    /// Fully expands `tokens` on their own, as is done to macro arguments
+    /// and to the operands of some directives.
+    fn expand_list(&mut self, tokens: Vec<PreprocessedToken>) -> Vec<PreprocessedToken> {
+        let mut input = Input::new(tokens, false);
+        let mut output = vec![];
+        while let Some(token) = self.next_input(&mut input) {
+            output.extend(self.expand(token, &mut input));
+        }
+        output
+    }
+}
+
+impl Iterator for Preprocessor {
+    type Item = Result<PreprocessedToken, PreprocessError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(path) = self.main.take() {
+            self.predefine();
+            self.include(path, Location::START);
+        }
+        loop {
+            if let Some(result) = self.pending.pop_front() {
+                return Some(result);
+            }
+            let mut input = mem::replace(&mut self.input, Input::new(vec![], true));
+            let token = self.next_input(&mut input);
+            let output = token.map(|token| self.expand(token, &mut input));
+            self.input = input;
+            match output {
+                Some(Some(token)) => self.pending.push_back(Ok(token)),
+                Some(None) => {}
+                None if self.end_of_file() => {}
+                None => return self.pending.pop_front(),
+            }
+        }
+    }
+}
+
+/// Writes preprocessed tokens back out as C source, with `# line "file"`
+/// markers wherever the output moves to another file or skips ahead.
+#[derive(Default)]
+pub struct Printer {
+    text: String,
+    file: Option<Rc<str>>,
+    line: usize,
+    previous: Option<String>,
+}
+
+impl Printer {
+    pub fn new() -> Self {
+        Printer::default()
+    }
+
+    pub fn print(&mut self, token: &PreprocessedToken) {
+        let same_file = self.file.as_ref() == Some(&token.file);
+        if !same_file || token.line < self.line || token.line > self.line + 8 {
+            if !self.text.is_empty() && !self.text.ends_with('\n') {
+                self.text.push('\n');
+            }
+            self.text
+                .push_str(&format!("# {} \"{}\"\n", token.line, escape(&token.file)));
+            self.file = Some(token.file.clone());
+            self.line = token.line;
+            self.previous = None;
+        }
+        while self.line < token.line {
+            self.text.push('\n');
+            self.line += 1;
+            self.previous = None;
+        }
+
+        if let Some(previous) = &self.previous {
+            if token.whitespace_before || would_paste(previous, &token.spelling) {
+                self.text.push(' ');
+            }
+        }
+        self.text.push_str(&token.spelling);
+        if matches!(token.token.token_type, TokenType::Directive(..)) {
+            self.text.push('\n');
+            self.line += 1;
+            self.previous = None;
+        } else {
+            self.previous = Some(token.spelling.clone());
+        }
+    }
+
+    pub fn finish(mut self) -> String {
+        if !self.text.is_empty() && !self.text.ends_with('\n') {
+            self.text.push('\n');
+        }
+        self.text
+    }
+}
+
+/// Whether printing `next` straight after `previous` would read back as
+/// different tokens.
+fn would_paste(previous: &str, next: &str) -> bool {
+    let (Some(last), Some(first)) = (previous.chars().last(), next.chars().next()) else {
+        return false;
+    };
+    let is_word = |character: char| is_identifier_continue(character) || character == '$';
+    let starts_word = is_word(first) || next.starts_with("\\u") || next.starts_with("\\U");
+    if is_word(last) && starts_word {
+        return true;
+    }
+    if previous.starts_with(|character: char| character.is_ascii_digit() || character == '.')
+        && (first == '.' || (matches!(last, 'e' | 'E' | 'p' | 'P') && matches!(first, '+' | '-')))
+    {
+        return true;
+    }
+    let pair: String = [last, first].iter().collect();
+    pair == "//"
+        || pair == "/*"
+        || (last == '.' && first.is_ascii_digit())
+        || PUNCTUATORS
+            .iter()
+            .any(|(spelling, _, _)| spelling.starts_with(&pair))
+}
+
+/// Joins tokens with single spaces where the source had whitespace.
+fn spell(tokens: &[PreprocessedToken]) -> String {
+    let mut text = String::new();
+    for (index, token) in tokens.iter().enumerate() {
+        if index > 0 && token.whitespace_before {
+            text.push(' ');
+        }
+        text.push_str(&token.spelling);
+    }
+    text
+}
+
+/// Escapes backslashes and double quotes for use inside a string literal.
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Reads a header name out of the expansion of a computed include.
+fn header_name(tokens: &[PreprocessedToken]) -> Option<HeaderName<'static>> {
+    match tokens {
+        [literal] if matches!(literal.token.token_type, TokenType::Literal(_)) => {
+            let name = literal.spelling.strip_prefix('"')?.strip_suffix('"')?;
+            Some(HeaderName {
+                name: Cow::Owned(name.to_string()),
+                angled: false,
+            })
+        }
+        [opening, name @ .., closing] if opening.spelling == "<" && closing.spelling == ">" => {
+            Some(HeaderName {
+                name: Cow::Owned(spell(name)),
+                angled: true,
+            })
+        }
+        _ => None,
+    }
+}
+
+/// Parses the parameter list of a function-like macro, without its
+/// parentheses, into the names and whether the macro is variadic.
+fn macro_parameters(tokens: &[PreprocessedToken]) -> Option<(Vec<Symbol>, bool)> {
+    let mut parameters: Vec<Symbol> = vec![];
+    let mut variadic = false;
+    if tokens.is_empty() {
+        return Some((parameters, variadic));
+    }
+    for group in tokens.split(|token| token.spelling == ",") {
+        if variadic {
+            return None;
+        }
+        match group {
+            [dots] if dots.spelling == "..." => {
+                parameters.push(Symbol::VA_ARGS);
+                variadic = true;
+            }
+            // GNU's named variable arguments, as in `args...`.
+            [name, dots] if dots.spelling == "..." => {
+                parameters.push(name.symbol()?);
+                variadic = true;
+            }
+            [name] => match name.symbol() {
+                Some(name) if !parameters.contains(&name) => parameters.push(name),
+                _ => return None,
+            },
+            _ => return None,
+        }
+    }
+    Some((parameters, variadic))
+}
+
+/// The index of the parenthesis closing the one `tokens` starts with.
+fn matching_parenthesis(tokens: &[PreprocessedToken]) -> Option<usize> {
+    let mut depth = 0;
+    for (index, token) in tokens.iter().enumerate() {
+        match token.spelling.as_str() {
+            "(" => depth += 1,
+            ")" if depth == 1 => return Some(index),
+            ")" => depth -= 1,
+            _ => {}
+        }
+    }
+    None
+}
+
+/// The current date and time as `__DATE__` and `__TIME__` spell them, in
+/// UTC.
+fn timestamp() -> (String, String) {
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+    let (days, seconds) = ((seconds / 86400) as i64, seconds % 86400);
+
+    // Converts days since 1970 to a civil date, counting years from March
+    // so leap days fall at the end.
+    let shifted = days + 719468;
+    let era = shifted.div_euclid(146097);
+    let day_of_era = shifted - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    (
+        format!("{} {:>2} {}", MONTHS[month as usize - 1], day, year),
+        format!(
+            "{:02}:{:02}:{:02}",
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60
+        ),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, process};
+
+    use super::*;
+
+    /// The directory the test called `name` writes its files to.
+    fn test_directory(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("cust-{}-{}", process::id(), name))
+    }
+
+    /// Writes `files` to a directory of their own and preprocesses the first,
+    /// returning the spellings of the tokens and the errors.
+    fn preprocess_with(
+        name: &str,
+        files: &[(&str, &str)],
+        configure: impl FnOnce(Preprocessor) -> Preprocessor,
+    ) -> (Vec<String>, Vec<PreprocessError>) {
+        let directory = test_directory(name);
+        for (file, text) in files {
+            let path = directory.join(file);
+            let parent = path.parent().expect("files are in the directory");
+            fs::create_dir_all(parent).expect("the temporary directory is writable");
+            fs::write(path, text).expect("the temporary directory is writable");
+        }
+        let mut spellings = vec![];
+        let mut errors = vec![];
+        for result in configure(Preprocessor::new(directory.join(files[0].0))) {
+            match result {
+                Ok(token) => spellings.push(token.spelling),
+                Err(error) => errors.push(error),
+            }
+        }
+        fs::remove_dir_all(&directory).ok();
+        (spellings, errors)
+    }
+
+    /// Preprocesses `source` as C23, which it must be free of errors in.
+    fn preprocess(name: &str, source: &str) -> Vec<String> {
+        let (spellings, errors) = preprocess_with(name, &[("main.c", source)], |preprocessor| {
+            preprocessor.with_standard(Standard::C23)
+        });
+        assert!(errors.is_empty(), "{}", errors[0]);
+        spellings
+    }
+
+    /// The spellings of the tokens of `text`, to compare with the output of
+    /// the preprocessor whatever the spacing.
+    fn tokens(text: &str) -> Vec<String> {
+        Lexer::new(text)
+            .with_standard(Standard::C23)
+            .filter_map(Result::ok)
+            .filter(|token| !matches!(token.token_type, TokenType::Comment(_)))
+            .map(|token| text[token.span.start.offset..token.span.end.offset].to_string())
+            .collect()
+    }
+
+    // The examples of C17 6.10.3.5.
+
+    #[test]
+    fn macros_are_rescanned_and_redefined() {
+        let source = r#"
+#define x 3
+#define f(a) f(x * (a))
+#undef x
+#define x 2
+#define g f
+#define z z[0]
+#define h g(~
+#define m(a) a(w)
+#define w 0,1
+#define t(a) a
+#define p() int
+#define q(x) x
+#define r(x,y) x ## y
+#define str(x) # x
+f(y+1) + f(f(z)) % t(t(g)(0) + t)(1);
+g(x+(3,4)-w) | h 5) & m
+        (f)^m(m);
+p() i[q()] = { q(1), r(2,3), r(4,), r(,5), r(,) };
+char c[2][6] = { str(hello), str() };
+"#;
+        let expected = r#"
+f(2 * (y+1)) + f(2 * (f(2 * (z[0])))) % f(2 * (0)) + t(1);
+f(2 * (2+(3,4)-0,1)) | f(2 * (~ 5)) & f(2 * (0,1))^m(0,1);
+int i[] = { 1, 23, 4, 5, };
+char c[2][6] = { "hello", "" };
+"#;
+        assert_eq!(preprocess("rescan", source), tokens(expected));
+    }
+
+    #[test]
+    fn stringifying_and_pasting() {
+        let source = r#"
+#define str(s) # s
+#define xstr(s) str(s)
+#define debug(s, t) printf("x" # s "= %d, x" # t "= %s", \
+    x ## s, x ## t)
+#define INCFILE(n) vers ## n
+#define glue(a, b) a ## b
+#define xglue(a, b) glue(a, b)
+#define HIGHLOW "hello"
+#define LOW LOW ", world"
+debug(1, 2);
+fputs(str(strncmp("abc\0d", "abc", '\4') // this goes away
+    == 0) str(: @\n), s);
+#include xstr(INCFILE(2).h)
+glue(HIGH, LOW);
+xglue(HIGH, LOW)
+"#;
+        let expected = r#"
+printf("x" "1" "= %d, x" "2" "= %s", x1, x2);
+fputs("strncmp(\"abc\\0d\", \"abc\", '\\4') == 0" ": @\n", s);
+int vers2;
+"hello";
+"hello" ", world"
+"#;
+        let files = [("main.c", source), ("vers2.h", "int vers2;\n")];
+        let (spellings, errors) = preprocess_with("paste", &files, |preprocessor| preprocessor);
+        // `@` and `\` are tokens to the preprocessor, though not to C.
+        assert!(matches!(
+            errors.as_slice(),
+            [PreprocessError::Lex { .. }, PreprocessError::Lex { .. }]
+        ));
+        assert_eq!(spellings, tokens(expected));
+    }
+
+    #[test]
+    fn placemarkers() {
+        let source = "
+#define t(x,y,z) x ## y ## z
+int j[] = { t(1,2,3), t(,4,5), t(6,,7), t(8,9,),
+    t(10,,), t(,11,), t(,,12), t(,,) };
+";
+        let expected = "int j[] = { 123, 45, 67, 89, 10, 11, 12, };";
+        assert_eq!(preprocess("placemarkers", source), tokens(expected));
+    }
+
+    #[test]
+    fn only_identical_redefinitions_are_allowed() {
+        let valid = "
+#define OBJ_LIKE (1-1)
+#define OBJ_LIKE /* white space */ (1-1) /* other */
+#define FUNC_LIKE(a) ( a )
+#define FUNC_LIKE( a )( /* note the white space */ \\
+    a /* other stuff on this line
+    */ )
+";
+        assert!(preprocess("redefinitions", valid).is_empty());
+        for invalid in [
+            "#define OBJ_LIKE (0)",
+            "#define OBJ_LIKE (1 - 1)",
+            "#define FUNC_LIKE(b) ( a )",
+            "#define FUNC_LIKE(b) ( b )",
+        ] {
+            let source = format!("{}{}\n", valid, invalid);
+            let (_, errors) =
+                preprocess_with("redefined", &[("main.c", &source)], |preprocessor| {
+                    preprocessor
+                });
+            assert!(
+                matches!(errors.as_slice(), [PreprocessError::MacroRedefined { .. }]),
+                "{}",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn variable_arguments() {
+        let source = r#"
+#define debug(...) fprintf(stderr, __VA_ARGS__)
+#define showlist(...) puts(#__VA_ARGS__)
+#define report(test, ...) ((test)?puts(#test):\
+    printf(__VA_ARGS__))
+debug("Flag");
+debug("X = %d\n", x);
+showlist(The first, second, and third items.);
+report(x>y, "x is %d but y is %d", x, y);
+"#;
+        let expected = r#"
+fprintf(stderr, "Flag");
+fprintf(stderr, "X = %d\n", x);
+puts("The first, second, and third items.");
+((x>y)?puts("x>y"): printf("x is %d but y is %d", x, y));
+"#;
+        assert_eq!(preprocess("variadic", source), tokens(expected));
+    }
+
+    /// The examples of `__VA_OPT__` in C23 6.10.5.1.
+    #[test]
+    fn optional_variable_arguments() {
+        let source = "
+#define F(...) f(0 __VA_OPT__(,) __VA_ARGS__)
+#define G(X, ...) f(0, X __VA_OPT__(,) __VA_ARGS__)
+#define SDEF(sname, ...) S sname __VA_OPT__(= { __VA_ARGS__ })
+#define EMP
+F(a,b,c)
+F()
+F(EMP)
+G(a,b,c)
+G(a,)
+G(a)
+SDEF(foo);
+SDEF(bar, 1, 2);
+";
+        let expected = "
+f(0, a,b,c)
+f(0)
+f(0)
+f(0, a, b, c)
+f(0, a)
+f(0, a)
+S foo;
+S bar = { 1, 2 };
+";
+        assert_eq!(preprocess("va-opt", source), tokens(expected));
+    }
+
+    #[test]
+    fn hide_sets_stop_recursion() {
+        let source = "
+#define f(a) a*g
+#define g(a) f(a)
+f(2)(9)
+#define self self + 1
+self
+#define ping pong
+#define pong ping
+ping pong
+";
+        let expected = "2*9*g self + 1 ping pong";
+        assert_eq!(preprocess("hide-sets", source), tokens(expected));
+    }
+
+    #[test]
+    fn gnu_comma_paste_drops_the_comma_before_no_arguments() {
+        let source = r#"
+#define e(format, ...) printf(format, ## __VA_ARGS__)
+e("a");
+e("b", 1, 2);
+"#;
+        let expected = r#"printf("a"); printf("b", 1, 2);"#;
+        let files = [("main.c", source)];
+        let (spellings, errors) = preprocess_with("gnu-comma", &files, |preprocessor| {
+            preprocessor.with_extension(Extension::Gnu)
+        });
+        assert!(errors.is_empty(), "{}", errors[0]);
+        assert_eq!(spellings, tokens(expected));
+    }
+
+    #[test]
+    fn pragma_once_and_include_guards_include_a_header_once() {
+        let files = [
+            (
+                "main.c",
+                "#include \"once.h\"\n#include \"guarded.h\"\n#include \"once.h\"\n\
+                 #include \"./guarded.h\"\n#include \"./once.h\"\nend\n",
+            ),
+            ("once.h", "#pragma once\nonce\n"),
+            (
+                "guarded.h",
+                "#ifndef GUARDED_H\n#define GUARDED_H\nguarded\n#endif\n",
+            ),
+        ];
+        let (spellings, errors) = preprocess_with("once", &files, |preprocessor| preprocessor);
+        assert!(errors.is_empty(), "{}", errors[0]);
+        assert_eq!(spellings, ["once", "guarded", "end"]);
+    }
+
+    #[test]
+    fn pragma_once_holds_however_the_header_is_reached() {
+        let files = [
+            (
+                "main.c",
+                "#include \"sub/a.h\"\n#include \"once.h\"\n#pragma pack(1)\nend\n",
+            ),
+            (
+                "sub/a.h",
+                "#include \"../once.h\"\n#include \"../sub/../once.h\"\n",
+            ),
+            ("once.h", "#pragma once\nonce\n"),
+        ];
+        let (spellings, errors) = preprocess_with("pragma", &files, |preprocessor| preprocessor);
+        assert!(errors.is_empty(), "{}", errors[0]);
+        assert_eq!(spellings, ["once", "#pragma pack(1)", "end"]);
+    }
+
+    #[test]
+    fn line_sets_the_number_and_name_of_the_next_line() {
+        let source = "
+#line 100 \"renamed.c\"
+__LINE__ __FILE__
+#define L 7
+#line L
+__LINE__ __FILE__
+# 20 \"marker.c\"
+__LINE__
+__FILE__
+";
+        let expected = r#"100 "renamed.c" 7 "renamed.c" 20 "marker.c""#;
+        assert_eq!(preprocess("line", source), tokens(expected));
+
+        let files = [("main.c", "#line x\n#line 0x10\n")];
+        let (_, errors) = preprocess_with("bad-line", &files, |preprocessor| preprocessor);
+        assert!(matches!(
+            errors.as_slice(),
+            [
+                PreprocessError::InvalidLine { .. },
+                PreprocessError::InvalidLine { .. }
+            ]
+        ));
+    }
+
+    #[test]
+    fn embed_becomes_a_list_of_the_bytes() {
+        let files = [
+            (
+                "main.c",
+                "int a[] = {\n#embed \"data.bin\"\n};\n#embed <missing.bin>\n",
+            ),
+            ("data.bin", "AB\n"),
+        ];
+        let (spellings, errors) = preprocess_with("embed", &files, |preprocessor| preprocessor);
+        assert_eq!(spellings, tokens("int a[] = { 65, 66, 10 };"));
+        assert!(matches!(
+            errors.as_slice(),
+            [PreprocessError::IncludeNotFound { .. }]
+        ));
+
+        // The parameters are reported and the whole file is embedded anyway.
+        let source = "#embed \"main.c\" limit(1)\n";
+        let (spellings, errors) =
+            preprocess_with("embed-limit", &[("main.c", source)], |preprocessor| {
+                preprocessor
+            });
+        assert_eq!(spellings.len(), 2 * source.len() - 1);
+        assert_eq!(spellings[..3], ["35", ",", "101"]);
+        assert!(matches!(
+            errors.as_slice(),
+            [PreprocessError::UnsupportedEmbedParameters { .. }]
+        ));
+    }
+
+    #[test]
+    fn includes_are_searched_next_to_the_file_then_along_the_paths() {
+        let files = [
+            (
+                "main.c",
+                "#include \"a.h\"\n#include <a.h>\n#include <missing.h>\n",
+            ),
+            ("a.h", "local\n"),
+        ];
+        let directory = env::temp_dir().join(format!("cust-{}-include-path", process::id()));
+        fs::create_dir_all(&directory).expect("the temporary directory is writable");
+        fs::write(directory.join("a.h"), "path\n").expect("the temporary directory is writable");
+        let (spellings, errors) = preprocess_with("include", &files, |preprocessor| {
+            preprocessor.with_include_path(&directory)
+        });
+        fs::remove_dir_all(&directory).ok();
+        assert_eq!(spellings, ["local", "path"]);
+        assert!(matches!(
+            errors.as_slice(),
+            [PreprocessError::IncludeNotFound { header, .. }] if header.name == "missing.h"
+        ));
+    }
+
+    #[test]
+    fn includes_are_searched_by_includer_then_quote_then_include_then_system_paths() {
+        let files = [
+            (
+                "main.c",
+                "#include \"one.h\"\n#include \"two.h\"\n#include \"three.h\"\n\
+                 #include \"four.h\"\n#include <one.h>\n#include <two.h>\n",
+            ),
+            ("one.h", "main_one\n"),
+            ("quote/one.h", "quote_one\n"),
+            ("quote/two.h", "quote_two\n"),
+            ("include/one.h", "include_one\n"),
+            ("include/two.h", "include_two\n"),
+            ("include/three.h", "include_three\n#include \"nested.h\"\n"),
+            ("include/nested.h", "include_nested\n"),
+            ("nested.h", "main_nested\n"),
+            ("system/one.h", "system_one\n"),
+            ("system/two.h", "system_two\n"),
+            ("system/three.h", "system_three\n"),
+            ("system/four.h", "system_four\n"),
+        ];
+        let directory = test_directory("search-order");
+        let (spellings, errors) = preprocess_with("search-order", &files, |preprocessor| {
+            preprocessor
+                .with_system_include_path(directory.join("system"))
+                .with_include_path(directory.join("include"))
+                .with_quote_include_path(directory.join("quote"))
+        });
+        assert!(errors.is_empty(), "{}", errors[0]);
+        assert_eq!(
+            spellings,
+            [
+                "main_one",
+                "quote_two",
+                "include_three",
+                "include_nested",
+                "system_four",
+                "include_one",
+                "include_two",
+            ]
+        );
+    }
+
+    #[test]
+    fn conditionals_skip_groups_and_their_errors() {
+        let source = "
+#define A 2
+#if A > 1 && defined(A)
+one
+#elif 1 / 0
+#error not evaluated
+#else
+#error not taken
+#endif
+#ifdef B
+#if 1 / 0
+#endif
+#elifndef B
+two
+#endif
+#if 0
+#if 1
+#else
+#endif
+#elif __STDC_VERSION__ >= 202311L
+three
+#endif
+";
+        assert_eq!(preprocess("conditionals", source), ["one", "two", "three"]);
+    }
+
+    #[test]
+    fn printer_marks_files_and_lines() {
+        let source = "#define N 1\nint a = N;\n\n\n\n\n\n\n\n\n\n\nint b;\n#line 100 \"renamed.c\"\nint c;\n";
+        let directory = env::temp_dir().join(format!("cust-{}-printer", process::id()));
+        fs::create_dir_all(&directory).expect("the temporary directory is writable");
+        let path = directory.join("main.c");
+        fs::write(&path, source).expect("the temporary directory is writable");
+        let mut printer = Printer::new();
+        for token in Preprocessor::new(&path).filter_map(Result::ok) {
+            printer.print(&token);
+        }
+        fs::remove_dir_all(&directory).ok();
+        let expected = format!(
+            "# 2 \"{}\"\nint a = 1;\n# 13 \"{0}\"\nint b;\n# 100 \"renamed.c\"\nint c;\n",
+            path.display()
+        );
+        assert_eq!(printer.finish(), expected);
+    }
+
+    #[test]
+    fn nested_expansions_record_each_step_innermost_first() {
+        let source = "#define INNER(x) x + 1
+#define OUTER(y) INNER(y * 2)
+OUTER(a)
+#define S(x) #x
+#define P(a, b) a ## b
+#define W(z) S(z) P(z, 1)
+W(q)
+";
+        let directory = test_directory("provenance");
+        fs::create_dir_all(&directory).expect("the temporary directory is writable");
+        let path = directory.join("main.c");
+        fs::write(&path, source).expect("the temporary directory is writable");
+        let tokens: Vec<PreprocessedToken> =
+            Preprocessor::new(&path).filter_map(Result::ok).collect();
+        fs::remove_dir_all(&directory).ok();
+
+        // Each token where it was written, then each expansion as the macro,
+        // where it was used, where it was defined and how the token got in.
+        let at = |position: &Position| format!("{}:{}", position.line, position.column);
+        let chains: Vec<String> = tokens
+            .iter()
+            .map(|token| {
+                let mut chain = format!("{} {}", token.spelling, at(&token.written));
+                for step in &token.expansion {
+                    chain += &format!(
+                        " < {} {} {} {:?}",
+                        step.name,
+                        at(&step.invocation),
+                        at(&step.definition),
+                        step.origin
+                    );
+                }
+                chain
+            })
+            .collect();
+        assert_eq!(
+            chains,
+            [
+                r#"a 3:7 < INNER 2:18 1:9 Argument("x") < OUTER 3:1 2:9 Argument("y")"#,
+                r#"* 2:26 < INNER 2:18 1:9 Argument("x") < OUTER 3:1 2:9 Body"#,
+                r#"2 2:28 < INNER 2:18 1:9 Argument("x") < OUTER 3:1 2:9 Body"#,
+                r#"+ 1:20 < INNER 2:18 1:9 Body < OUTER 3:1 2:9 Body"#,
+                r#"1 1:22 < INNER 2:18 1:9 Body < OUTER 3:1 2:9 Body"#,
+                r#""q" 4:14 < S 6:14 4:9 Stringified("x") < W 7:1 6:9 Body"#,
+                r#"q1 7:3 < P 6:19 5:9 Pasted < W 7:1 6:9 Body"#,
+            ]
+        );
+        // Every token counts as being where the outermost macro was used.
+        let lines: Vec<usize> = tokens.iter().map(|token| token.line).collect();
+        assert_eq!(lines, [3, 3, 3, 3, 3, 7, 7]);
+    }
+}
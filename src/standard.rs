@@ -0,0 +1,83 @@
+use std::fmt::Display;
+
+/// A revision of the C standard, which decides which words are keywords.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum Standard {
+    C89,
+    /// C89 with Amendment 1, which added digraphs.
+    C95,
+    C99,
+    C11,
+    #[default]
+    C17,
+    C23,
+}
+
+impl Standard {
+    /// Parses a name as given to `-std=`, including the GNU dialects and
+    /// the names used before a standard was published.
+    pub fn from_name(name: &str) -> Option<Standard> {
+        match name.to_ascii_lowercase().as_str() {
+            "c89" | "c90" | "ansi" | "gnu89" | "gnu90" | "iso9899:1990" => Some(Standard::C89),
+            "c95" | "iso9899:199409" => Some(Standard::C95),
+            "c99" | "c9x" | "gnu99" | "iso9899:1999" => Some(Standard::C99),
+            "c11" | "c1x" | "gnu11" | "iso9899:2011" => Some(Standard::C11),
+            "c17" | "c18" | "gnu17" | "gnu18" | "iso9899:2017" | "iso9899:2018" => {
+                Some(Standard::C17)
+            }
+            "c23" | "c2x" | "gnu23" | "gnu2x" | "iso9899:2024" => Some(Standard::C23),
+            _ => None,
+        }
+    }
+
+    /// The value of `__STDC_VERSION__`, which C89 does not define.
+    pub fn version(&self) -> Option<i64> {
+        match self {
+            Standard::C89 => None,
+            Standard::C95 => Some(199409),
+            Standard::C99 => Some(199901),
+            Standard::C11 => Some(201112),
+            Standard::C17 => Some(201710),
+            Standard::C23 => Some(202311),
+        }
+    }
+}
+
+/// A compiler's extensions to the standard language.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Extension {
+    Gnu,
+    Msvc,
+}
+
+impl Extension {
+    pub fn from_name(name: &str) -> Option<Extension> {
+        match name.to_ascii_lowercase().as_str() {
+            "gnu" | "gcc" | "clang" => Some(Extension::Gnu),
+            "msvc" | "ms" => Some(Extension::Msvc),
+            _ => None,
+        }
+    }
+}
+
+impl Display for Standard {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Standard::C89 => write!(f, "C89"),
+            Standard::C95 => write!(f, "C95"),
+            Standard::C99 => write!(f, "C99"),
+            Standard::C11 => write!(f, "C11"),
+            Standard::C17 => write!(f, "C17"),
+            Standard::C23 => write!(f, "C23"),
+        }
+    }
+}
+
+impl Display for Extension {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Extension::Gnu => write!(f, "GNU"),
+            Extension::Msvc => write!(f, "MSVC"),
+        }
+    }
+}
@@ -0,0 +1,287 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    mem::size_of,
+    sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard},
+};
+
+/// An interned name. Symbols from the same interner are equal exactly when
+/// their names are, so comparing names costs an integer comparison.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Symbol(u32);
+
+/// The names the preprocessor looks for, which every interner starts with
+/// so that their symbols are the constants on [`Symbol`].
+const PREDEFINED: [&str; 5] = [
+    "defined",
+    "__has_include",
+    "__FILE__",
+    "__LINE__",
+    "__VA_ARGS__",
+];
+
+impl Symbol {
+    pub const DEFINED: Symbol = Symbol(0);
+    pub const HAS_INCLUDE: Symbol = Symbol(1);
+    pub const FILE: Symbol = Symbol(2);
+    pub const LINE: Symbol = Symbol(3);
+    pub const VA_ARGS: Symbol = Symbol(4);
+
+    /// The position of the name in its interner, counting from zero in the
+    /// order names were first seen, after the predefined ones.
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+/// A table that stores each distinct name once and hands out a [`Symbol`]
+/// for it.
+#[derive(Debug)]
+pub struct Interner {
+    symbols: HashMap<Arc<str>, Symbol>,
+    names: Vec<Arc<str>>,
+}
+
+impl Default for Interner {
+    fn default() -> Self {
+        let mut interner = Interner {
+            symbols: HashMap::new(),
+            names: vec![],
+        };
+        for name in PREDEFINED {
+            interner.intern(name);
+        }
+        interner
+    }
+}
+
+impl Interner {
+    pub fn new() -> Self {
+        Interner::default()
+    }
+
+    /// The symbol for `name`, adding the name if it is new.
+    pub fn intern(&mut self, name: &str) -> Symbol {
+        if let Some(symbol) = self.symbols.get(name) {
+            return *symbol;
+        }
+        let index = u32::try_from(self.names.len()).expect("fewer than 2^32 distinct names");
+        let symbol = Symbol(index);
+        let name: Arc<str> = Arc::from(name);
+        self.names.push(name.clone());
+        self.symbols.insert(name, symbol);
+        symbol
+    }
+
+    /// The symbol for `name` if it has been interned.
+    pub fn get(&self, name: &str) -> Option<Symbol> {
+        self.symbols.get(name).copied()
+    }
+
+    /// The name behind `symbol`. Panics if the symbol came from another
+    /// interner that has more names than this one.
+    pub fn resolve(&self, symbol: Symbol) -> &str {
+        &self.names[symbol.index()]
+    }
+
+    /// The number of distinct names interned, not counting the predefined
+    /// ones.
+    pub fn len(&self) -> usize {
+        self.names.len() - PREDEFINED.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// An estimate in bytes of the heap memory the table holds: the names
+    /// with their reference counts, and the capacity of the map and the list
+    /// of names.
+    pub fn memory_usage(&self) -> usize {
+        let names: usize = self
+            .names
+            .iter()
+            .map(|name| name.len() + 2 * size_of::<usize>())
+            .sum();
+        // Each map slot also has a control byte.
+        let map = self.symbols.capacity() * (size_of::<(Arc<str>, Symbol)>() + 1);
+        names + map + self.names.capacity() * size_of::<Arc<str>>()
+    }
+}
+
+/// An [`Interner`] behind a lock, for threads lexing different files into
+/// one table. Names already interned only take the lock for reading.
+#[derive(Debug, Default)]
+pub struct SharedInterner {
+    interner: RwLock<Interner>,
+}
+
+impl SharedInterner {
+    pub fn new() -> Self {
+        SharedInterner::default()
+    }
+
+    /// The symbol for `name`, adding the name if it is new.
+    pub fn intern(&self, name: &str) -> Symbol {
+        if let Some(symbol) = self.read().get(name) {
+            return symbol;
+        }
+        // Another thread may have added the name in between, which
+        // `Interner::intern` allows for.
+        self.write().intern(name)
+    }
+
+    pub fn get(&self, name: &str) -> Option<Symbol> {
+        self.read().get(name)
+    }
+
+    /// The name behind `symbol`, shared rather than borrowed since the
+    /// table may grow once the lock is released.
+    pub fn resolve(&self, symbol: Symbol) -> Arc<str> {
+        self.read().names[symbol.index()].clone()
+    }
+
+    pub fn len(&self) -> usize {
+        self.read().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.read().is_empty()
+    }
+
+    /// See [`Interner::memory_usage`].
+    pub fn memory_usage(&self) -> usize {
+        self.read().memory_usage()
+    }
+
+    /// The table, once no other thread uses it.
+    pub fn into_inner(self) -> Interner {
+        self.interner
+            .into_inner()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    // Interning cannot panic between updating the list and the map, so a
+    // lock poisoned by a panicking thread still guards a consistent table.
+    fn read(&self) -> RwLockReadGuard<'_, Interner> {
+        self.interner
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, Interner> {
+        self.interner
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Somewhere the [`Lexer`](crate::Lexer) can intern the names of
+/// identifiers and keywords, given to it with
+/// [`with_interner`](crate::Lexer::with_interner).
+pub trait Intern {
+    fn intern(&mut self, name: &str) -> Symbol;
+}
+
+impl Intern for Interner {
+    fn intern(&mut self, name: &str) -> Symbol {
+        Interner::intern(self, name)
+    }
+}
+
+impl Intern for &SharedInterner {
+    fn intern(&mut self, name: &str) -> Symbol {
+        SharedInterner::intern(self, name)
+    }
+}
+
+/// An interner shared by lexers on one thread, such as those the
+/// [`Preprocessor`](crate::Preprocessor) runs while it holds the table.
+impl Intern for &RefCell<Interner> {
+    fn intern(&mut self, name: &str) -> Symbol {
+        self.borrow_mut().intern(name)
+    }
+}
+
+impl<T: Intern + ?Sized> Intern for &mut T {
+    fn intern(&mut self, name: &str) -> Symbol {
+        T::intern(self, name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+
+    #[test]
+    fn the_same_name_gets_the_same_symbol() {
+        let mut interner = Interner::new();
+        assert!(interner.is_empty());
+        let first = interner.intern("count");
+        let second = interner.intern("total");
+        assert_ne!(first, second);
+        assert_eq!(interner.intern("count"), first);
+        assert_eq!(interner.get("total"), Some(second));
+        assert_eq!(interner.get("missing"), None);
+        assert_eq!(interner.resolve(first), "count");
+        assert_eq!(interner.resolve(second), "total");
+        assert_eq!(interner.len(), 2);
+    }
+
+    #[test]
+    fn predefined_names_have_constant_symbols() {
+        let mut interner = Interner::new();
+        for (name, symbol) in [
+            ("defined", Symbol::DEFINED),
+            ("__has_include", Symbol::HAS_INCLUDE),
+            ("__FILE__", Symbol::FILE),
+            ("__LINE__", Symbol::LINE),
+            ("__VA_ARGS__", Symbol::VA_ARGS),
+        ] {
+            assert_eq!(interner.intern(name), symbol, "{}", name);
+            assert_eq!(interner.resolve(symbol), name);
+            assert_eq!(SharedInterner::new().intern(name), symbol, "{}", name);
+        }
+        assert!(interner.is_empty());
+    }
+
+    #[test]
+    fn threads_share_one_table() {
+        let interner = SharedInterner::new();
+        let names: Vec<String> = (0..200)
+            .map(|index| format!("name{}", index % 50))
+            .collect();
+        let symbols: Vec<Vec<Symbol>> = thread::scope(|scope| {
+            let workers: Vec<_> = (0..4)
+                .map(|worker| {
+                    let (interner, names) = (&interner, &names);
+                    scope.spawn(move || {
+                        // Each thread meets the names in a different order.
+                        names
+                            .iter()
+                            .cycle()
+                            .skip(worker * 37)
+                            .take(names.len())
+                            .map(|name| interner.intern(name))
+                            .collect()
+                    })
+                })
+                .collect();
+            workers
+                .into_iter()
+                .map(|worker| worker.join().unwrap())
+                .collect()
+        });
+        assert_eq!(interner.len(), 50);
+        for (worker, symbols) in symbols.iter().enumerate() {
+            for (name, symbol) in names.iter().cycle().skip(worker * 37).zip(symbols) {
+                assert_eq!(&*interner.resolve(*symbol), name);
+                assert_eq!(interner.get(name), Some(*symbol));
+            }
+        }
+        let interner = interner.into_inner();
+        assert_eq!(interner.resolve(symbols[0][0]), names[0]);
+    }
+}
@@ -1,89 +1,190 @@
-use std::fmt::Display;
+use std::{borrow::Cow, fmt::Display};
 
 use tabled::Tabled;
 
-#[derive(Tabled)]
-pub struct Token {
-    pub line: usize,
-    pub block: usize,
-    pub token_type: TokenType,
+use crate::{
+    literal::{CharacterConstant, StringLiteral},
+    number::Number,
+    standard::{Extension, Standard},
+    symbol::Symbol,
+};
+
+/// A token and where it was found. Its payload borrows from the source it
+/// was lexed from; [`Token::into_owned`] detaches it.
+#[derive(Clone, Tabled)]
+pub struct Token<'src> {
+    pub span: Span,
+    pub token_type: TokenType<'src>,
+    /// How the token was written if it is a digraph, such as `<:` for `[`.
+    #[tabled(skip)]
+    pub digraph: Option<&'static str>,
+    /// The interned name of an identifier or keyword, if the lexer was given
+    /// an interner.
+    #[tabled(skip)]
+    pub symbol: Option<Symbol>,
+}
+
+impl Token<'_> {
+    /// The same token with every payload copied out of the source, for
+    /// keeping after the source is gone.
+    pub fn into_owned(self) -> Token<'static> {
+        Token {
+            span: self.span,
+            token_type: self.token_type.into_owned(),
+            digraph: self.digraph,
+            symbol: self.symbol,
+        }
+    }
 }
 
-impl Display for Token {
+impl Display for Token<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match &self.token_type {
             TokenType::Identifier(identifier) => {
                 write!(
                     f,
-                    "Identifier \"{}\" in line {} and block {}",
-                    identifier, self.line, self.block
+                    "Identifier \"{}\" in line {} and column {}",
+                    identifier, self.span.start.line, self.span.start.column
                 )
             }
             TokenType::Comment(comment) => {
                 write!(
                     f,
-                    "Comment \"{}\" starting from line {} and block {}",
-                    comment, self.line, self.block
+                    "Comment \"{}\" starting from line {} and column {}",
+                    comment, self.span.start.line, self.span.start.column
                 )
             }
             TokenType::Operator(operator) => {
                 write!(
                     f,
-                    "Operator \"{}\" found in line {} and block {}",
-                    operator, self.line, self.block
+                    "Operator \"{}\" found in line {} and column {}",
+                    operator, self.span.start.line, self.span.start.column
                 )
             }
             TokenType::Literal(literal) => {
                 write!(
                     f,
-                    "Literal \"{}\" found in line {} and block {}",
-                    literal, self.line, self.block
+                    "Literal \"{}\" found in line {} and column {}",
+                    literal, self.span.start.line, self.span.start.column
                 )
             }
             TokenType::Keyword(keyword) => {
                 write!(
                     f,
-                    "keyword \"{}\" found in line {} and block {}",
-                    keyword, self.line, self.block
+                    "keyword \"{}\" found in line {} and column {}",
+                    keyword, self.span.start.line, self.span.start.column
                 )
             }
             TokenType::Number(number) => {
                 write!(
                     f,
-                    "Number \"{}\" found in line {} and block {}",
-                    number, self.line, self.block
+                    "Number \"{}\" found in line {} and column {}",
+                    number, self.span.start.line, self.span.start.column
                 )
             }
             TokenType::Delimiter(delimiter) => {
                 write!(
                     f,
-                    "Delimiter \"{}\" found in line {} and block {}",
-                    delimiter, self.line, self.block
+                    "Delimiter \"{}\" found in line {} and column {}",
+                    delimiter, self.span.start.line, self.span.start.column
                 )
             }
             TokenType::Character(char) => {
                 write!(
                     f,
-                    "Character \"{}\" found in line {} and block {}",
-                    char, self.line, self.block
+                    "Character \"{}\" found in line {} and column {}",
+                    char, self.span.start.line, self.span.start.column
+                )
+            }
+            TokenType::Directive(directive, _) => {
+                write!(
+                    f,
+                    "Directive \"#{}\" found in line {} and column {}",
+                    directive, self.span.start.line, self.span.start.column
+                )
+            }
+            TokenType::HeaderName(header_name) => {
+                write!(
+                    f,
+                    "Header name {} found in line {} and column {}",
+                    header_name, self.span.start.line, self.span.start.column
+                )
+            }
+            TokenType::Error(text) => {
+                write!(
+                    f,
+                    "Unrecognised input \"{}\" found in line {} and column {}",
+                    text, self.span.start.line, self.span.start.column
                 )
             }
         }
     }
 }
 
-pub enum TokenType {
-    Identifier(String),
-    Comment(String),
+/// The kind of a token and its payload. Text is borrowed from the source
+/// unless a line splice or a universal character name meant it had to be
+/// rewritten.
+#[derive(Clone)]
+pub enum TokenType<'src> {
+    /// An identifier's name with any universal character names decoded, so
+    /// that names compare equal however they were written.
+    Identifier(Cow<'src, str>),
+    Comment(Cow<'src, str>),
     Operator(Operator),
-    Literal(String),
+    Literal(StringLiteral<'src>),
     Keyword(Keyword),
-    Number(String),
+    Number(Number<'src>),
     Delimiter(Delimiter),
-    Character(char),
+    Character(CharacterConstant<'src>),
+    /// A preprocessing directive together with the rest of its line.
+    Directive(Directive, Vec<Token<'src>>),
+    /// The file named by `#include` or `#embed`.
+    HeaderName(HeaderName<'src>),
+    /// Input the lexer could not make sense of and skipped.
+    Error(Cow<'src, str>),
+}
+
+impl TokenType<'_> {
+    /// The same payload copied out of the source.
+    pub fn into_owned(self) -> TokenType<'static> {
+        match self {
+            TokenType::Identifier(value) => TokenType::Identifier(Cow::Owned(value.into_owned())),
+            TokenType::Comment(value) => TokenType::Comment(Cow::Owned(value.into_owned())),
+            TokenType::Operator(value) => TokenType::Operator(value),
+            TokenType::Literal(value) => TokenType::Literal(value.into_owned()),
+            TokenType::Keyword(value) => TokenType::Keyword(value),
+            TokenType::Number(value) => TokenType::Number(value.into_owned()),
+            TokenType::Delimiter(value) => TokenType::Delimiter(value),
+            TokenType::Character(value) => TokenType::Character(value.into_owned()),
+            TokenType::Directive(directive, tokens) => TokenType::Directive(
+                directive,
+                tokens.into_iter().map(Token::into_owned).collect(),
+            ),
+            TokenType::HeaderName(value) => TokenType::HeaderName(value.into_owned()),
+            TokenType::Error(value) => TokenType::Error(Cow::Owned(value.into_owned())),
+        }
+    }
+
+    /// The token's payload as text, e.g. the name of an identifier or the
+    /// spelling of a number.
+    pub fn content(&self) -> String {
+        match self {
+            TokenType::Identifier(value) | TokenType::Comment(value) | TokenType::Error(value) => {
+                value.to_string()
+            }
+            TokenType::Literal(value) => value.to_string(),
+            TokenType::Operator(value) => value.to_string(),
+            TokenType::Keyword(value) => value.to_string(),
+            TokenType::Number(value) => value.to_string(),
+            TokenType::Delimiter(value) => value.to_string(),
+            TokenType::Character(value) => value.to_string(),
+            TokenType::Directive(value, _) => format!("#{}", value),
+            TokenType::HeaderName(value) => value.to_string(),
+        }
+    }
 }
 
-impl Display for TokenType {
+impl Display for TokenType<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match &self {
             TokenType::Identifier(_) => write!(f, "Identifier"),
@@ -94,11 +195,96 @@ impl Display for TokenType {
             TokenType::Number(_) => write!(f, "Number"),
             TokenType::Delimiter(_) => write!(f, "Delimiter"),
             TokenType::Character(_) => write!(f, "Character"),
+            TokenType::Directive(..) => write!(f, "Directive"),
+            TokenType::HeaderName(_) => write!(f, "HeaderName"),
+            TokenType::Error(_) => write!(f, "Error"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Directive {
+    Include,
+    Define,
+    Undef,
+    If,
+    Ifdef,
+    Ifndef,
+    Elif,
+    Elifdef,
+    Elifndef,
+    Else,
+    Endif,
+    Line,
+    Error,
+    Warning,
+    Pragma,
+    Embed,
+}
+
+impl Directive {
+    /// The first standard with this directive. Before it, the name is an
+    /// unknown directive.
+    pub fn standard(&self) -> Standard {
+        match self {
+            Directive::Elifdef | Directive::Elifndef => Standard::C23,
+            _ => Standard::C89,
+        }
+    }
+}
+
+impl Display for Directive {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Directive::Include => write!(f, "include"),
+            Directive::Define => write!(f, "define"),
+            Directive::Undef => write!(f, "undef"),
+            Directive::If => write!(f, "if"),
+            Directive::Ifdef => write!(f, "ifdef"),
+            Directive::Ifndef => write!(f, "ifndef"),
+            Directive::Elif => write!(f, "elif"),
+            Directive::Elifdef => write!(f, "elifdef"),
+            Directive::Elifndef => write!(f, "elifndef"),
+            Directive::Else => write!(f, "else"),
+            Directive::Endif => write!(f, "endif"),
+            Directive::Line => write!(f, "line"),
+            Directive::Error => write!(f, "error"),
+            Directive::Warning => write!(f, "warning"),
+            Directive::Pragma => write!(f, "pragma"),
+            Directive::Embed => write!(f, "embed"),
+        }
+    }
+}
+
+/// A header name, spelled `<name>` to search only the system directories or
+/// `"name"` to look next to the including file first.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeaderName<'src> {
+    pub name: Cow<'src, str>,
+    pub angled: bool,
+}
+
+impl HeaderName<'_> {
+    /// The same name copied out of the source.
+    pub fn into_owned(self) -> HeaderName<'static> {
+        HeaderName {
+            name: Cow::Owned(self.name.into_owned()),
+            angled: self.angled,
+        }
+    }
+}
+
+impl Display for HeaderName<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.angled {
+            write!(f, "<{}>", self.name)
+        } else {
+            write!(f, "\"{}\"", self.name)
         }
     }
 }
 
-#[derive(Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Operator {
     // Arithmetic Operators
     Addition,
@@ -140,49 +326,24 @@ pub enum Operator {
     BitwiseAndAssignment,
     BitwiseOrAssignment,
     BitwiseExclusiveOrAndAssignment,
+
+    // Conditional Operators
+    Conditional,
+    /// GNU's `a ?: b`, which yields `a` unless it is zero.
+    Elvis,
+
+    // Member Access Operators
+    MemberAccess,
+    PointerMemberAccess,
 }
 
 impl Display for Operator {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Operator::Addition => write!(f, "+"),
-            Operator::Subtraction => write!(f, "-"),
-            Operator::Multiplication => write!(f, "*"),
-            Operator::Division => write!(f, "/"),
-            Operator::Remainder => write!(f, "%"),
-            Operator::Increment => write!(f, "++"),
-            Operator::Decrement => write!(f, "--"),
-            Operator::Equal => write!(f, "=="),
-            Operator::NotEqual => write!(f, "!="),
-            Operator::Bigger => write!(f, ">"),
-            Operator::BiggerOrEqual => write!(f, ">="),
-            Operator::Smaller => write!(f, "<"),
-            Operator::SmallerOrEqual => write!(f, "<="),
-            Operator::LogicalAnd => write!(f, "&&"),
-            Operator::LogicalOr => write!(f, "||"),
-            Operator::BitwiseAnd => write!(f, "&"),
-            Operator::LogicalNegation => write!(f, "!"),
-            Operator::BitwiseOr => write!(f, "|"),
-            Operator::ExclusiveOr => write!(f, "^"),
-            Operator::BitwiseNegation => write!(f, "~"),
-            Operator::LeftShift => write!(f, "<<"),
-            Operator::RightShift => write!(f, ">>"),
-            Operator::Assignment => write!(f, "="),
-            Operator::AdditionAndAssignment => write!(f, "+="),
-            Operator::SubtractionAndAssignment => write!(f, "-="),
-            Operator::MultiplicationAndAssignment => write!(f, "*="),
-            Operator::DivisionAndAssignment => write!(f, "/="),
-            Operator::RemainderAndAssignment => write!(f, "%="),
-            Operator::RightShiftAndAssignment => write!(f, ">>="),
-            Operator::LeftShiftAndAssignment => write!(f, "<<="),
-            Operator::BitwiseAndAssignment => write!(f, "&="),
-            Operator::BitwiseOrAssignment => write!(f, "|="),
-            Operator::BitwiseExclusiveOrAndAssignment => write!(f, "^="),
-        }
+        f.write_str(Punctuator::Operator(*self).spelling())
     }
 }
 
-#[derive(Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Delimiter {
     Comma,
     SemiColon,
@@ -192,24 +353,135 @@ pub enum Delimiter {
     ClosingBracket,
     OpeningCurlyBracket,
     ClosingCurlyBracket,
+    Colon,
+    /// C23's `::`, which attribute names use.
+    DoubleColon,
+    Ellipsis,
+    Hash,
+    DoubleHash,
 }
 
 impl Display for Delimiter {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(Punctuator::Delimiter(*self).spelling())
+    }
+}
+
+/// An operator or delimiter, which C together calls punctuators.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Punctuator {
+    Operator(Operator),
+    Delimiter(Delimiter),
+}
+
+impl Punctuator {
+    /// The usual spelling, which is the first in [`PUNCTUATORS`].
+    pub fn spelling(self) -> &'static str {
+        PUNCTUATORS
+            .iter()
+            .find(|(_, punctuator, _)| *punctuator == self)
+            .map_or("", |(spelling, _, _)| spelling)
+    }
+
+    /// The first standard in which this is a punctuator. Before it, the
+    /// spelling lexes as shorter punctuators.
+    pub fn standard(self) -> Standard {
         match self {
-            Delimiter::Comma => write!(f, ","),
-            Delimiter::SemiColon => write!(f, ";"),
-            Delimiter::OpeningParenthesis => write!(f, "("),
-            Delimiter::ClosingParenthesis => write!(f, ")"),
-            Delimiter::OpeningBracket => write!(f, "["),
-            Delimiter::ClosingBracket => write!(f, "]"),
-            Delimiter::OpeningCurlyBracket => write!(f, "{{"),
-            Delimiter::ClosingCurlyBracket => write!(f, "}}"),
+            Punctuator::Delimiter(Delimiter::DoubleColon) => Standard::C23,
+            _ => Standard::C89,
         }
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+impl Display for Punctuator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.spelling())
+    }
+}
+
+/// Every way to write a punctuator, and the extension it needs if it is
+/// not standard. The lexer takes the longest spelling the input starts
+/// with. A punctuator's usual spelling comes first; any later one, such as
+/// the digraph `<:` for `[`, is an alternative.
+pub const PUNCTUATORS: &[(&str, Punctuator, Option<Extension>)] = {
+    use Punctuator as P;
+    &[
+        // Arithmetic Operators
+        ("+", P::Operator(Operator::Addition), None),
+        ("-", P::Operator(Operator::Subtraction), None),
+        ("*", P::Operator(Operator::Multiplication), None),
+        ("/", P::Operator(Operator::Division), None),
+        ("%", P::Operator(Operator::Remainder), None),
+        ("++", P::Operator(Operator::Increment), None),
+        ("--", P::Operator(Operator::Decrement), None),
+        // Relational Operators
+        ("==", P::Operator(Operator::Equal), None),
+        ("!=", P::Operator(Operator::NotEqual), None),
+        (">", P::Operator(Operator::Bigger), None),
+        (">=", P::Operator(Operator::BiggerOrEqual), None),
+        ("<", P::Operator(Operator::Smaller), None),
+        ("<=", P::Operator(Operator::SmallerOrEqual), None),
+        // Logical and Bitwise Operators
+        ("&&", P::Operator(Operator::LogicalAnd), None),
+        ("||", P::Operator(Operator::LogicalOr), None),
+        ("&", P::Operator(Operator::BitwiseAnd), None),
+        ("!", P::Operator(Operator::LogicalNegation), None),
+        ("|", P::Operator(Operator::BitwiseOr), None),
+        ("^", P::Operator(Operator::ExclusiveOr), None),
+        ("~", P::Operator(Operator::BitwiseNegation), None),
+        ("<<", P::Operator(Operator::LeftShift), None),
+        (">>", P::Operator(Operator::RightShift), None),
+        // Assignment Operators
+        ("=", P::Operator(Operator::Assignment), None),
+        ("+=", P::Operator(Operator::AdditionAndAssignment), None),
+        ("-=", P::Operator(Operator::SubtractionAndAssignment), None),
+        (
+            "*=",
+            P::Operator(Operator::MultiplicationAndAssignment),
+            None,
+        ),
+        ("/=", P::Operator(Operator::DivisionAndAssignment), None),
+        ("%=", P::Operator(Operator::RemainderAndAssignment), None),
+        (">>=", P::Operator(Operator::RightShiftAndAssignment), None),
+        ("<<=", P::Operator(Operator::LeftShiftAndAssignment), None),
+        ("&=", P::Operator(Operator::BitwiseAndAssignment), None),
+        ("|=", P::Operator(Operator::BitwiseOrAssignment), None),
+        (
+            "^=",
+            P::Operator(Operator::BitwiseExclusiveOrAndAssignment),
+            None,
+        ),
+        // Conditional Operators
+        ("?", P::Operator(Operator::Conditional), None),
+        ("?:", P::Operator(Operator::Elvis), Some(Extension::Gnu)),
+        // Member Access Operators
+        (".", P::Operator(Operator::MemberAccess), None),
+        ("->", P::Operator(Operator::PointerMemberAccess), None),
+        // Delimiters
+        (",", P::Delimiter(Delimiter::Comma), None),
+        (";", P::Delimiter(Delimiter::SemiColon), None),
+        ("(", P::Delimiter(Delimiter::OpeningParenthesis), None),
+        (")", P::Delimiter(Delimiter::ClosingParenthesis), None),
+        ("[", P::Delimiter(Delimiter::OpeningBracket), None),
+        ("]", P::Delimiter(Delimiter::ClosingBracket), None),
+        ("{", P::Delimiter(Delimiter::OpeningCurlyBracket), None),
+        ("}", P::Delimiter(Delimiter::ClosingCurlyBracket), None),
+        (":", P::Delimiter(Delimiter::Colon), None),
+        ("::", P::Delimiter(Delimiter::DoubleColon), None),
+        ("...", P::Delimiter(Delimiter::Ellipsis), None),
+        ("#", P::Delimiter(Delimiter::Hash), None),
+        ("##", P::Delimiter(Delimiter::DoubleHash), None),
+        // Digraphs
+        ("<:", P::Delimiter(Delimiter::OpeningBracket), None),
+        (":>", P::Delimiter(Delimiter::ClosingBracket), None),
+        ("<%", P::Delimiter(Delimiter::OpeningCurlyBracket), None),
+        ("%>", P::Delimiter(Delimiter::ClosingCurlyBracket), None),
+        ("%:", P::Delimiter(Delimiter::Hash), None),
+        ("%:%:", P::Delimiter(Delimiter::DoubleHash), None),
+    ]
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Keyword {
     Auto,
     Break,
@@ -244,10 +516,124 @@ pub enum Keyword {
     Unsigned,
     Void,
     Volatile,
-    Wihle,
+    While,
     _Bool,
     _Complex,
     _Imaginary,
+    _Alignas,
+    _Alignof,
+    _Atomic,
+    _Generic,
+    _Noreturn,
+    _StaticAssert,
+    _ThreadLocal,
+    Alignas,
+    Alignof,
+    Bool,
+    Constexpr,
+    False,
+    Nullptr,
+    StaticAssert,
+    ThreadLocal,
+    True,
+    Typeof,
+    TypeofUnqual,
+    _BitInt,
+    _Decimal32,
+    _Decimal64,
+    _Decimal128,
+    Asm,
+    __Asm,
+    __Asm__,
+    __Attribute,
+    __Attribute__,
+    __Typeof,
+    __Typeof__,
+    __Extension__,
+    __Inline,
+    __Inline__,
+    __Restrict,
+    __Restrict__,
+    __Const,
+    __Const__,
+    __Volatile,
+    __Volatile__,
+    __Signed,
+    __Signed__,
+    __Alignof,
+    __Alignof__,
+    __Label__,
+    __AutoType,
+    __Int128,
+    __Real__,
+    __Imag__,
+    __Complex__,
+    __Thread,
+    __BuiltinVaArg,
+    __BuiltinVaList,
+    __BuiltinOffsetof,
+    __BuiltinTypesCompatibleP,
+    __BuiltinChooseExpr,
+    __Declspec,
+    __Forceinline,
+    __Int8,
+    __Int16,
+    __Int32,
+    __Int64,
+    __Cdecl,
+    __Stdcall,
+    __Fastcall,
+    __Vectorcall,
+    __Thiscall,
+    __Clrcall,
+    __Ptr32,
+    __Ptr64,
+    __Unaligned,
+    __W64,
+    __Based,
+    __Try,
+    __Except,
+    __Finally,
+    __Leave,
+    __Pragma,
+    __Assume,
+    __Noop,
+}
+
+impl Keyword {
+    /// The first standard in which this is a keyword.
+    pub fn standard(&self) -> Standard {
+        match self {
+            Keyword::Inline
+            | Keyword::Restrict
+            | Keyword::_Bool
+            | Keyword::_Complex
+            | Keyword::_Imaginary => Standard::C99,
+            Keyword::_Alignas
+            | Keyword::_Alignof
+            | Keyword::_Atomic
+            | Keyword::_Generic
+            | Keyword::_Noreturn
+            | Keyword::_StaticAssert
+            | Keyword::_ThreadLocal => Standard::C11,
+            Keyword::Alignas
+            | Keyword::Alignof
+            | Keyword::Bool
+            | Keyword::Constexpr
+            | Keyword::False
+            | Keyword::Nullptr
+            | Keyword::StaticAssert
+            | Keyword::ThreadLocal
+            | Keyword::True
+            | Keyword::Typeof
+            | Keyword::TypeofUnqual
+            | Keyword::_BitInt
+            | Keyword::_Decimal32
+            | Keyword::_Decimal64
+            | Keyword::_Decimal128 => Standard::C23,
+            _ => Standard::C89,
+        }
+    }
 }
 
 impl Display for Keyword {
@@ -286,10 +672,134 @@ impl Display for Keyword {
             Keyword::Unsigned => write!(f, "unsigned"),
             Keyword::Void => write!(f, "void"),
             Keyword::Volatile => write!(f, "volatile"),
-            Keyword::Wihle => write!(f, "wihle"),
+            Keyword::While => write!(f, "while"),
             Keyword::_Bool => write!(f, "_Bool"),
             Keyword::_Complex => write!(f, "_Complex"),
             Keyword::_Imaginary => write!(f, "_Imaginary"),
+            Keyword::_Alignas => write!(f, "_Alignas"),
+            Keyword::_Alignof => write!(f, "_Alignof"),
+            Keyword::_Atomic => write!(f, "_Atomic"),
+            Keyword::_Generic => write!(f, "_Generic"),
+            Keyword::_Noreturn => write!(f, "_Noreturn"),
+            Keyword::_StaticAssert => write!(f, "_Static_assert"),
+            Keyword::_ThreadLocal => write!(f, "_Thread_local"),
+            Keyword::Alignas => write!(f, "alignas"),
+            Keyword::Alignof => write!(f, "alignof"),
+            Keyword::Bool => write!(f, "bool"),
+            Keyword::Constexpr => write!(f, "constexpr"),
+            Keyword::False => write!(f, "false"),
+            Keyword::Nullptr => write!(f, "nullptr"),
+            Keyword::StaticAssert => write!(f, "static_assert"),
+            Keyword::ThreadLocal => write!(f, "thread_local"),
+            Keyword::True => write!(f, "true"),
+            Keyword::Typeof => write!(f, "typeof"),
+            Keyword::TypeofUnqual => write!(f, "typeof_unqual"),
+            Keyword::_BitInt => write!(f, "_BitInt"),
+            Keyword::_Decimal32 => write!(f, "_Decimal32"),
+            Keyword::_Decimal64 => write!(f, "_Decimal64"),
+            Keyword::_Decimal128 => write!(f, "_Decimal128"),
+            Keyword::Asm => write!(f, "asm"),
+            Keyword::__Asm => write!(f, "__asm"),
+            Keyword::__Asm__ => write!(f, "__asm__"),
+            Keyword::__Attribute => write!(f, "__attribute"),
+            Keyword::__Attribute__ => write!(f, "__attribute__"),
+            Keyword::__Typeof => write!(f, "__typeof"),
+            Keyword::__Typeof__ => write!(f, "__typeof__"),
+            Keyword::__Extension__ => write!(f, "__extension__"),
+            Keyword::__Inline => write!(f, "__inline"),
+            Keyword::__Inline__ => write!(f, "__inline__"),
+            Keyword::__Restrict => write!(f, "__restrict"),
+            Keyword::__Restrict__ => write!(f, "__restrict__"),
+            Keyword::__Const => write!(f, "__const"),
+            Keyword::__Const__ => write!(f, "__const__"),
+            Keyword::__Volatile => write!(f, "__volatile"),
+            Keyword::__Volatile__ => write!(f, "__volatile__"),
+            Keyword::__Signed => write!(f, "__signed"),
+            Keyword::__Signed__ => write!(f, "__signed__"),
+            Keyword::__Alignof => write!(f, "__alignof"),
+            Keyword::__Alignof__ => write!(f, "__alignof__"),
+            Keyword::__Label__ => write!(f, "__label__"),
+            Keyword::__AutoType => write!(f, "__auto_type"),
+            Keyword::__Int128 => write!(f, "__int128"),
+            Keyword::__Real__ => write!(f, "__real__"),
+            Keyword::__Imag__ => write!(f, "__imag__"),
+            Keyword::__Complex__ => write!(f, "__complex__"),
+            Keyword::__Thread => write!(f, "__thread"),
+            Keyword::__BuiltinVaArg => write!(f, "__builtin_va_arg"),
+            Keyword::__BuiltinVaList => write!(f, "__builtin_va_list"),
+            Keyword::__BuiltinOffsetof => write!(f, "__builtin_offsetof"),
+            Keyword::__BuiltinTypesCompatibleP => write!(f, "__builtin_types_compatible_p"),
+            Keyword::__BuiltinChooseExpr => write!(f, "__builtin_choose_expr"),
+            Keyword::__Declspec => write!(f, "__declspec"),
+            Keyword::__Forceinline => write!(f, "__forceinline"),
+            Keyword::__Int8 => write!(f, "__int8"),
+            Keyword::__Int16 => write!(f, "__int16"),
+            Keyword::__Int32 => write!(f, "__int32"),
+            Keyword::__Int64 => write!(f, "__int64"),
+            Keyword::__Cdecl => write!(f, "__cdecl"),
+            Keyword::__Stdcall => write!(f, "__stdcall"),
+            Keyword::__Fastcall => write!(f, "__fastcall"),
+            Keyword::__Vectorcall => write!(f, "__vectorcall"),
+            Keyword::__Thiscall => write!(f, "__thiscall"),
+            Keyword::__Clrcall => write!(f, "__clrcall"),
+            Keyword::__Ptr32 => write!(f, "__ptr32"),
+            Keyword::__Ptr64 => write!(f, "__ptr64"),
+            Keyword::__Unaligned => write!(f, "__unaligned"),
+            Keyword::__W64 => write!(f, "__w64"),
+            Keyword::__Based => write!(f, "__based"),
+            Keyword::__Try => write!(f, "__try"),
+            Keyword::__Except => write!(f, "__except"),
+            Keyword::__Finally => write!(f, "__finally"),
+            Keyword::__Leave => write!(f, "__leave"),
+            Keyword::__Pragma => write!(f, "__pragma"),
+            Keyword::__Assume => write!(f, "__assume"),
+            Keyword::__Noop => write!(f, "__noop"),
         }
     }
 }
+
+/// A point in the source. `offset` is in bytes; lines and columns are
+/// 1-based, with columns counting characters rather than bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Location {
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
+    /// The column in UTF-16 code units, only tracked when the lexer is asked
+    /// to.
+    pub utf16_column: Option<usize>,
+}
+
+impl Location {
+    /// The very beginning of a source.
+    pub const START: Location = Location {
+        offset: 0,
+        line: 1,
+        column: 1,
+        utf16_column: None,
+    };
+}
+
+impl Display for Location {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "in line {} and column {}", self.line, self.column)
+    }
+}
+
+/// The source range a token was read from. `end` is the location just past
+/// its last character.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub start: Location,
+    pub end: Location,
+}
+
+impl Display for Span {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}:{}-{}:{}",
+            self.start.line, self.start.column, self.end.line, self.end.column
+        )
+    }
+}
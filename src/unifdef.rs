@@ -0,0 +1,686 @@
+use std::{cell::RefCell, collections::HashMap, path::PathBuf};
+
+use crate::{
+    error::PreprocessError,
+    expression,
+    lexer::Lexer,
+    standard::{Extension, Standard},
+    symbol::{Interner, Symbol},
+    tokens::{Delimiter, Directive, Location, Operator, Token, TokenType},
+};
+
+/// What the known macros say about a condition: true, false, or `None` if
+/// it depends on a macro that is not known.
+type Outcome = Option<bool>;
+
+/// What the command line says about a macro.
+#[derive(Clone)]
+enum Definition {
+    /// An object-like macro and its replacement text.
+    Object(String),
+    /// A function-like macro, which is defined but whose uses cannot be
+    /// evaluated without its arguments.
+    Function,
+    Undefined,
+}
+
+/// Where a line of a conditional group stands.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Branch {
+    /// The lines are kept, along with any directives.
+    Live,
+    /// The lines are removed.
+    Dead,
+}
+
+/// An `#if` group being processed.
+struct Conditional {
+    /// The whole group is inside a removed branch.
+    ignored: bool,
+    /// A branch whose condition was known to be true has been seen, so the
+    /// rest are removed.
+    resolved: bool,
+    /// A branch whose condition is unknown has been kept, so the group's
+    /// directives stay in the output.
+    kept: bool,
+    branch: Branch,
+    location: Location,
+}
+
+/// Resolves the conditionals of a file whose outcome follows from a fixed
+/// set of defined and undefined macros, like `unifdef`.
+///
+/// Dead branches and the directives of resolved groups are removed. Every
+/// other line, including conditionals that depend on macros not in the set,
+/// is copied byte for byte.
+pub struct Unifdef {
+    path: PathBuf,
+    standard: Standard,
+    extensions: Vec<Extension>,
+    definitions: HashMap<Symbol, Definition>,
+    /// The names of macros and of the identifiers lexed.
+    interner: RefCell<Interner>,
+}
+
+impl Unifdef {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Unifdef {
+            path: path.into(),
+            standard: Standard::default(),
+            extensions: vec![],
+            definitions: HashMap::new(),
+            interner: RefCell::new(Interner::new()),
+        }
+    }
+
+    /// Selects the C standard, which decides the keywords.
+    pub fn with_standard(mut self, standard: Standard) -> Self {
+        self.standard = standard;
+        self
+    }
+
+    /// Enables a compiler's extensions in the lexer.
+    pub fn with_extension(mut self, extension: Extension) -> Self {
+        if !self.extensions.contains(&extension) {
+            self.extensions.push(extension);
+        }
+        self
+    }
+
+    /// Treats `name` as defined to `value`, as `-D name=value` does. A name
+    /// with parameters, such as `f(x)`, defines a function-like macro.
+    pub fn with_definition(mut self, name: &str, value: &str) -> Self {
+        let (name, definition) = match name.split_once('(') {
+            Some((name, _)) => (name, Definition::Function),
+            None => (name, Definition::Object(value.to_string())),
+        };
+        let name = self.interner.get_mut().intern(name);
+        self.definitions.insert(name, definition);
+        self
+    }
+
+    /// Treats `name` as undefined, as `-U name` does.
+    pub fn with_undefinition(mut self, name: &str) -> Self {
+        let name = self.interner.get_mut().intern(name);
+        self.definitions.insert(name, Definition::Undefined);
+        self
+    }
+
+    fn lexer<'src>(&'src self, text: &'src str) -> Lexer<'src> {
+        let mut lexer = Lexer::new(text)
+            .with_standard(self.standard)
+            .with_interner(&self.interner);
+        for extension in &self.extensions {
+            lexer = lexer.with_extension(*extension);
+        }
+        lexer
+    }
+
+    /// Returns `source` with the known conditionals resolved.
+    pub fn process(&self, source: &str) -> Result<String, PreprocessError> {
+        let mut output = String::with_capacity(source.len());
+        let mut conditionals: Vec<Conditional> = vec![];
+        // Everything before `copied` has been written or removed.
+        let mut copied = 0;
+        for token in self.lexer(source).filter_map(Result::ok) {
+            let TokenType::Directive(directive, tokens) = &token.token_type else {
+                continue;
+            };
+            let start = line_start(source, token.span.start.offset);
+            let end = line_end(source, token.span.end.offset);
+            let live = conditionals
+                .last()
+                .is_none_or(|conditional| conditional.branch == Branch::Live);
+            if live {
+                output.push_str(&source[copied..start]);
+            }
+            copied = end;
+            let line = &source[start..end];
+            let location = token.span.start;
+
+            match directive {
+                Directive::If | Directive::Ifdef | Directive::Ifndef => {
+                    let condition = if live {
+                        self.condition(*directive, tokens)
+                    } else {
+                        None
+                    };
+                    let conditional = Conditional {
+                        ignored: !live,
+                        resolved: condition == Some(true),
+                        kept: live && condition.is_none(),
+                        branch: match condition {
+                            Some(false) => Branch::Dead,
+                            _ if !live => Branch::Dead,
+                            _ => Branch::Live,
+                        },
+                        location,
+                    };
+                    if conditional.kept {
+                        output.push_str(line);
+                    }
+                    conditionals.push(conditional);
+                }
+                Directive::Elif | Directive::Elifdef | Directive::Elifndef | Directive::Else => {
+                    let Some(conditional) = conditionals.last_mut() else {
+                        return Err(self.unbalanced(*directive, location));
+                    };
+                    if conditional.ignored {
+                        continue;
+                    }
+                    if conditional.resolved {
+                        conditional.branch = Branch::Dead;
+                        continue;
+                    }
+                    let condition = match directive {
+                        Directive::Else => Some(true),
+                        _ => self.condition(*directive, tokens),
+                    };
+                    conditional.branch = match condition {
+                        Some(false) => Branch::Dead,
+                        _ => Branch::Live,
+                    };
+                    match (condition, conditional.kept) {
+                        (Some(false), _) | (Some(true), false) => {}
+                        (Some(true), true) if *directive != Directive::Else => {
+                            // The earlier branches stay, so this one becomes
+                            // their `#else`.
+                            output.push_str(&replace_directive(line, "else", true));
+                        }
+                        (None, false) => {
+                            // Every branch before this one was removed, so
+                            // this starts the group.
+                            let name = match directive {
+                                Directive::Elifdef => "ifdef",
+                                Directive::Elifndef => "ifndef",
+                                _ => "if",
+                            };
+                            output.push_str(&replace_directive(line, name, false));
+                        }
+                        _ => output.push_str(line),
+                    }
+                    conditional.resolved = condition == Some(true);
+                    conditional.kept |= condition.is_none();
+                }
+                Directive::Endif => {
+                    let Some(conditional) = conditionals.pop() else {
+                        return Err(self.unbalanced(*directive, location));
+                    };
+                    if conditional.kept {
+                        output.push_str(line);
+                    }
+                }
+                _ => {
+                    // Other directives are copied with the text around them.
+                    copied = start;
+                }
+            }
+        }
+
+        if let Some(conditional) = conditionals.last() {
+            return Err(PreprocessError::UnterminatedConditional {
+                file: self.path.clone(),
+                location: conditional.location,
+            });
+        }
+        output.push_str(&source[copied..]);
+        Ok(output)
+    }
+
+    fn unbalanced(&self, directive: Directive, location: Location) -> PreprocessError {
+        PreprocessError::UnbalancedConditional {
+            directive,
+            file: self.path.clone(),
+            location,
+        }
+    }
+
+    /// Decides a condition from the known macros, or `None` if it depends
+    /// on anything else.
+    fn condition(&self, directive: Directive, tokens: &[Token]) -> Outcome {
+        let tokens: Vec<Token> = tokens
+            .iter()
+            .filter(|token| !matches!(token.token_type, TokenType::Comment(_)))
+            .cloned()
+            .collect();
+        match directive {
+            Directive::Ifdef | Directive::Ifndef | Directive::Elifdef | Directive::Elifndef => {
+                let [token] = tokens.as_slice() else {
+                    return None;
+                };
+                let defined = self.is_defined(name(token)?)?;
+                Some(defined == matches!(directive, Directive::Ifdef | Directive::Elifdef))
+            }
+            _ => {
+                let mut rest = tokens.as_slice();
+                let outcome = self.decide(&mut rest)?;
+                if rest.is_empty() {
+                    outcome
+                } else {
+                    None
+                }
+            }
+        }
+    }
+
+    /// Decides the `?:` expression at the start of `tokens`, moving past it,
+    /// so that an operand of `&&`, `||`, `!` or `?:` that is known decides
+    /// the outcome whatever the unknown ones are. Returns `None` if the
+    /// expression is malformed.
+    fn decide(&self, tokens: &mut &[Token]) -> Option<Outcome> {
+        let condition = self.decide_or(tokens)?;
+        let elvis = if eat(tokens, Operator::Elvis) {
+            true
+        } else if eat(tokens, Operator::Conditional) {
+            self.extensions.contains(&Extension::Gnu) && eat_colon(tokens)
+        } else {
+            return Some(condition);
+        };
+        if elvis {
+            // `a ?: b` is true exactly when `a || b` is.
+            return Some(or(condition, self.decide(tokens)?));
+        }
+        let first = self.decide(tokens)?;
+        if !eat_colon(tokens) {
+            return None;
+        }
+        let second = self.decide(tokens)?;
+        Some(match condition {
+            Some(true) => first,
+            Some(false) => second,
+            None if first == second => first,
+            None => None,
+        })
+    }
+
+    fn decide_or(&self, tokens: &mut &[Token]) -> Option<Outcome> {
+        let mut outcome = self.decide_and(tokens)?;
+        while eat(tokens, Operator::LogicalOr) {
+            outcome = or(outcome, self.decide_and(tokens)?);
+        }
+        Some(outcome)
+    }
+
+    fn decide_and(&self, tokens: &mut &[Token]) -> Option<Outcome> {
+        let mut outcome = self.decide_operand(tokens)?;
+        while eat(tokens, Operator::LogicalAnd) {
+            outcome = and(outcome, self.decide_operand(tokens)?);
+        }
+        Some(outcome)
+    }
+
+    /// Decides an operand of `&&` or `||`, which runs up to the next of
+    /// them, `?`, `:`, `,` or an unmatched `)`. A parenthesized condition,
+    /// perhaps negated with `!`, is decided in three values in turn; any
+    /// other operand is evaluated if every macro in it is known.
+    fn decide_operand(&self, tokens: &mut &[Token]) -> Option<Outcome> {
+        let mut depth = 0;
+        let end = tokens
+            .iter()
+            .position(|token| match &token.token_type {
+                TokenType::Delimiter(Delimiter::OpeningParenthesis) => {
+                    depth += 1;
+                    false
+                }
+                TokenType::Delimiter(Delimiter::ClosingParenthesis) if depth > 0 => {
+                    depth -= 1;
+                    false
+                }
+                TokenType::Delimiter(Delimiter::ClosingParenthesis) => true,
+                TokenType::Delimiter(Delimiter::Colon | Delimiter::Comma)
+                | TokenType::Operator(
+                    Operator::LogicalAnd
+                    | Operator::LogicalOr
+                    | Operator::Conditional
+                    | Operator::Elvis,
+                ) => depth == 0,
+                _ => false,
+            })
+            .unwrap_or(tokens.len());
+        let (operand, rest) = tokens.split_at(end);
+        *tokens = rest;
+        if depth > 0 {
+            return None;
+        }
+        let negations = operand
+            .iter()
+            .take_while(|token| is_operator(token, Operator::LogicalNegation))
+            .count();
+        let mut group = &operand[negations..];
+        if group_end(group) == Some(group.len()) {
+            group = &group[1..group.len() - 1];
+            let outcome = self.decide(&mut group)?;
+            if !group.is_empty() {
+                return None;
+            }
+            return Some(outcome.map(|outcome| outcome != (negations % 2 == 1)));
+        }
+        if operand.is_empty() {
+            return None;
+        }
+        let operand = self.substitute(operand, &mut vec![]);
+        let is_defined = |name: Symbol| self.is_defined(name) == Some(true);
+        Some(operand.and_then(|operand| {
+            expression::evaluate(&operand, &self.extensions, &is_defined, &|_| false)
+                .ok()
+                .map(|value| value.is_true())
+        }))
+    }
+
+    fn is_defined(&self, name: Symbol) -> Option<bool> {
+        match self.definitions.get(&name)? {
+            Definition::Undefined => Some(false),
+            Definition::Object(_) | Definition::Function => Some(true),
+        }
+    }
+
+    /// Replaces the known object-like macros in `tokens`, or returns `None`
+    /// if the tokens use a macro that is not known. `expanding` holds the
+    /// macros being replaced, which are left alone inside themselves.
+    fn substitute<'a>(
+        &'a self,
+        tokens: &[Token<'a>],
+        expanding: &mut Vec<Symbol>,
+    ) -> Option<Vec<Token<'a>>> {
+        let mut substituted = vec![];
+        let mut tokens = tokens.iter();
+        while let Some(token) = tokens.next() {
+            let Some(macro_name) = name(token) else {
+                substituted.push(token.clone());
+                continue;
+            };
+            if macro_name == Symbol::DEFINED {
+                // The operand names a macro rather than using it.
+                substituted.push(token.clone());
+                let mut operand = tokens.next()?;
+                if matches!(
+                    operand.token_type,
+                    TokenType::Delimiter(Delimiter::OpeningParenthesis)
+                ) {
+                    substituted.push(operand.clone());
+                    operand = tokens.next()?;
+                }
+                self.is_defined(name(operand)?)?;
+                substituted.push(operand.clone());
+                continue;
+            }
+            if expanding.contains(&macro_name) {
+                substituted.push(token.clone());
+                continue;
+            }
+            match self.definitions.get(&macro_name) {
+                Some(Definition::Object(value)) => {
+                    let value: Vec<Token> = self
+                        .lexer(value)
+                        .filter_map(Result::ok)
+                        .filter(|token| !matches!(token.token_type, TokenType::Comment(_)))
+                        .collect();
+                    expanding.push(macro_name);
+                    substituted.extend(self.substitute(&value, expanding)?);
+                    expanding.pop();
+                }
+                Some(Definition::Undefined) => substituted.push(token.clone()),
+                // A keyword that is not a macro keeps its meaning.
+                None if matches!(token.token_type, TokenType::Keyword(_)) => {
+                    substituted.push(token.clone())
+                }
+                Some(Definition::Function) | None => return None,
+            }
+        }
+        Some(substituted)
+    }
+}
+
+/// The name of an identifier or keyword, which both can name macros.
+fn name(token: &Token) -> Option<Symbol> {
+    match &token.token_type {
+        TokenType::Identifier(_) | TokenType::Keyword(_) => token.symbol,
+        _ => None,
+    }
+}
+
+fn is_operator(token: &Token, operator: Operator) -> bool {
+    matches!(token.token_type, TokenType::Operator(found) if found == operator)
+}
+
+/// Moves past `operator` if `tokens` starts with it.
+fn eat(tokens: &mut &[Token], operator: Operator) -> bool {
+    let found = tokens
+        .first()
+        .is_some_and(|token| is_operator(token, operator));
+    if found {
+        *tokens = &tokens[1..];
+    }
+    found
+}
+
+fn eat_colon(tokens: &mut &[Token]) -> bool {
+    let found = tokens
+        .first()
+        .is_some_and(|token| matches!(token.token_type, TokenType::Delimiter(Delimiter::Colon)));
+    if found {
+        *tokens = &tokens[1..];
+    }
+    found
+}
+
+/// The length of the parenthesized group `tokens` starts with, or `None`
+/// if they do not start with `(` or it is not closed.
+fn group_end(tokens: &[Token]) -> Option<usize> {
+    if !matches!(
+        tokens.first()?.token_type,
+        TokenType::Delimiter(Delimiter::OpeningParenthesis)
+    ) {
+        return None;
+    }
+    let mut depth = 0;
+    for (index, token) in tokens.iter().enumerate() {
+        match token.token_type {
+            TokenType::Delimiter(Delimiter::OpeningParenthesis) => depth += 1,
+            TokenType::Delimiter(Delimiter::ClosingParenthesis) => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(index + 1);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+fn and(left: Outcome, right: Outcome) -> Outcome {
+    match (left, right) {
+        (Some(false), _) | (_, Some(false)) => Some(false),
+        (Some(true), Some(true)) => Some(true),
+        _ => None,
+    }
+}
+
+fn or(left: Outcome, right: Outcome) -> Outcome {
+    match (left, right) {
+        (Some(true), _) | (_, Some(true)) => Some(true),
+        (Some(false), Some(false)) => Some(false),
+        _ => None,
+    }
+}
+
+/// The offset of the start of the line containing `offset`.
+fn line_start(source: &str, offset: usize) -> usize {
+    source[..offset].rfind('\n').map_or(0, |index| index + 1)
+}
+
+/// The offset just past the newline ending the line containing `offset`.
+fn line_end(source: &str, offset: usize) -> usize {
+    source[offset..]
+        .find('\n')
+        .map_or(source.len(), |index| offset + index + 1)
+}
+
+/// Renames the directive on `line` to `name`, keeping the `#` (or `%:`)
+/// and the spacing around it. With `drop_operands` the rest of the line
+/// goes, too.
+fn replace_directive(line: &str, name: &str, drop_operands: bool) -> String {
+    let hash = match (line.find('#'), line.find("%:")) {
+        (Some(hash), Some(digraph)) if digraph < hash => digraph + 2,
+        (Some(hash), _) => hash + 1,
+        (None, Some(digraph)) => digraph + 2,
+        (None, None) => 0,
+    };
+    let name_start = hash + line[hash..].len() - line[hash..].trim_start().len();
+    let name_end = line[name_start..]
+        .find(|character: char| !character.is_ascii_alphabetic())
+        .map_or(line.len(), |index| name_start + index);
+    let rest = if drop_operands {
+        &line[line.trim_end_matches(['\r', '\n']).len()..]
+    } else {
+        &line[name_end..]
+    };
+    format!("{}{}{}", &line[..name_start], name, rest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `source` with the conditionals resolved against `defined` (each a
+    /// name and its value) and `undefined`.
+    fn unifdef(source: &str, defined: &[(&str, &str)], undefined: &[&str]) -> String {
+        let mut unifdef = Unifdef::new("test.c").with_standard(Standard::C23);
+        for (name, value) in defined {
+            unifdef = unifdef.with_definition(name, value);
+        }
+        for name in undefined {
+            unifdef = unifdef.with_undefinition(name);
+        }
+        unifdef
+            .process(source)
+            .unwrap_or_else(|error| panic!("{}: {}", source, error))
+    }
+
+    #[test]
+    fn elif_starts_or_ends_the_group_when_earlier_branches_resolve() {
+        let source = "#if A\na\n#elif B\nb\n#else\nc\n#endif\n";
+        for (defined, expected) in [
+            // The removed first branch leaves `#elif B` to start the group.
+            (&[("A", "0")][..], "#if B\nb\n#else\nc\n#endif\n"),
+            // A true `#elif` after a kept branch becomes its `#else`.
+            (&[("B", "1")], "#if A\na\n#else\nb\n#endif\n"),
+            (&[("A", "1")], "a\n"),
+            (&[("A", "0"), ("B", "0")], "c\n"),
+            (&[("A", "0"), ("B", "1")], "b\n"),
+            (&[], source),
+        ] {
+            assert_eq!(unifdef(source, defined, &[]), expected, "{:?}", defined);
+        }
+        assert_eq!(
+            unifdef("#ifdef A\na\n#elifdef B\nb\n#endif\n", &[], &["A"]),
+            "#ifdef B\nb\n#endif\n"
+        );
+        assert_eq!(
+            unifdef("#ifdef A\na\n#elifndef B\nb\n#endif\n", &[], &["B"]),
+            "#ifdef A\na\n#else\nb\n#endif\n"
+        );
+    }
+
+    #[test]
+    fn a_known_operand_can_decide_an_unknown_condition() {
+        for (condition, expected) in [
+            ("A && U", Some(false)),
+            ("U && A", Some(false)),
+            ("B || U", Some(true)),
+            ("U || B", Some(true)),
+            ("!B && U", Some(false)),
+            ("!(B || U)", Some(false)),
+            ("!!(U || B)", Some(true)),
+            ("(U && A) || B", Some(true)),
+            ("defined(U) && !defined(B)", Some(false)),
+            ("U ? B : B", Some(true)),
+            ("A ? U : B", Some(true)),
+            ("B && U", None),
+            ("A || U", None),
+            ("!U", None),
+        ] {
+            let source = format!("#if {}\nyes\n#else\nno\n#endif\n", condition);
+            let expected = match expected {
+                Some(true) => "yes\n".to_string(),
+                Some(false) => "no\n".to_string(),
+                None => source.clone(),
+            };
+            assert_eq!(
+                unifdef(&source, &[("A", "0"), ("B", "1")], &[]),
+                expected,
+                "{}",
+                condition
+            );
+        }
+    }
+
+    #[test]
+    fn object_like_macros_are_substituted() {
+        let defined = [
+            ("VERSION", "3"),
+            ("ALIAS", "VERSION"),
+            ("SUM", "VERSION + ALIAS"),
+            ("SELF", "SELF + 1"),
+            ("f(x)", "x"),
+        ];
+        for (condition, expected) in [
+            ("VERSION >= 2", "yes\n"),
+            ("ALIAS == 3", "yes\n"),
+            ("SUM == 6", "yes\n"),
+            // A macro is not replaced inside itself, so the inner `SELF`
+            // is an identifier and evaluates to 0.
+            ("SELF == 1", "yes\n"),
+            ("VERSION < 2", ""),
+        ] {
+            let source = format!("#if {}\nyes\n#endif\n", condition);
+            assert_eq!(unifdef(&source, &defined, &[]), expected, "{}", condition);
+        }
+        // A function-like macro is defined, but its uses are unknown.
+        for source in [
+            "#if f(1)\nyes\n#endif\n",
+            "#if VERSION + UNKNOWN\nyes\n#endif\n",
+        ] {
+            assert_eq!(unifdef(source, &defined, &[]), source);
+        }
+        assert_eq!(unifdef("#ifdef f\nyes\n#endif\n", &defined, &[]), "yes\n");
+    }
+
+    #[test]
+    fn nested_conditionals_resolve_independently() {
+        let source = "#if U\n#if A\na\n#else\nb\n#endif\n#endif\n";
+        assert_eq!(unifdef(source, &[("A", "1")], &[]), "#if U\na\n#endif\n");
+        let source = "#if A\n#if U\na\n#endif\n#else\n#if U\nb\n#endif\n#endif\n";
+        assert_eq!(unifdef(source, &[("A", "1")], &[]), "#if U\na\n#endif\n");
+        assert_eq!(unifdef(source, &[("A", "0")], &[]), "#if U\nb\n#endif\n");
+        // Conditionals inside a removed branch go with it, even unbalanced
+        // looking ones like `#else` of the inner group.
+        let source = "#if A\n#ifdef X\nx\n#else\ny\n#endif\n#endif\nz\n";
+        assert_eq!(unifdef(source, &[("A", "0")], &[]), "z\n");
+    }
+
+    #[test]
+    fn bytes_outside_resolved_directives_are_kept() {
+        let source = "int a; \\\n  int b;\r\n#if A\r\nkept;\r\n#elif U\r\ngone;\r\n#endif\r\n\tx\\\r\ny /* c */\r\n";
+        assert_eq!(
+            unifdef(source, &[("A", "1")], &[]),
+            "int a; \\\n  int b;\r\nkept;\r\n\tx\\\r\ny /* c */\r\n"
+        );
+        assert_eq!(
+            unifdef(source, &[("A", "0")], &[]),
+            "int a; \\\n  int b;\r\n#if U\r\ngone;\r\n#endif\r\n\tx\\\r\ny /* c */\r\n"
+        );
+        // A directive continued with a line splice is removed whole.
+        assert_eq!(
+            unifdef("#if A && \\\n  U\nx\n#endif\ny\n", &[("A", "0")], &[]),
+            "y\n"
+        );
+        let source = "#if U\r\n#  define X 1 \\\r\n  + 2\r\n#endif\r\n";
+        assert_eq!(unifdef(source, &[("A", "0")], &[]), source);
+        assert_eq!(
+            unifdef("#if U\r\na\r\n#elif 1\r\nb\r\n#endif\r\n", &[], &[]),
+            "#if U\r\na\r\n#else\r\nb\r\n#endif\r\n"
+        );
+    }
+}